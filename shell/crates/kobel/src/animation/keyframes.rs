@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use crate::animation::{linear, KobelAnimatable, KobelAnimationClock, KobelEasing};
+
+#[derive(Debug, Clone, Copy)]
+struct KobelKeyframe<T> {
+    /// Time since the previous keyframe.
+    after: Duration,
+    value: T,
+    /// Curve used on the way from the previous keyframe to this one.
+    easing: KobelEasing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KobelPlayback {
+    Idle,
+    Playing(Instant),
+    Finished,
+}
+
+/// Plays a value through a fixed sequence of keyframes, for effects that are
+/// more than a single transition, such as a bounce or a pulse.
+#[derive(Debug, Clone)]
+pub struct KobelKeyframes<T: KobelAnimatable> {
+    initial: T,
+    keyframes: Vec<KobelKeyframe<T>>,
+    playback: KobelPlayback,
+}
+
+impl<T: KobelAnimatable> KobelKeyframes<T> {
+    pub fn new(initial: T) -> Self {
+        Self {
+            initial,
+            keyframes: vec![],
+            playback: KobelPlayback::Idle,
+        }
+    }
+
+    /// Adds a keyframe reached `after` the previous one, moving there linearly.
+    pub fn then(self, after: Duration, value: T) -> Self {
+        self.then_eased(after, value, linear)
+    }
+
+    pub fn then_eased(mut self, after: Duration, value: T, easing: KobelEasing) -> Self {
+        self.keyframes.push(KobelKeyframe {
+            after,
+            value,
+            easing,
+        });
+        self
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.keyframes.iter().map(|keyframe| keyframe.after).sum()
+    }
+
+    fn final_value(&self) -> T {
+        self.keyframes.last().map_or(self.initial, |keyframe| keyframe.value)
+    }
+
+    /// Plays the sequence from the start. Under reduce motion it ends right away.
+    pub fn start(&mut self, clock: &KobelAnimationClock) {
+        self.playback = if clock.reduce_motion() {
+            KobelPlayback::Finished
+        } else {
+            KobelPlayback::Playing(clock.now())
+        };
+    }
+
+    /// The value before the sequence has been started is its initial value,
+    /// and after it has finished, its final one.
+    pub fn value(&self, clock: &KobelAnimationClock) -> T {
+        let started_at = match self.playback {
+            KobelPlayback::Idle => return self.initial,
+            KobelPlayback::Finished => return self.final_value(),
+            KobelPlayback::Playing(_) if clock.reduce_motion() => return self.final_value(),
+            KobelPlayback::Playing(started_at) => started_at,
+        };
+
+        let mut remaining = clock.now().saturating_duration_since(started_at);
+        let mut from = self.initial;
+
+        for keyframe in &self.keyframes {
+            if remaining < keyframe.after {
+                let progress = remaining.as_secs_f32() / keyframe.after.as_secs_f32();
+                return from.lerp(keyframe.value, (keyframe.easing)(progress));
+            }
+
+            remaining -= keyframe.after;
+            from = keyframe.value;
+        }
+
+        from
+    }
+
+    pub fn is_animating(&self, clock: &KobelAnimationClock) -> bool {
+        match self.playback {
+            KobelPlayback::Playing(started_at) => !clock.reduce_motion() && clock.now() < started_at + self.duration(),
+            KobelPlayback::Idle | KobelPlayback::Finished => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::Size;
+
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn pulse() -> KobelKeyframes<f32> {
+        KobelKeyframes::new(1.0_f32)
+            .then(ms(100), 1.5)
+            .then_eased(ms(200), 1.0, |t| t * t)
+    }
+
+    #[test]
+    fn holds_the_initial_value_until_started() {
+        let clock = KobelAnimationClock::new(Instant::now(), false);
+        let keyframes = pulse();
+
+        assert_eq!(keyframes.value(&clock), 1.0);
+        assert!(!keyframes.is_animating(&clock));
+    }
+
+    #[test]
+    fn plays_each_segment_with_its_own_easing() {
+        let mut clock = KobelAnimationClock::new(Instant::now(), false);
+        let mut keyframes = pulse();
+
+        keyframes.start(&clock);
+        assert_eq!(keyframes.duration(), ms(300));
+
+        clock.advance(ms(50));
+        assert_eq!(keyframes.value(&clock), 1.25);
+
+        clock.advance(ms(50));
+        assert_eq!(keyframes.value(&clock), 1.5);
+
+        clock.advance(ms(100));
+        assert_eq!(keyframes.value(&clock), 1.375);
+        assert!(keyframes.is_animating(&clock));
+
+        clock.advance(ms(100));
+        assert_eq!(keyframes.value(&clock), 1.0);
+        assert!(!keyframes.is_animating(&clock));
+    }
+
+    #[test]
+    fn instant_keyframes_jump() {
+        let mut clock = KobelAnimationClock::new(Instant::now(), false);
+        let mut keyframes = KobelKeyframes::new(Size::new(0.0, 0.0))
+            .then(Duration::ZERO, Size::new(10.0, 10.0))
+            .then(ms(100), Size::new(20.0, 10.0));
+
+        keyframes.start(&clock);
+        assert_eq!(keyframes.value(&clock), Size::new(10.0, 10.0));
+
+        clock.advance(ms(50));
+        assert_eq!(keyframes.value(&clock), Size::new(15.0, 10.0));
+    }
+
+    #[test]
+    fn reduce_motion_shows_the_final_value() {
+        let clock = KobelAnimationClock::new(Instant::now(), true);
+        let mut keyframes = KobelKeyframes::new(0.0_f32)
+            .then(ms(100), 1.0)
+            .then(ms(100), 0.5);
+
+        keyframes.start(&clock);
+
+        assert!(!keyframes.is_animating(&clock));
+        assert_eq!(keyframes.value(&clock), 0.5);
+    }
+}
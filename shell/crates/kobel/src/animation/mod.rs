@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+use iced::{Color, Size, Vector};
+
+pub mod keyframes;
+pub mod spring;
+pub mod timeline;
+
+pub use keyframes::KobelKeyframes;
+pub use spring::KobelSpring;
+pub use timeline::KobelTimeline;
+
+/// An easing curve mapping progress in `0.0..=1.0` to eased progress, such as
+/// `easing_function::easings::Linear::ease`. Curves like elastic or back may
+/// leave the range in between, but must end on `1.0`.
+pub type KobelEasing = fn(f32) -> f32;
+
+pub fn linear(progress: f32) -> f32 {
+    easing_function::easings::Linear::ease(progress)
+}
+
+/// The time that animations are evaluated at, together with the user's motion
+/// preference. Animations never read the system clock themselves, so tests can
+/// drive them with a clock that only moves when told to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KobelAnimationClock {
+    now: Instant,
+    reduce_motion: bool,
+}
+
+impl KobelAnimationClock {
+    pub fn new(now: Instant, reduce_motion: bool) -> Self {
+        Self {
+            now,
+            reduce_motion,
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// When set, animations jump straight to their final value.
+    pub fn reduce_motion(&self) -> bool {
+        self.reduce_motion
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+}
+
+/// A value that can be interpolated and driven by a spring.
+pub trait KobelAnimatable: Copy + PartialEq + std::fmt::Debug {
+    fn add(self, other: Self) -> Self;
+
+    fn sub(self, other: Self) -> Self;
+
+    fn scale(self, factor: f32) -> Self;
+
+    /// Largest absolute component, used to decide when a spring has come to rest.
+    fn magnitude(self) -> f32;
+
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self.add(to.sub(self).scale(t))
+    }
+}
+
+impl KobelAnimatable for f32 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn magnitude(self) -> f32 {
+        self.abs()
+    }
+}
+
+impl KobelAnimatable for Vector {
+    fn add(self, other: Self) -> Self {
+        Vector::new(self.x + other.x, self.y + other.y)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Vector::new(self.x - other.x, self.y - other.y)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Vector::new(self.x * factor, self.y * factor)
+    }
+
+    fn magnitude(self) -> f32 {
+        self.x.abs().max(self.y.abs())
+    }
+}
+
+impl KobelAnimatable for Size {
+    fn add(self, other: Self) -> Self {
+        Size::new(self.width + other.width, self.height + other.height)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Size::new(self.width - other.width, self.height - other.height)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Size::new(self.width * factor, self.height * factor)
+    }
+
+    fn magnitude(self) -> f32 {
+        self.width.abs().max(self.height.abs())
+    }
+}
+
+impl KobelAnimatable for Color {
+    fn add(self, other: Self) -> Self {
+        Color::from_rgba(self.r + other.r, self.g + other.g, self.b + other.b, self.a + other.a)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Color::from_rgba(self.r - other.r, self.g - other.g, self.b - other.b, self.a - other.a)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Color::from_rgba(self.r * factor, self.g * factor, self.b * factor, self.a * factor)
+    }
+
+    fn magnitude(self) -> f32 {
+        [self.r, self.g, self.b, self.a]
+            .into_iter()
+            .fold(0.0, |max, c| max.max(c.abs()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_interpolates_every_component() {
+        assert_eq!(0.0_f32.lerp(10.0, 0.25), 2.5);
+        assert_eq!(Vector::new(0.0, 10.0).lerp(Vector::new(10.0, 0.0), 0.5), Vector::new(5.0, 5.0));
+        assert_eq!(Size::new(100.0, 0.0).lerp(Size::new(200.0, 50.0), 0.5), Size::new(150.0, 25.0));
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 0.5), Color::from_rgb(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn clock_only_moves_when_advanced() {
+        let start = Instant::now();
+        let mut clock = KobelAnimationClock::new(start, false);
+
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_millis(16));
+        assert_eq!(clock.now(), start + Duration::from_millis(16));
+    }
+}
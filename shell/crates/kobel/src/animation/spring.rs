@@ -0,0 +1,256 @@
+use std::time::{Duration, Instant};
+
+use crate::animation::{KobelAnimatable, KobelAnimationClock};
+
+// Springs are integrated in fixed steps, so the motion looks the same no
+// matter how irregularly frames arrive
+static SPRING_STEP: Duration = Duration::from_micros(2083);
+
+/// Moves a value towards a target like a damped spring. Unlike a timeline it
+/// has no fixed duration, and keeps its velocity when retargeted, which suits
+/// values that follow the pointer or change direction halfway.
+#[derive(Debug, Clone, Copy)]
+pub struct KobelSpring<T: KobelAnimatable> {
+    value: T,
+    velocity: T,
+    target: T,
+    updated_at: Option<Instant>,
+
+    stiffness: f32,
+    damping: f32,
+    mass: f32,
+    rest_threshold: f32,
+}
+
+impl<T: KobelAnimatable> KobelSpring<T> {
+    /// Creates a spring resting at `value`, tuned to settle quickly without overshooting much.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            velocity: value.scale(0.0),
+            target: value,
+            updated_at: None,
+
+            stiffness: 300.0,
+            damping: 30.0,
+            mass: 1.0,
+            rest_threshold: 0.001,
+        }
+    }
+
+    pub fn stiffness(mut self, stiffness: f32) -> Self {
+        self.stiffness = stiffness;
+        self
+    }
+
+    pub fn damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Distance and speed below which the spring snaps to its target and stops.
+    pub fn rest_threshold(mut self, rest_threshold: f32) -> Self {
+        self.rest_threshold = rest_threshold;
+        self
+    }
+
+    pub fn target(&self) -> T {
+        self.target
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn set_target(&mut self, target: T, clock: &KobelAnimationClock) {
+        if clock.reduce_motion() {
+            self.snap_to(target);
+            return;
+        }
+
+        // Catch up first, so the time before the change is spent moving
+        // towards the old target
+        self.tick(clock);
+
+        self.target = target;
+        if self.updated_at.is_none() {
+            self.updated_at = Some(clock.now());
+        }
+    }
+
+    /// Jumps to `value` and stops moving.
+    pub fn snap_to(&mut self, value: T) {
+        self.value = value;
+        self.target = value;
+        self.velocity = value.scale(0.0);
+        self.updated_at = None;
+    }
+
+    /// Advances the spring to the clock's time and returns the new value.
+    pub fn tick(&mut self, clock: &KobelAnimationClock) -> T {
+        let Some(updated_at) = self.updated_at else {
+            return self.value;
+        };
+
+        if clock.reduce_motion() {
+            self.snap_to(self.target);
+            return self.value;
+        }
+
+        let elapsed = clock.now().saturating_duration_since(updated_at);
+        let steps = (elapsed.as_secs_f64() / SPRING_STEP.as_secs_f64()) as u32;
+        let step = SPRING_STEP.as_secs_f32();
+
+        for _ in 0..steps {
+            let displacement = self.value.sub(self.target);
+            let force = displacement.scale(-self.stiffness).sub(self.velocity.scale(self.damping));
+
+            self.velocity = self.velocity.add(force.scale(step / self.mass));
+            self.value = self.value.add(self.velocity.scale(step));
+
+            if self.is_at_rest() {
+                self.snap_to(self.target);
+                return self.value;
+            }
+        }
+
+        // Keep the remainder, so short frames still add up to whole steps
+        self.updated_at = Some(updated_at + SPRING_STEP * steps);
+
+        self.value
+    }
+
+    fn is_at_rest(&self) -> bool {
+        self.value.sub(self.target).magnitude() < self.rest_threshold
+            && self.velocity.magnitude() < self.rest_threshold
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.updated_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::Vector;
+
+    use super::*;
+
+    fn clock() -> KobelAnimationClock {
+        KobelAnimationClock::new(Instant::now(), false)
+    }
+
+    fn run_for(spring: &mut KobelSpring<f32>, clock: &mut KobelAnimationClock, duration: Duration) -> Vec<f32> {
+        let frame = Duration::from_millis(16);
+        let mut values = vec![];
+        let mut elapsed = Duration::ZERO;
+
+        while elapsed < duration {
+            clock.advance(frame);
+            elapsed += frame;
+            values.push(spring.tick(clock));
+        }
+
+        values
+    }
+
+    #[test]
+    fn settles_on_the_target() {
+        let mut clock = clock();
+        let mut spring = KobelSpring::new(0.0_f32);
+
+        spring.set_target(100.0, &clock);
+        run_for(&mut spring, &mut clock, Duration::from_secs(2));
+
+        assert_eq!(spring.value(), 100.0);
+        assert!(!spring.is_animating());
+    }
+
+    #[test]
+    fn critically_damped_spring_does_not_overshoot() {
+        let mut clock = clock();
+        // damping = 2 * sqrt(stiffness * mass)
+        let mut spring = KobelSpring::new(0.0_f32).stiffness(100.0).damping(20.0);
+
+        spring.set_target(1.0, &clock);
+        let values = run_for(&mut spring, &mut clock, Duration::from_secs(2));
+
+        assert!(values.iter().all(|v| *v <= 1.0));
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn underdamped_spring_overshoots() {
+        let mut clock = clock();
+        let mut spring = KobelSpring::new(0.0_f32).stiffness(300.0).damping(5.0);
+
+        spring.set_target(1.0, &clock);
+        let values = run_for(&mut spring, &mut clock, Duration::from_secs(3));
+
+        assert!(values.iter().any(|v| *v > 1.0));
+        assert_eq!(spring.value(), 1.0);
+    }
+
+    #[test]
+    fn motion_does_not_depend_on_the_frame_rate() {
+        let mut fast_clock = clock();
+        let mut slow_clock = fast_clock;
+        let mut fast = KobelSpring::new(0.0_f32);
+        let mut slow = KobelSpring::new(0.0_f32);
+
+        fast.set_target(1.0, &fast_clock);
+        slow.set_target(1.0, &slow_clock);
+
+        for _ in 0..6 {
+            fast_clock.advance(Duration::from_micros(8333));
+            fast.tick(&fast_clock);
+        }
+        slow_clock.advance(Duration::from_micros(49998));
+        slow.tick(&slow_clock);
+
+        assert!((fast.value() - slow.value()).abs() < 0.001);
+    }
+
+    #[test]
+    fn retargeting_keeps_the_velocity() {
+        let mut clock = clock();
+        let mut spring = KobelSpring::new(0.0_f32);
+
+        spring.set_target(100.0, &clock);
+        run_for(&mut spring, &mut clock, Duration::from_millis(100));
+        let before = spring.value();
+
+        spring.set_target(0.0, &clock);
+        clock.advance(Duration::from_millis(16));
+
+        // Still carried forward by its momentum
+        assert!(spring.tick(&clock) > before);
+    }
+
+    #[test]
+    fn animates_vectors() {
+        let mut clock = KobelAnimationClock::new(Instant::now(), false);
+        let mut spring = KobelSpring::new(Vector::new(0.0, 0.0));
+
+        spring.set_target(Vector::new(0.0, -80.0), &clock);
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(spring.tick(&clock), Vector::new(0.0, -80.0));
+    }
+
+    #[test]
+    fn reduce_motion_jumps_to_the_target() {
+        let clock = KobelAnimationClock::new(Instant::now(), true);
+        let mut spring = KobelSpring::new(0.0_f32);
+
+        spring.set_target(1.0, &clock);
+
+        assert_eq!(spring.value(), 1.0);
+        assert!(!spring.is_animating());
+    }
+}
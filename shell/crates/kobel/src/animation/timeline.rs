@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use crate::animation::{linear, KobelAnimatable, KobelAnimationClock, KobelEasing};
+
+/// Moves a value to a target over a fixed duration, following an easing curve.
+/// Retargeting while running starts from wherever the value currently is, so
+/// interrupted transitions (such as hovering in and out quickly) do not jump.
+#[derive(Debug, Clone, Copy)]
+pub struct KobelTimeline<T: KobelAnimatable> {
+    from: T,
+    to: T,
+    started_at: Option<Instant>,
+    delay: Duration,
+    duration: Duration,
+    easing: KobelEasing,
+}
+
+impl<T: KobelAnimatable> KobelTimeline<T> {
+    /// Creates a timeline resting at `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            from: value,
+            to: value,
+            started_at: None,
+            delay: Duration::ZERO,
+            duration: Duration::from_millis(200),
+            easing: linear,
+        }
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn easing(mut self, easing: KobelEasing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn target(&self) -> T {
+        self.to
+    }
+
+    pub fn animate_to(&mut self, to: T, clock: &KobelAnimationClock) {
+        if clock.reduce_motion() || self.duration.is_zero() {
+            self.snap_to(to);
+            return;
+        }
+
+        if to == self.to && self.started_at.is_some() {
+            return;
+        }
+
+        self.from = self.value(clock);
+        self.to = to;
+        self.started_at = Some(clock.now() + self.delay);
+    }
+
+    /// Jumps to `value` without animating.
+    pub fn snap_to(&mut self, value: T) {
+        self.from = value;
+        self.to = value;
+        self.started_at = None;
+    }
+
+    pub fn progress(&self, clock: &KobelAnimationClock) -> f32 {
+        let Some(started_at) = self.started_at else {
+            return 1.0;
+        };
+
+        if clock.reduce_motion() {
+            return 1.0;
+        }
+
+        let elapsed = clock.now().saturating_duration_since(started_at);
+        (elapsed.as_secs_f32() / self.duration.as_secs_f32()).clamp(0.0, 1.0)
+    }
+
+    pub fn value(&self, clock: &KobelAnimationClock) -> T {
+        match self.progress(clock) {
+            progress if progress >= 1.0 => self.to,
+            progress => self.from.lerp(self.to, (self.easing)(progress)),
+        }
+    }
+
+    pub fn is_animating(&self, clock: &KobelAnimationClock) -> bool {
+        self.progress(clock) < 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock() -> KobelAnimationClock {
+        KobelAnimationClock::new(Instant::now(), false)
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn rests_until_animated() {
+        let clock = clock();
+        let timeline = KobelTimeline::new(0.5_f32);
+
+        assert_eq!(timeline.value(&clock), 0.5);
+        assert!(!timeline.is_animating(&clock));
+    }
+
+    #[test]
+    fn follows_the_easing_curve() {
+        let mut clock = clock();
+        let mut timeline = KobelTimeline::new(0.0_f32)
+            .duration(ms(100))
+            .easing(|t| t * t);
+
+        timeline.animate_to(1.0, &clock);
+
+        clock.advance(ms(50));
+        assert_eq!(timeline.value(&clock), 0.25);
+        assert!(timeline.is_animating(&clock));
+
+        clock.advance(ms(50));
+        assert_eq!(timeline.value(&clock), 1.0);
+        assert!(!timeline.is_animating(&clock));
+    }
+
+    #[test]
+    fn waits_for_the_delay() {
+        let mut clock = clock();
+        let mut timeline = KobelTimeline::new(0.0_f32)
+            .duration(ms(100))
+            .delay(ms(100));
+
+        timeline.animate_to(1.0, &clock);
+
+        clock.advance(ms(50));
+        assert_eq!(timeline.value(&clock), 0.0);
+        assert!(timeline.is_animating(&clock));
+
+        clock.advance(ms(100));
+        assert_eq!(timeline.value(&clock), 0.5);
+    }
+
+    #[test]
+    fn retargeting_starts_from_the_current_value() {
+        let mut clock = clock();
+        let mut timeline = KobelTimeline::new(0.0_f32).duration(ms(100));
+
+        timeline.animate_to(1.0, &clock);
+        clock.advance(ms(50));
+        timeline.animate_to(0.0, &clock);
+
+        assert_eq!(timeline.value(&clock), 0.5);
+
+        clock.advance(ms(50));
+        assert_eq!(timeline.value(&clock), 0.25);
+    }
+
+    #[test]
+    fn retargeting_to_the_same_value_keeps_running() {
+        let mut clock = clock();
+        let mut timeline = KobelTimeline::new(0.0_f32).duration(ms(100));
+
+        timeline.animate_to(1.0, &clock);
+        clock.advance(ms(50));
+        timeline.animate_to(1.0, &clock);
+
+        clock.advance(ms(50));
+        assert!(!timeline.is_animating(&clock));
+    }
+
+    #[test]
+    fn reduce_motion_jumps_to_the_target() {
+        let clock = KobelAnimationClock::new(Instant::now(), true);
+        let mut timeline = KobelTimeline::new(0.0_f32).duration(ms(100));
+
+        timeline.animate_to(1.0, &clock);
+
+        assert_eq!(timeline.value(&clock), 1.0);
+        assert!(!timeline.is_animating(&clock));
+    }
+
+    #[test]
+    fn reduce_motion_finishes_a_running_animation() {
+        let mut clock = clock();
+        let mut timeline = KobelTimeline::new(0.0_f32).duration(ms(100));
+
+        timeline.animate_to(1.0, &clock);
+        clock.advance(ms(10));
+
+        let reduced = KobelAnimationClock::new(clock.now(), true);
+        assert_eq!(timeline.value(&reduced), 1.0);
+    }
+}
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+
+use iced::{futures::SinkExt, Color, Subscription};
+use serde::Deserialize;
+use tokio::sync::watch;
+use zbus::{object_server::SignalEmitter, zvariant::{OwnedValue, Value}};
+
+use crate::{theme::KobelThemeVariant, KobelRootMessage};
+
+pub static APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+pub static COLOR_SCHEME_KEY: &str = "color-scheme";
+pub static ACCENT_COLOR_KEY: &str = "accent-color";
+
+pub static PORTAL_BUS_NAME: &str = "org.freedesktop.impl.portal.desktop.kobel";
+pub static PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Value of `org.freedesktop.appearance color-scheme`, as defined by the
+/// XDG Settings portal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KobelColorScheme {
+    #[default]
+    NoPreference,
+    PreferDark,
+    PreferLight,
+}
+
+impl KobelColorScheme {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => KobelColorScheme::PreferDark,
+            2 => KobelColorScheme::PreferLight,
+            _ => KobelColorScheme::NoPreference,
+        }
+    }
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            KobelColorScheme::NoPreference => 0,
+            KobelColorScheme::PreferDark => 1,
+            KobelColorScheme::PreferLight => 2,
+        }
+    }
+
+    pub fn variant(self) -> Option<KobelThemeVariant> {
+        match self {
+            KobelColorScheme::NoPreference => None,
+            KobelColorScheme::PreferDark => Some(KobelThemeVariant::Dark),
+            KobelColorScheme::PreferLight => Some(KobelThemeVariant::Light),
+        }
+    }
+
+    fn gsettings_value(self) -> &'static str {
+        match self {
+            KobelColorScheme::NoPreference => "default",
+            KobelColorScheme::PreferDark => "prefer-dark",
+            KobelColorScheme::PreferLight => "prefer-light",
+        }
+    }
+}
+
+impl From<KobelThemeVariant> for KobelColorScheme {
+    fn from(variant: KobelThemeVariant) -> Self {
+        match variant {
+            KobelThemeVariant::Light => KobelColorScheme::PreferLight,
+            KobelThemeVariant::Dark => KobelColorScheme::PreferDark,
+        }
+    }
+}
+
+/// How the shell relates to the desktop-wide color scheme preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KobelColorSchemeMode {
+    /// Switch light/dark and the accent color whenever the system preference changes.
+    #[default]
+    Follow,
+    /// Act as the authority: serve the preference over the Settings portal and
+    /// write it to GSettings, so that GTK and Qt apps follow the shell's toggle.
+    Publish,
+    /// Neither read nor write the system preference.
+    Ignore,
+}
+
+/// The appearance the shell currently shows, as published to other apps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KobelAppearance {
+    pub color_scheme: KobelColorScheme,
+    pub accent_color: Color,
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait PortalSettings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+    #[zbus(signal)]
+    fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+fn parse_color_scheme(value: &Value<'_>) -> Option<KobelColorScheme> {
+    u32::try_from(value).ok().map(KobelColorScheme::from_u32)
+}
+
+// The accent color is an sRGB triple, where any component outside of 0..1
+// means that no accent color has been set.
+fn parse_accent_color(value: &Value<'_>) -> Option<Color> {
+    let (r, g, b) = <(f64, f64, f64)>::try_from(value.try_clone().ok()?).ok()?;
+
+    if [r, g, b].iter().all(|c| (0.0..=1.0).contains(c)) {
+        Some(Color::from_rgb(r as f32, g as f32, b as f32))
+    } else {
+        None
+    }
+}
+
+/// Reads the color scheme and accent color from the portal on `connection`, then
+/// forwards every change until the connection goes away.
+pub async fn follow(
+    connection: &zbus::Connection,
+    output: &mut iced::futures::channel::mpsc::Sender<KobelRootMessage>,
+) -> zbus::Result<()> {
+    use iced::futures::StreamExt;
+
+    let proxy = PortalSettingsProxy::new(connection).await?;
+    let mut changes = proxy.receive_setting_changed().await?;
+
+    match proxy.read_one(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).await {
+        Ok(value) => {
+            if let Some(scheme) = parse_color_scheme(&value) {
+                let _ = output.send(KobelRootMessage::ColorSchemeChanged(scheme)).await;
+            }
+        },
+        Err(e) => log::warn!("Failed to read the system color scheme: {}", e),
+    }
+
+    match proxy.read_one(APPEARANCE_NAMESPACE, ACCENT_COLOR_KEY).await {
+        Ok(value) => {
+            let _ = output.send(KobelRootMessage::AccentColorChanged(parse_accent_color(&value))).await;
+        },
+        Err(e) => log::debug!("No system accent color available: {}", e),
+    }
+
+    while let Some(signal) = changes.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+
+        if args.namespace() != &APPEARANCE_NAMESPACE {
+            continue;
+        }
+
+        let message = match *args.key() {
+            key if key == COLOR_SCHEME_KEY => parse_color_scheme(args.value()).map(KobelRootMessage::ColorSchemeChanged),
+            key if key == ACCENT_COLOR_KEY => Some(KobelRootMessage::AccentColorChanged(parse_accent_color(args.value()))),
+            _ => None,
+        };
+
+        if let Some(message) = message
+            && output.send(message).await.is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Backend side of the Settings portal, so xdg-desktop-portal can hand the
+/// shell's own preference to apps.
+pub struct KobelSettingsPortal {
+    appearance: KobelAppearance,
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.portal.Error")]
+pub enum KobelPortalError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+}
+
+impl KobelSettingsPortal {
+    fn values(&self) -> HashMap<String, OwnedValue> {
+        let accent = self.appearance.accent_color;
+
+        HashMap::from([
+            (
+                COLOR_SCHEME_KEY.to_string(),
+                OwnedValue::from(self.appearance.color_scheme.as_u32()),
+            ),
+            (
+                ACCENT_COLOR_KEY.to_string(),
+                Value::from((accent.r as f64, accent.g as f64, accent.b as f64))
+                    .try_into()
+                    .expect("accent color holds no file descriptors"),
+            ),
+        ])
+    }
+}
+
+// Namespaces passed to ReadAll may end in a `*` to match a whole prefix.
+fn namespace_matches(pattern: &str, namespace: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => namespace.starts_with(prefix),
+        None => pattern == namespace,
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Settings")]
+impl KobelSettingsPortal {
+    async fn read_all(&self, namespaces: Vec<String>) -> HashMap<String, HashMap<String, OwnedValue>> {
+        let wanted = namespaces.is_empty()
+            || namespaces.iter().any(|pattern| pattern.is_empty() || namespace_matches(pattern, APPEARANCE_NAMESPACE));
+
+        if wanted {
+            HashMap::from([(APPEARANCE_NAMESPACE.to_string(), self.values())])
+        } else {
+            HashMap::new()
+        }
+    }
+
+    async fn read(&self, namespace: &str, key: &str) -> Result<OwnedValue, KobelPortalError> {
+        if namespace == APPEARANCE_NAMESPACE
+            && let Some(value) = self.values().remove(key)
+        {
+            return Ok(value);
+        }
+
+        Err(KobelPortalError::NotFound(format!("Requested setting {}.{} not found", namespace, key)))
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        1
+    }
+
+    #[zbus(signal)]
+    async fn setting_changed(emitter: &SignalEmitter<'_>, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Serves the Settings portal backend on `connection` and keeps it, and GSettings,
+/// in sync with `appearance` until the shell exits.
+pub async fn publish(
+    connection: &zbus::Connection,
+    mut appearance: watch::Receiver<KobelAppearance>,
+) -> zbus::Result<()> {
+    let current = *appearance.borrow_and_update();
+
+    connection.object_server()
+        .at(PORTAL_OBJECT_PATH, KobelSettingsPortal { appearance: current })
+        .await?;
+    connection.request_name(PORTAL_BUS_NAME).await?;
+
+    log::info!("Publishing the color scheme as {}", PORTAL_BUS_NAME);
+    write_gsettings(current.color_scheme).await;
+
+    let portal = connection.object_server()
+        .interface::<_, KobelSettingsPortal>(PORTAL_OBJECT_PATH)
+        .await?;
+
+    while appearance.changed().await.is_ok() {
+        let next = *appearance.borrow_and_update();
+        let previous = std::mem::replace(&mut portal.get_mut().await.appearance, next);
+
+        let emitter = portal.signal_emitter();
+
+        if previous.color_scheme != next.color_scheme {
+            KobelSettingsPortal::setting_changed(emitter, APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY, Value::from(next.color_scheme.as_u32())).await?;
+            write_gsettings(next.color_scheme).await;
+        }
+
+        if previous.accent_color != next.accent_color {
+            let accent = next.accent_color;
+            let value = Value::from((accent.r as f64, accent.g as f64, accent.b as f64));
+            KobelSettingsPortal::setting_changed(emitter, APPEARANCE_NAMESPACE, ACCENT_COLOR_KEY, value).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Portal backends such as xdg-desktop-portal-gtk read the preference from
+// GSettings, so keep it in sync as well when it is available.
+async fn write_gsettings(color_scheme: KobelColorScheme) {
+    // Tests serve the portal on a bus of their own and leave the desktop alone
+    if cfg!(test) {
+        return;
+    }
+
+    let result = tokio::process::Command::new("gsettings")
+        .args(["set", "org.gnome.desktop.interface", "color-scheme", color_scheme.gsettings_value()])
+        .status()
+        .await;
+
+    match result {
+        Ok(status) if status.success() => {},
+        Ok(status) => log::debug!("gsettings exited with {}", status),
+        Err(e) => log::debug!("Failed to run gsettings: {}", e),
+    }
+}
+
+pub fn subscription(mode: KobelColorSchemeMode, appearance: watch::Receiver<KobelAppearance>) -> Subscription<KobelRootMessage> {
+    struct AppearanceWatcher;
+
+    if mode == KobelColorSchemeMode::Ignore {
+        return Subscription::none();
+    }
+
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<AppearanceWatcher>(), mode),
+        iced::stream::channel(4, move |mut output| async move {
+            let connection = match zbus::Connection::session().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    log::error!("Failed to connect to the session bus: {}", e);
+                    return;
+                }
+            };
+
+            let result = match mode {
+                KobelColorSchemeMode::Follow => follow(&connection, &mut output).await,
+                KobelColorSchemeMode::Publish => publish(&connection, appearance).await,
+                KobelColorSchemeMode::Ignore => Ok(()),
+            };
+
+            if let Err(e) = result {
+                log::error!("Color scheme {:?} failed: {}", mode, e);
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use iced::futures::{channel::mpsc, StreamExt};
+
+    use super::*;
+    use crate::util::test::KobelPrivateBus;
+
+    /// How `xdg-desktop-portal` sees the backend.
+    #[zbus::proxy(
+        interface = "org.freedesktop.impl.portal.Settings",
+        default_service = "org.freedesktop.impl.portal.desktop.kobel",
+        default_path = "/org/freedesktop/portal/desktop"
+    )]
+    trait PortalBackend {
+        fn read_all(&self, namespaces: &[&str]) -> zbus::Result<HashMap<String, HashMap<String, OwnedValue>>>;
+
+        fn read(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+        #[zbus(signal)]
+        fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+    }
+
+    /// Stands in for `xdg-desktop-portal`, as followed by the shell.
+    struct KobelFakePortal {
+        values: HashMap<(String, String), OwnedValue>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.portal.Settings")]
+    impl KobelFakePortal {
+        fn read_one(&self, namespace: &str, key: &str) -> zbus::fdo::Result<OwnedValue> {
+            self.values.get(&(namespace.to_string(), key.to_string()))
+                .map(|value| value.try_clone().unwrap())
+                .ok_or_else(|| zbus::fdo::Error::Failed(format!("{}.{} not found", namespace, key)))
+        }
+    }
+
+    fn appearance(color_scheme: KobelColorScheme, accent_color: Color) -> KobelAppearance {
+        KobelAppearance { color_scheme, accent_color }
+    }
+
+    /// Runs `publish` on its own connection to `bus`, returning once the
+    /// backend can be called.
+    async fn serve(bus: &KobelPrivateBus, current: KobelAppearance) -> (watch::Sender<KobelAppearance>, PortalBackendProxy<'static>) {
+        let (sender, receiver) = watch::channel(current);
+        let server = bus.connect().await;
+        tokio::spawn(async move { publish(&server, receiver).await });
+
+        let client = bus.connect().await;
+        let dbus = zbus::fdo::DBusProxy::new(&client).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !dbus.name_has_owner(PORTAL_BUS_NAME.try_into().unwrap()).await.unwrap() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+            .await
+            .expect("the portal backend never took its name");
+
+        (sender, PortalBackendProxy::new(&client).await.unwrap())
+    }
+
+    async fn emit(connection: &zbus::Connection, namespace: &str, key: &str, value: Value<'_>) {
+        connection.emit_signal(
+            None::<()>,
+            PORTAL_OBJECT_PATH,
+            "org.freedesktop.portal.Settings",
+            "SettingChanged",
+            &(namespace, key, value),
+        )
+            .await
+            .unwrap();
+    }
+
+    async fn next<S: StreamExt + Unpin>(stream: &mut S) -> S::Item {
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("nothing arrived")
+            .expect("the stream ended")
+    }
+
+    #[test]
+    fn matches_namespace_prefixes() {
+        assert!(namespace_matches("org.freedesktop.appearance", APPEARANCE_NAMESPACE));
+        assert!(namespace_matches("org.freedesktop.*", APPEARANCE_NAMESPACE));
+        assert!(namespace_matches("*", APPEARANCE_NAMESPACE));
+        assert!(!namespace_matches("org.freedesktop", APPEARANCE_NAMESPACE));
+        assert!(!namespace_matches("org.gnome.*", APPEARANCE_NAMESPACE));
+    }
+
+    #[test]
+    fn parses_accent_colors_in_range() {
+        assert_eq!(parse_accent_color(&Value::from((0.0, 0.5, 1.0))), Some(Color::from_rgb(0.0, 0.5, 1.0)));
+        assert_eq!(parse_accent_color(&Value::from((1.5, 0.0, 0.0))), None);
+        assert_eq!(parse_accent_color(&Value::from((-1.0, -1.0, -1.0))), None);
+        assert_eq!(parse_accent_color(&Value::from(1u32)), None);
+    }
+
+    #[tokio::test]
+    async fn reads_settings_over_the_bus() {
+        let Some(bus) = KobelPrivateBus::start() else {
+            eprintln!("Skipping, as dbus-daemon is not installed");
+            return;
+        };
+
+        let (_sender, backend) = serve(&bus, appearance(KobelColorScheme::PreferDark, Color::from_rgb(0.0, 0.5, 1.0))).await;
+
+        for namespaces in [&[][..], &[""], &["org.freedesktop.*"], &["org.gnome.*", "org.freedesktop.appearance"]] {
+            let all = backend.read_all(namespaces).await.unwrap();
+            let values = &all[APPEARANCE_NAMESPACE];
+            assert_eq!(parse_color_scheme(&values[COLOR_SCHEME_KEY]), Some(KobelColorScheme::PreferDark), "{:?}", namespaces);
+            assert_eq!(parse_accent_color(&values[ACCENT_COLOR_KEY]), Some(Color::from_rgb(0.0, 0.5, 1.0)), "{:?}", namespaces);
+        }
+        assert!(backend.read_all(&["org.gnome.*", "org.freedesktop"]).await.unwrap().is_empty());
+
+        let scheme = backend.read(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).await.unwrap();
+        assert_eq!(parse_color_scheme(&scheme), Some(KobelColorScheme::PreferDark));
+
+        for (namespace, key) in [(APPEARANCE_NAMESPACE, "contrast"), ("org.gnome.desktop.interface", COLOR_SCHEME_KEY)] {
+            let Err(zbus::Error::MethodError(name, _, _)) = backend.read(namespace, key).await else {
+                panic!("{}.{} was found", namespace, key);
+            };
+            assert_eq!(name.as_str(), "org.freedesktop.portal.Error.NotFound");
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_only_changed_settings() {
+        let Some(bus) = KobelPrivateBus::start() else {
+            eprintln!("Skipping, as dbus-daemon is not installed");
+            return;
+        };
+
+        let blue = Color::from_rgb(0.0, 0.5, 1.0);
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        let (sender, backend) = serve(&bus, appearance(KobelColorScheme::PreferDark, blue)).await;
+        let mut changes = backend.receive_setting_changed().await.unwrap();
+
+        let mut next_change = async || {
+            let signal = next(&mut changes).await;
+            let args = signal.args().unwrap();
+            assert_eq!(*args.namespace(), APPEARANCE_NAMESPACE);
+            (args.key().to_string(), args.value().try_to_owned().unwrap())
+        };
+
+        sender.send(appearance(KobelColorScheme::PreferLight, blue)).unwrap();
+        let (key, value) = next_change().await;
+        assert_eq!(key, COLOR_SCHEME_KEY);
+        assert_eq!(parse_color_scheme(&value), Some(KobelColorScheme::PreferLight));
+
+        // Sending the same appearance again emits nothing, so the accent
+        // color is the next change to arrive
+        sender.send(appearance(KobelColorScheme::PreferLight, blue)).unwrap();
+        sender.send(appearance(KobelColorScheme::PreferLight, red)).unwrap();
+        let (key, value) = next_change().await;
+        assert_eq!(key, ACCENT_COLOR_KEY);
+        assert_eq!(parse_accent_color(&value), Some(red));
+    }
+
+    #[tokio::test]
+    async fn follows_the_portal() {
+        let Some(bus) = KobelPrivateBus::start() else {
+            eprintln!("Skipping, as dbus-daemon is not installed");
+            return;
+        };
+
+        let portal = bus.connect().await;
+        let values = HashMap::from([
+            ((APPEARANCE_NAMESPACE.to_string(), COLOR_SCHEME_KEY.to_string()), OwnedValue::from(1u32)),
+            ((APPEARANCE_NAMESPACE.to_string(), ACCENT_COLOR_KEY.to_string()), Value::from((0.0, 0.5, 1.0)).try_into().unwrap()),
+        ]);
+        portal.object_server().at(PORTAL_OBJECT_PATH, KobelFakePortal { values }).await.unwrap();
+        portal.request_name("org.freedesktop.portal.Desktop").await.unwrap();
+
+        let (mut output, mut messages) = mpsc::channel(4);
+        let shell = bus.connect().await;
+        tokio::spawn(async move { follow(&shell, &mut output).await });
+
+        let KobelRootMessage::ColorSchemeChanged(scheme) = next(&mut messages).await else {
+            panic!("the color scheme was not read first");
+        };
+        assert_eq!(scheme, KobelColorScheme::PreferDark);
+        let KobelRootMessage::AccentColorChanged(accent) = next(&mut messages).await else {
+            panic!("the accent color was not read");
+        };
+        assert_eq!(accent, Some(Color::from_rgb(0.0, 0.5, 1.0)));
+
+        // Other namespaces and keys are skipped
+        emit(&portal, "org.gnome.desktop.interface", COLOR_SCHEME_KEY, Value::from(0u32)).await;
+        emit(&portal, APPEARANCE_NAMESPACE, "contrast", Value::from(1u32)).await;
+        emit(&portal, APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY, Value::from(2u32)).await;
+        let KobelRootMessage::ColorSchemeChanged(scheme) = next(&mut messages).await else {
+            panic!("the color scheme change was not forwarded");
+        };
+        assert_eq!(scheme, KobelColorScheme::PreferLight);
+
+        emit(&portal, APPEARANCE_NAMESPACE, ACCENT_COLOR_KEY, Value::from((1.0, 0.0, 0.0))).await;
+        let KobelRootMessage::AccentColorChanged(accent) = next(&mut messages).await else {
+            panic!("the accent color change was not forwarded");
+        };
+        assert_eq!(accent, Some(Color::from_rgb(1.0, 0.0, 0.0)));
+
+        // Out of range means the accent color was unset
+        emit(&portal, APPEARANCE_NAMESPACE, ACCENT_COLOR_KEY, Value::from((1.5, 0.0, 0.0))).await;
+        let KobelRootMessage::AccentColorChanged(accent) = next(&mut messages).await else {
+            panic!("the accent color change was not forwarded");
+        };
+        assert_eq!(accent, None);
+    }
+}
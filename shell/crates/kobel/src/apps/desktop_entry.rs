@@ -0,0 +1,314 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::util::key_file::{self, split_list, unescape};
+
+static DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+static DESKTOP_ACTION_GROUP_PREFIX: &str = "Desktop Action ";
+
+/// The user's locale, split into the parts localized keys are matched on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KobelLocale {
+    pub lang: String,
+    pub country: Option<String>,
+    pub modifier: Option<String>,
+}
+
+impl KobelLocale {
+    /// The locale messages are shown in, from `$LC_ALL`, `$LC_MESSAGES` or `$LANG`.
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+    }
+
+    /// Parses `lang_COUNTRY.ENCODING@MODIFIER`, where all but `lang` are optional.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (rest, modifier) = match value.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier.to_string())),
+            None => (value, None),
+        };
+        let rest = rest.split_once('.').map_or(rest, |(rest, _)| rest);
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country.to_string())),
+            None => (rest, None),
+        };
+
+        // C and POSIX mean untranslated
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return None;
+        }
+
+        Some(Self {
+            lang: lang.to_string(),
+            country,
+            modifier,
+        })
+    }
+
+    /// Locale suffixes to try, most specific first, as the specification orders them.
+    pub fn candidates(&self) -> Vec<String> {
+        let mut candidates = vec![];
+
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            candidates.push(format!("{}_{}@{}", self.lang, country, modifier));
+        }
+        if let Some(country) = &self.country {
+            candidates.push(format!("{}_{}", self.lang, country));
+        }
+        if let Some(modifier) = &self.modifier {
+            candidates.push(format!("{}@{}", self.lang, modifier));
+        }
+        candidates.push(self.lang.clone());
+
+        candidates
+    }
+}
+
+/// An additional way to start an application, such as "New Private Window".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KobelDesktopAction {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+}
+
+/// A parsed `.desktop` file, with localized keys already resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KobelDesktopEntry {
+    /// The desktop file ID, such as `org.gnome.Nautilus.desktop`.
+    pub id: String,
+    pub path: PathBuf,
+    /// `Application`, `Link` or `Directory`.
+    pub kind: String,
+    pub name: String,
+    pub generic_name: Option<String>,
+    pub comment: Option<String>,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+    pub try_exec: Option<String>,
+    /// Working directory to start the application in.
+    pub working_dir: Option<PathBuf>,
+    pub terminal: bool,
+    pub no_display: bool,
+    pub hidden: bool,
+    pub only_show_in: Vec<String>,
+    pub not_show_in: Vec<String>,
+    pub categories: Vec<String>,
+    pub keywords: Vec<String>,
+    pub mime_types: Vec<String>,
+    pub startup_wm_class: Option<String>,
+    pub startup_notify: bool,
+    pub dbus_activatable: bool,
+    pub actions: Vec<KobelDesktopAction>,
+}
+
+impl KobelDesktopEntry {
+    pub fn parse(id: &str, path: PathBuf, contents: &str, locale: Option<&KobelLocale>) -> anyhow::Result<Self> {
+        let groups = key_file::parse_groups(contents);
+        let locales = locale.map(KobelLocale::candidates).unwrap_or_default();
+
+        let entry = groups.get(DESKTOP_ENTRY_GROUP).context("No [Desktop Entry] group")?;
+        let keys = KobelLocalizedKeys { keys: entry, locales: &locales };
+
+        let Some(kind) = keys.string("Type") else {
+            bail!("No Type key");
+        };
+        let Some(name) = keys.localized("Name") else {
+            bail!("No Name key");
+        };
+
+        let actions = keys.list("Actions").into_iter()
+            .filter_map(|action_id| {
+                let Some(group) = groups.get(&format!("{}{}", DESKTOP_ACTION_GROUP_PREFIX, action_id)) else {
+                    log::debug!("'{}' lists action '{}' without a group for it", id, action_id);
+                    return None;
+                };
+                let keys = KobelLocalizedKeys { keys: group, locales: &locales };
+
+                Some(KobelDesktopAction {
+                    name: keys.localized("Name")?,
+                    icon: keys.localized("Icon"),
+                    exec: keys.string("Exec"),
+                    id: action_id,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            id: id.to_string(),
+            path,
+            kind,
+            name,
+            generic_name: keys.localized("GenericName"),
+            comment: keys.localized("Comment"),
+            icon: keys.localized("Icon"),
+            exec: keys.string("Exec"),
+            try_exec: keys.string("TryExec"),
+            working_dir: keys.string("Path").map(PathBuf::from),
+            terminal: keys.boolean("Terminal"),
+            no_display: keys.boolean("NoDisplay"),
+            hidden: keys.boolean("Hidden"),
+            only_show_in: keys.list("OnlyShowIn"),
+            not_show_in: keys.list("NotShowIn"),
+            categories: keys.list("Categories"),
+            keywords: keys.localized_list("Keywords"),
+            mime_types: keys.list("MimeType"),
+            startup_wm_class: keys.string("StartupWMClass"),
+            startup_notify: keys.boolean("StartupNotify"),
+            dbus_activatable: keys.boolean("DBusActivatable"),
+            actions,
+        })
+    }
+
+    pub fn is_application(&self) -> bool {
+        self.kind == "Application"
+    }
+
+    /// Whether `OnlyShowIn` and `NotShowIn` allow showing the entry in any of
+    /// `desktops`, the names from `$XDG_CURRENT_DESKTOP`.
+    pub fn shows_in(&self, desktops: &[String]) -> bool {
+        if desktops.iter().any(|desktop| self.not_show_in.contains(desktop)) {
+            return false;
+        }
+
+        self.only_show_in.is_empty() || desktops.iter().any(|desktop| self.only_show_in.contains(desktop))
+    }
+}
+
+struct KobelLocalizedKeys<'a> {
+    keys: &'a HashMap<String, String>,
+    locales: &'a [String],
+}
+
+impl KobelLocalizedKeys<'_> {
+    fn raw(&self, key: &str) -> Option<&str> {
+        self.keys.get(key).map(String::as_str)
+    }
+
+    fn raw_localized(&self, key: &str) -> Option<&str> {
+        self.locales.iter()
+            .find_map(|locale| self.keys.get(&format!("{}[{}]", key, locale)))
+            .map(String::as_str)
+            .or_else(|| self.raw(key))
+    }
+
+    fn string(&self, key: &str) -> Option<String> {
+        self.raw(key).map(unescape).filter(|value| !value.is_empty())
+    }
+
+    fn localized(&self, key: &str) -> Option<String> {
+        self.raw_localized(key).map(unescape).filter(|value| !value.is_empty())
+    }
+
+    fn boolean(&self, key: &str) -> bool {
+        self.raw(key) == Some("true")
+    }
+
+    fn list(&self, key: &str) -> Vec<String> {
+        self.raw(key).map(split_list).unwrap_or_default()
+    }
+
+    fn localized_list(&self, key: &str) -> Vec<String> {
+        self.raw_localized(key).map(split_list).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FIREFOX: &str = "
+[Desktop Entry]
+Type=Application
+Name=Firefox
+Name[de]=Firefox Browser
+Name[pt_BR]=Navegador Firefox
+GenericName=Web Browser
+Comment=Browse the\\sWeb
+Icon=firefox
+Exec=firefox %u
+TryExec=firefox
+Terminal=false
+Categories=GNOME;GTK;Network;WebBrowser;
+Keywords=web;internet;
+Keywords[de]=Internet;WWW;
+MimeType=text/html;x-scheme-handler/http;
+StartupWMClass=firefox
+StartupNotify=true
+Actions=new-window;new-private-window;missing;
+
+[Desktop Action new-window]
+Name=New Window
+Name[de]=Neues Fenster
+Exec=firefox --new-window %u
+
+[Desktop Action new-private-window]
+Name=New Private Window
+Exec=firefox --private-window %u
+";
+
+    fn parse(contents: &str, locale: Option<&str>) -> anyhow::Result<KobelDesktopEntry> {
+        let locale = locale.and_then(KobelLocale::parse);
+        KobelDesktopEntry::parse("firefox.desktop", "/usr/share/applications/firefox.desktop".into(), contents, locale.as_ref())
+    }
+
+    #[test]
+    fn parses_locales() {
+        assert_eq!(
+            KobelLocale::parse("sr_RS.UTF-8@latin").unwrap().candidates(),
+            ["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(KobelLocale::parse("de").unwrap().candidates(), ["de"]);
+        assert_eq!(KobelLocale::parse("C.UTF-8"), None);
+    }
+
+    #[test]
+    fn parses_entries() {
+        let entry = parse(FIREFOX, None).unwrap();
+
+        assert!(entry.is_application());
+        assert_eq!(entry.name, "Firefox");
+        assert_eq!(entry.comment.as_deref(), Some("Browse the Web"));
+        assert_eq!(entry.exec.as_deref(), Some("firefox %u"));
+        assert_eq!(entry.categories, ["GNOME", "GTK", "Network", "WebBrowser"]);
+        assert_eq!(entry.startup_wm_class.as_deref(), Some("firefox"));
+        assert!(entry.startup_notify && !entry.terminal);
+
+        assert_eq!(entry.actions.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["new-window", "new-private-window"]);
+        assert_eq!(entry.actions[1].exec.as_deref(), Some("firefox --private-window %u"));
+    }
+
+    #[test]
+    fn localizes_keys() {
+        let german = parse(FIREFOX, Some("de_AT.UTF-8")).unwrap();
+        assert_eq!(german.name, "Firefox Browser");
+        assert_eq!(german.keywords, ["Internet", "WWW"]);
+        assert_eq!(german.actions[0].name, "Neues Fenster");
+        assert_eq!(german.actions[1].name, "New Private Window");
+
+        assert_eq!(parse(FIREFOX, Some("pt_BR")).unwrap().name, "Navegador Firefox");
+        assert_eq!(parse(FIREFOX, Some("pt_PT")).unwrap().name, "Firefox");
+    }
+
+    #[test]
+    fn filters_by_desktop() {
+        let only_gnome = parse("[Desktop Entry]\nType=Application\nName=Settings\nOnlyShowIn=GNOME;Unity;\n", None).unwrap();
+        assert!(only_gnome.shows_in(&["ubuntu".to_string(), "GNOME".to_string()]));
+        assert!(!only_gnome.shows_in(&["KDE".to_string()]));
+
+        let not_kde = parse("[Desktop Entry]\nType=Application\nName=Files\nNotShowIn=KDE;\n", None).unwrap();
+        assert!(not_kde.shows_in(&[]));
+        assert!(!not_kde.shows_in(&["KDE".to_string()]));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        assert!(parse("[Desktop Action x]\nName=X\n", None).is_err());
+        assert!(parse("[Desktop Entry]\nName=No type\n", None).is_err());
+        assert!(parse("[Desktop Entry]\nType=Application\n", None).is_err());
+    }
+}
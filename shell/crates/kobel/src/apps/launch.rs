@@ -0,0 +1,542 @@
+//! Starting applications from their desktop entries, as the Desktop Entry
+//! Specification describes for the `Exec` key.
+
+use std::{path::PathBuf, process::Stdio, time::{Duration, Instant}};
+
+use anyhow::{bail, Context};
+
+use crate::{apps::{find_program, KobelAppDatabase, KobelDesktopEntry}, wayfire::protocol::KobelWayfireView};
+
+/// The name the shell asks for activation tokens under.
+pub static ACTIVATION_APP_ID: &str = "kobelwm";
+/// How long a launched application has to show a window before the shell
+/// stops waiting for it.
+pub static LAUNCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Terminals to run `Terminal=true` applications in, with the arguments that
+/// come before the command. `$TERMINAL` is tried first, with `-e`.
+static TERMINALS: &[(&str, &[&str])] = &[
+    ("xdg-terminal-exec", &[]),
+    ("kgx", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("ptyxis", &["--"]),
+    ("foot", &[]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("konsole", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+/// Software centers that can show an application's page, with what comes
+/// before its desktop file ID in the argument.
+static SOFTWARE_CENTERS: &[(&str, &str)] = &[
+    ("gnome-software", "--details="),
+    ("plasma-discover", "appstream://"),
+];
+
+/// Starts `entry`, or its action called `action`, opening `uris`. An
+/// application taking a single file is started once per file, so there may
+/// be several children. The activation token is handed to the first, so the
+/// compositor focuses its window once it maps. Dropping the returned
+/// children leaves them running, and tokio reaps them once they exit.
+pub fn launch(
+    entry: &KobelDesktopEntry,
+    action: Option<&str>,
+    uris: &[String],
+    activation_token: Option<&str>,
+) -> anyhow::Result<Vec<tokio::process::Child>> {
+    let exec = match action {
+        Some(action) => entry.actions.iter()
+            .find(|a| a.id == action)
+            .with_context(|| format!("'{}' has no action '{}'", entry.id, action))?
+            .exec.as_deref(),
+        None => entry.exec.as_deref(),
+    };
+    let exec = exec.with_context(|| format!("'{}' has no Exec key", entry.id))?;
+
+    let mut commands = expand_exec(&split_exec(exec)?, entry, uris);
+    if entry.terminal {
+        let terminal = terminal_command().context("No terminal found to run the application in")?;
+        for args in &mut commands {
+            args.splice(0..0, terminal.iter().cloned());
+        }
+    }
+
+    let working_dir = entry.working_dir.clone()
+        .filter(|dir| dir.is_dir())
+        .or_else(|| std::env::var_os("HOME").map(PathBuf::from));
+
+    let mut children = Vec::with_capacity(commands.len());
+    for (i, args) in commands.iter().enumerate() {
+        let Some((program, args)) = args.split_first() else {
+            bail!("'{}' has an empty Exec key", entry.id);
+        };
+
+        let mut command = tokio::process::Command::new(program);
+        command.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            // A process group of its own, so signals meant for the shell do not reach it
+            .process_group(0);
+
+        if let Some(working_dir) = &working_dir {
+            command.current_dir(working_dir);
+        }
+
+        // Never hand down a token the shell itself was started with, and
+        // tokens are good for one window only
+        match activation_token.filter(|_| i == 0) {
+            Some(token) => command.env("XDG_ACTIVATION_TOKEN", token).env("DESKTOP_STARTUP_ID", token),
+            None => command.env_remove("XDG_ACTIVATION_TOKEN").env_remove("DESKTOP_STARTUP_ID"),
+        };
+
+        let child = command.spawn()
+            .with_context(|| format!("Failed to start '{}'", program))?;
+
+        log::info!("Launched '{}' as {}", entry.id, child.id().unwrap_or_default());
+
+        children.push(child);
+    }
+
+    Ok(children)
+}
+
+/// An application that was started and has not shown a window yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelLaunch {
+    pub serial: u64,
+    /// The desktop file ID.
+    pub id: String,
+    pub started_at: Instant,
+    /// Known once the process has been spawned.
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KobelLaunchEvent {
+    Spawned { serial: u64, pid: u32 },
+    /// The application could not be started, or its process failed before
+    /// it showed a window.
+    Failed { serial: u64, error: String },
+    /// The application showed no window within [`LAUNCH_TIMEOUT`].
+    TimedOut(u64),
+}
+
+/// Launches waiting for their application's first window, so the dock can
+/// show that something is happening.
+#[derive(Debug, Default)]
+pub struct KobelLaunches {
+    launches: Vec<KobelLaunch>,
+    next_serial: u64,
+}
+
+impl KobelLaunches {
+    /// Starts waiting for a window of the application with the desktop file
+    /// ID `id`, returning the serial that later events refer to it by.
+    pub fn start(&mut self, id: &str, now: Instant) -> u64 {
+        self.next_serial += 1;
+        self.launches.push(KobelLaunch {
+            serial: self.next_serial,
+            id: id.to_string(),
+            started_at: now,
+            pid: None,
+        });
+
+        self.next_serial
+    }
+
+    /// The oldest launch of the application with the desktop file ID `id`
+    /// that is still waiting.
+    pub fn get(&self, id: &str) -> Option<&KobelLaunch> {
+        self.launches.iter().find(|launch| launch.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.launches.is_empty()
+    }
+
+    /// Applies an event, returning the launch it ended if that was still
+    /// waiting. Events about launches that already ended are ignored.
+    pub fn apply(&mut self, event: &KobelLaunchEvent) -> Option<KobelLaunch> {
+        let serial = match event {
+            KobelLaunchEvent::Spawned { serial, pid } => {
+                if let Some(launch) = self.launches.iter_mut().find(|launch| launch.serial == *serial) {
+                    launch.pid = Some(*pid);
+                }
+                return None;
+            },
+            KobelLaunchEvent::Failed { serial, .. } | KobelLaunchEvent::TimedOut(serial) => *serial,
+        };
+
+        let index = self.launches.iter().position(|launch| launch.serial == serial)?;
+        Some(self.launches.remove(index))
+    }
+
+    /// Ends the launches a window that just opened belongs to, either started
+    /// as the process `pid` or of the application with the desktop file ID
+    /// `id`. Returns whether any ended.
+    pub fn window_opened(&mut self, pid: Option<u32>, id: &str) -> bool {
+        let len = self.launches.len();
+        self.launches.retain(|launch| launch.id != id && (pid.is_none() || launch.pid != pid));
+
+        self.launches.len() != len
+    }
+
+    /// Ends the launches a view the compositor just mapped belongs to, if it
+    /// is an application window. Returns whether any ended.
+    pub fn view_mapped(&mut self, view: &KobelWayfireView, apps: &KobelAppDatabase) -> bool {
+        view.is_toplevel() && self.window_opened(u32::try_from(view.pid).ok(), &apps.id_for_app_id(&view.app_id))
+    }
+}
+
+/// Whether `entry` takes files or URIs on its command line, through `%f`,
+/// `%F`, `%u` or `%U`.
+pub fn takes_files(entry: &KobelDesktopEntry) -> bool {
+    entry.exec.as_deref()
+        .and_then(|exec| split_exec(exec).ok())
+        .is_some_and(|args| args.iter().any(|arg| ["%f", "%F", "%u", "%U"].contains(&arg.as_str())))
+}
+
+/// Splits an `Exec` value into arguments, resolving its quoting.
+pub fn split_exec(exec: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => args.extend(current.take()),
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => arg.push(escaped),
+                            Some(other) => {
+                                arg.push('\\');
+                                arg.push(other);
+                            },
+                            None => bail!("Exec ends in an escape"),
+                        },
+                        Some(other) => arg.push(other),
+                        None => bail!("Exec has an unterminated quote"),
+                    }
+                }
+            },
+            _ => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(current);
+    Ok(args)
+}
+
+/// Replaces field codes in `args`, giving the commands to run. `%F` and `%U`
+/// expand to one argument per file in a single command. `%f` and `%u` take a
+/// single file, so the application is started once per file. Codes with
+/// nothing to expand to are removed along with their argument.
+pub fn expand_exec(args: &[String], entry: &KobelDesktopEntry, uris: &[String]) -> Vec<Vec<String>> {
+    let codes: Vec<char> = args.iter().flat_map(|arg| field_codes(arg)).collect();
+
+    let groups: Vec<&[String]> = if codes.contains(&'u') {
+        uris.chunks(1).collect()
+    } else if codes.contains(&'f') {
+        // Only local files count, so a remote URI does not start an empty instance
+        uris.chunks(1).filter(|uri| uri_to_path(&uri[0]).is_some()).collect()
+    } else {
+        vec![uris]
+    };
+
+    if groups.is_empty() {
+        return vec![expand_args(args, entry, &[])];
+    }
+
+    groups.into_iter()
+        .map(|uris| expand_args(args, entry, uris))
+        .collect()
+}
+
+// The field codes in an argument, `%%` included
+fn field_codes(arg: &str) -> Vec<char> {
+    let mut codes = vec![];
+    let mut chars = arg.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            codes.extend(chars.next());
+        }
+    }
+
+    codes
+}
+
+fn expand_args(args: &[String], entry: &KobelDesktopEntry, uris: &[String]) -> Vec<String> {
+    let files: Vec<String> = uris.iter().filter_map(|uri| uri_to_path(uri)).collect();
+    let mut expanded = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "%F" => expanded.extend(files.iter().cloned()),
+            "%U" => expanded.extend(uris.iter().cloned()),
+            "%f" => expanded.extend(files.first().cloned()),
+            "%u" => expanded.extend(uris.first().cloned()),
+            // Deprecated codes are removed
+            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {},
+            "%i" => {
+                if let Some(icon) = &entry.icon {
+                    expanded.extend(["--icon".to_string(), icon.clone()]);
+                }
+            },
+            _ => expanded.push(expand_inline(arg, entry, &files, uris)),
+        }
+    }
+
+    expanded
+}
+
+// Field codes within a longer argument, such as `--name=%c`
+fn expand_inline(arg: &str, entry: &KobelDesktopEntry, files: &[String], uris: &[String]) -> String {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('c') => expanded.push_str(&entry.name),
+            Some('k') => expanded.push_str(&entry.path.to_string_lossy()),
+            Some('f') => expanded.push_str(files.first().map_or("", String::as_str)),
+            Some('u') => expanded.push_str(uris.first().map_or("", String::as_str)),
+            // Deprecated codes and codes that only make sense on their own expand to nothing
+            Some(code) => log::debug!("Ignoring field code '%{}' in '{}'", code, entry.id),
+            None => {},
+        }
+    }
+
+    expanded
+}
+
+/// The command that runs a program in a terminal, the program's own command
+/// line following it.
+pub fn terminal_command() -> Option<Vec<String>> {
+    if let Ok(terminal) = std::env::var("TERMINAL")
+        && find_program(&terminal).is_some()
+    {
+        return Some(vec![terminal, "-e".to_string()]);
+    }
+
+    TERMINALS.iter()
+        .find(|(program, _)| find_program(program).is_some())
+        .map(|(program, args)| std::iter::once(*program).chain(args.iter().copied()).map(String::from).collect())
+}
+
+/// The command that shows the application with the desktop file ID `id` in
+/// a software center, if one is installed.
+pub fn details_command(id: &str) -> Option<Vec<String>> {
+    SOFTWARE_CENTERS.iter()
+        .find(|(program, _)| find_program(program).is_some())
+        .map(|(program, prefix)| vec![program.to_string(), format!("{}{}", prefix, id)])
+}
+
+/// Shows the application with the desktop file ID `id` in a software center.
+pub fn show_details(id: &str) -> anyhow::Result<()> {
+    let command = details_command(id).context("No software center found to show the application in")?;
+
+    tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .env_remove("XDG_ACTIVATION_TOKEN")
+        .env_remove("DESKTOP_STARTUP_ID")
+        .spawn()
+        .with_context(|| format!("Failed to start '{}'", command[0]))?;
+
+    Ok(())
+}
+
+/// The local path of a `file://` URI, or of an absolute path given as is.
+/// Only local files can be passed as paths, so `%f` skips anything else.
+pub fn uri_to_path(uri: &str) -> Option<String> {
+    if uri.starts_with('/') {
+        return Some(uri.to_string());
+    }
+
+    let path = uri.strip_prefix("file://")?;
+    // The host, usually empty or localhost, is dropped
+    let path = &path[path.find('/')?..];
+
+    percent_decode(path)
+}
+
+pub fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::test::{temp_dir, write}, wayfire::{mock::{next_event, KobelMockWayfire}, KobelWayfireClient, KobelWayfireEvent}};
+
+    fn entry() -> KobelDesktopEntry {
+        KobelDesktopEntry {
+            id: "org.gnome.TextEditor.desktop".to_string(),
+            path: "/usr/share/applications/org.gnome.TextEditor.desktop".into(),
+            name: "Text Editor".to_string(),
+            icon: Some("org.gnome.TextEditor".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn view(id: u64, app_id: &str, kind: &str, pid: i32) -> KobelWayfireView {
+        KobelWayfireView {
+            id,
+            pid,
+            app_id: app_id.to_string(),
+            kind: kind.to_string(),
+            mapped: true,
+            ..Default::default()
+        }
+    }
+
+    fn expand(exec: &str, uris: &[&str]) -> Vec<Vec<String>> {
+        let uris: Vec<String> = uris.iter().map(|uri| uri.to_string()).collect();
+        expand_exec(&split_exec(exec).unwrap(), &entry(), &uris)
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(split_exec("foot  -e htop").unwrap(), ["foot", "-e", "htop"]);
+        assert_eq!(
+            split_exec(r#"sh -c "echo \"\$HOME\" \\ done" x"#).unwrap(),
+            ["sh", "-c", r#"echo "$HOME" \ done"#, "x"]
+        );
+        assert_eq!(split_exec(r#"app "" --flag"#).unwrap(), ["app", "", "--flag"]);
+        assert!(split_exec(r#"app "unterminated"#).is_err());
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let uris = ["file:///home/user/My%20Notes.txt", "https://example.com", "file:///tmp/b.txt"];
+
+        assert_eq!(expand("gnome-text-editor %U", &uris), [["gnome-text-editor", uris[0], uris[1], uris[2]]]);
+        assert_eq!(expand("gnome-text-editor %F", &uris), [["gnome-text-editor", "/home/user/My Notes.txt", "/tmp/b.txt"]]);
+        assert_eq!(expand("gnome-text-editor %u", &[]), [["gnome-text-editor"]]);
+        assert_eq!(expand("gnome-text-editor %f", &[]), [["gnome-text-editor"]]);
+        assert_eq!(
+            expand("app %i --name=%c --file=%k --100%% %d %m", &[]),
+            [["app", "--icon", "org.gnome.TextEditor", "--name=Text Editor", "--file=/usr/share/applications/org.gnome.TextEditor.desktop", "--100%"]]
+        );
+    }
+
+    #[test]
+    fn launches_once_per_file_for_single_file_codes() {
+        let uris = ["file:///home/user/My%20Notes.txt", "https://example.com", "file:///tmp/b.txt"];
+
+        // Remote URIs have no path, so %f skips them
+        assert_eq!(
+            expand("gnome-text-editor %f", &uris),
+            [["gnome-text-editor", "/home/user/My Notes.txt"], ["gnome-text-editor", "/tmp/b.txt"]]
+        );
+        assert_eq!(
+            expand("gnome-text-editor --file=%u", &uris[..2]),
+            [["gnome-text-editor", "--file=file:///home/user/My%20Notes.txt"], ["gnome-text-editor", "--file=https://example.com"]]
+        );
+        // A literal %% is not a field code
+        assert_eq!(expand("app --100%%f", &uris[..2]), [["app", "--100%f"]]);
+    }
+
+    #[test]
+    fn ends_launches_when_a_window_opens() {
+        let now = Instant::now();
+        let mut launches = KobelLaunches::default();
+
+        let editor = launches.start("org.gnome.TextEditor.desktop", now);
+        let terminal = launches.start("org.gnome.Console.desktop", now);
+        assert_ne!(editor, terminal);
+
+        // Windows are told apart by process, as app IDs may differ from desktop file IDs
+        launches.apply(&KobelLaunchEvent::Spawned { serial: terminal, pid: 42 });
+        assert!(!launches.window_opened(Some(7), "firefox.desktop"));
+        assert!(launches.window_opened(Some(42), "kgx"));
+        assert_eq!(launches.get("org.gnome.Console.desktop"), None);
+
+        assert!(launches.window_opened(None, "org.gnome.TextEditor.desktop"));
+        assert!(launches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ends_launches_when_the_compositor_maps_a_window() {
+        let dir = temp_dir("launch", "view-mapped");
+        write(dir.join("org.gnome.Console.desktop"), "[Desktop Entry]\nType=Application\nName=Console\nExec=kgx\n");
+        let apps = KobelAppDatabase::load_from(vec![dir], None, vec![]);
+
+        let mock = KobelMockWayfire::builder().start();
+        let mut events = KobelWayfireClient::connect_to(mock.path()).await.unwrap().watch(None).await.unwrap();
+
+        let mut launches = KobelLaunches::default();
+        launches.start("org.gnome.Console.desktop", Instant::now());
+        let editor = launches.start("org.gnome.TextEditor.desktop", Instant::now());
+        launches.apply(&KobelLaunchEvent::Spawned { serial: editor, pid: 42 });
+
+        let mut mapped = async |view: KobelWayfireView| {
+            mock.emit_view_mapped(view);
+            let KobelWayfireEvent::ViewMapped { view } = next_event(&mut events).await else {
+                panic!("expected a mapped view");
+            };
+            launches.view_mapped(&view, &apps)
+        };
+
+        // Panels and popups are not the window being waited for
+        assert!(!mapped(view(1, "org.gnome.Console", "panel", 7)).await);
+        assert!(mapped(view(2, "org.gnome.Console", "toplevel", 7)).await);
+        assert!(mapped(view(3, "gnome-text-editor", "toplevel", 42)).await);
+        assert!(launches.is_empty());
+    }
+
+    #[test]
+    fn reports_only_launches_still_waiting() {
+        let now = Instant::now();
+        let mut launches = KobelLaunches::default();
+
+        let serial = launches.start("org.gnome.TextEditor.desktop", now);
+        assert_eq!(launches.get("org.gnome.TextEditor.desktop").map(|launch| launch.serial), Some(serial));
+
+        let failed = KobelLaunchEvent::Failed { serial, error: "exited with status 1".to_string() };
+        assert_eq!(launches.apply(&failed).map(|launch| launch.id), Some("org.gnome.TextEditor.desktop".to_string()));
+
+        // The timeout that follows has nothing left to end
+        assert_eq!(launches.apply(&failed), None);
+        assert_eq!(launches.apply(&KobelLaunchEvent::TimedOut(serial)), None);
+    }
+
+    #[test]
+    fn converts_only_local_file_uris() {
+        assert_eq!(uri_to_path("file:///tmp/a%23b"), Some("/tmp/a#b".to_string()));
+        assert_eq!(uri_to_path("file://localhost/tmp/a"), Some("/tmp/a".to_string()));
+        assert_eq!(uri_to_path("/tmp/a"), Some("/tmp/a".to_string()));
+        assert_eq!(uri_to_path("https://example.com/a"), None);
+    }
+}
@@ -0,0 +1,376 @@
+//! Counts, progress and urgency that apps publish for their launcher through
+//! Unity's `com.canonical.Unity.LauncherEntry` signal, as most docks show them.
+//!
+//! Apps emit `Update(app_uri, properties)` from any object path, with only the
+//! properties that changed. What they published is dropped when they leave the bus.
+
+use std::collections::HashMap;
+
+use iced::{futures::{SinkExt, StreamExt}, Subscription};
+use zbus::{fdo::{DBusProxy, NameOwnerChangedStream}, message::Type, zvariant::{OwnedValue, Value}, MatchRule, MessageStream};
+
+use crate::{apps::KobelAppDatabase, KobelRootMessage};
+
+pub static LAUNCHER_ENTRY_INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+pub static LAUNCHER_ENTRY_UPDATE: &str = "Update";
+/// Apps name themselves by desktop file ID behind this scheme.
+pub static LAUNCHER_ENTRY_URI_SCHEME: &str = "application://";
+
+/// What an app last published for its launcher.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KobelLauncherEntry {
+    /// Unique bus name of the app, so its entry goes away with it.
+    sender: String,
+
+    count: i64,
+    count_visible: bool,
+    progress: f64,
+    progress_visible: bool,
+    urgent: bool,
+}
+
+impl KobelLauncherEntry {
+    /// The number to show on the app's icon, such as unread messages.
+    pub fn count(&self) -> Option<i64> {
+        self.count_visible.then_some(self.count)
+    }
+
+    /// How far along the app's current task is, from 0 to 1.
+    pub fn progress(&self) -> Option<f64> {
+        self.progress_visible.then_some(self.progress.clamp(0.0, 1.0))
+    }
+
+    /// Whether the app wants the user's attention.
+    pub fn urgent(&self) -> bool {
+        self.urgent
+    }
+}
+
+/// A single `Update` signal, with the properties it left out as `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KobelLauncherEntryUpdate {
+    pub sender: String,
+    /// Desktop file ID of the app, such as `org.telegram.desktop.desktop`.
+    pub app_id: String,
+
+    pub count: Option<i64>,
+    pub count_visible: Option<bool>,
+    pub progress: Option<f64>,
+    pub progress_visible: Option<bool>,
+    pub urgent: Option<bool>,
+}
+
+impl KobelLauncherEntryUpdate {
+    /// Reads the arguments of an `Update` signal, or `None` when the URI does
+    /// not name a desktop file.
+    pub fn parse(sender: &str, app_uri: &str, properties: &HashMap<String, OwnedValue>) -> Option<Self> {
+        let app_id = app_uri.strip_prefix(LAUNCHER_ENTRY_URI_SCHEME)?;
+        if app_id.is_empty() {
+            return None;
+        }
+
+        let boolean = |key: &str| properties.get(key).and_then(|value| bool::try_from(value).ok());
+
+        Some(Self {
+            sender: sender.to_string(),
+            app_id: app_id.to_string(),
+
+            count: properties.get("count").and_then(|value| parse_count(value)),
+            count_visible: boolean("count-visible"),
+            progress: properties.get("progress").and_then(|value| f64::try_from(value).ok()),
+            progress_visible: boolean("progress-visible"),
+            urgent: boolean("urgent"),
+        })
+    }
+}
+
+// The count is specified as an int64, but not every app sends one
+fn parse_count(value: &Value<'_>) -> Option<i64> {
+    i64::try_from(value).ok()
+        .or_else(|| i32::try_from(value).ok().map(i64::from))
+        .or_else(|| u32::try_from(value).ok().map(i64::from))
+        .or_else(|| u64::try_from(value).ok().and_then(|count| i64::try_from(count).ok()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KobelLauncherEntryEvent {
+    Updated(KobelLauncherEntryUpdate),
+    /// The app with this unique bus name left the bus.
+    SenderGone(String),
+}
+
+/// Launcher entries by the ID the dock groups the app under, as built up
+/// from every update.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KobelLauncherEntries {
+    entries: HashMap<String, KobelLauncherEntry>,
+}
+
+impl KobelLauncherEntries {
+    pub fn get(&self, app_id: &str) -> Option<&KobelLauncherEntry> {
+        self.entries.get(app_id)
+    }
+
+    /// Applies an event, returning whether any entry changed. Apps name
+    /// themselves as they please, so the ID is matched against `apps` the
+    /// way window app IDs are.
+    pub fn apply(&mut self, event: KobelLauncherEntryEvent, apps: &KobelAppDatabase) -> bool {
+        match event {
+            KobelLauncherEntryEvent::Updated(update) => {
+                let entry = self.entries.entry(apps.id_for_app_id(&update.app_id)).or_default();
+                let previous = entry.clone();
+
+                // Another instance of the app took over
+                entry.sender = update.sender;
+
+                if let Some(count) = update.count {
+                    entry.count = count;
+                }
+                if let Some(count_visible) = update.count_visible {
+                    entry.count_visible = count_visible;
+                }
+                if let Some(progress) = update.progress {
+                    entry.progress = progress;
+                }
+                if let Some(progress_visible) = update.progress_visible {
+                    entry.progress_visible = progress_visible;
+                }
+                if let Some(urgent) = update.urgent {
+                    entry.urgent = urgent;
+                }
+
+                *entry != previous
+            },
+            KobelLauncherEntryEvent::SenderGone(sender) => {
+                let len = self.entries.len();
+                self.entries.retain(|_, entry| entry.sender != sender);
+                self.entries.len() != len
+            },
+        }
+    }
+}
+
+/// Listens for launcher entry updates on a bus, and for the apps that sent
+/// them going away.
+pub struct KobelLauncherEntryWatcher {
+    updates: MessageStream,
+    owners: NameOwnerChangedStream,
+}
+
+impl KobelLauncherEntryWatcher {
+    /// Starts listening, so no signal sent after this returns is missed.
+    pub async fn new(connection: &zbus::Connection) -> zbus::Result<Self> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface(LAUNCHER_ENTRY_INTERFACE)?
+            .member(LAUNCHER_ENTRY_UPDATE)?
+            .build();
+
+        let updates = MessageStream::for_match_rule(rule, connection, None).await?;
+        let owners = DBusProxy::new(connection).await?
+            .receive_name_owner_changed()
+            .await?;
+
+        Ok(Self { updates, owners })
+    }
+
+    /// Waits for the next event, or `None` once the connection is closed.
+    pub async fn next(&mut self) -> Option<KobelLauncherEntryEvent> {
+        loop {
+            tokio::select! {
+                message = self.updates.next() => {
+                    let message = match message? {
+                        Ok(message) => message,
+                        Err(e) => {
+                            log::debug!("Failed to receive a launcher entry update: {}", e);
+                            continue;
+                        },
+                    };
+
+                    let header = message.header();
+                    let Some(sender) = header.sender() else {
+                        continue;
+                    };
+
+                    let Ok((app_uri, properties)) = message.body().deserialize::<(String, HashMap<String, OwnedValue>)>() else {
+                        log::debug!("Ignoring a malformed launcher entry update from {}", sender);
+                        continue;
+                    };
+
+                    if let Some(update) = KobelLauncherEntryUpdate::parse(sender, &app_uri, &properties) {
+                        return Some(KobelLauncherEntryEvent::Updated(update));
+                    }
+                },
+                signal = self.owners.next() => {
+                    let signal = signal?;
+                    let Ok(args) = signal.args() else {
+                        continue;
+                    };
+
+                    // Only unique names send signals, and they are never taken over
+                    if args.name().starts_with(':') && args.new_owner().is_none() {
+                        return Some(KobelLauncherEntryEvent::SenderGone(args.name().to_string()));
+                    }
+                },
+            }
+        }
+    }
+}
+
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct LauncherEntryWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<LauncherEntryWatcher>(),
+        iced::stream::channel(16, |mut output| async move {
+            let connection = match zbus::Connection::session().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    log::error!("Failed to connect to the session bus: {}", e);
+                    return;
+                }
+            };
+
+            let mut watcher = match KobelLauncherEntryWatcher::new(&connection).await {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    log::error!("Failed to listen for launcher entry updates: {}", e);
+                    return;
+                }
+            };
+
+            while let Some(event) = watcher.next().await {
+                if output.send(KobelRootMessage::LauncherEntry(event)).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::util::test::{temp_dir, write, KobelPrivateBus};
+
+    async fn emit(connection: &zbus::Connection, app_uri: &str, properties: &[(&str, Value<'_>)]) {
+        let properties: HashMap<&str, &Value> = properties.iter().map(|(key, value)| (*key, value)).collect();
+
+        connection.emit_signal(
+            None::<()>,
+            "/com/canonical/unity/launcherentry/1",
+            LAUNCHER_ENTRY_INTERFACE,
+            LAUNCHER_ENTRY_UPDATE,
+            &(app_uri, properties),
+        )
+            .await
+            .unwrap();
+    }
+
+    async fn next(watcher: &mut KobelLauncherEntryWatcher) -> KobelLauncherEntryEvent {
+        tokio::time::timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("no launcher entry event arrived")
+            .expect("the connection closed")
+    }
+
+    #[test]
+    fn merges_partial_updates() {
+        let update = |properties: &[(&str, Value<'static>)]| {
+            let properties = properties.iter()
+                .map(|(key, value)| (key.to_string(), OwnedValue::try_from(value).unwrap()))
+                .collect();
+            KobelLauncherEntryEvent::Updated(KobelLauncherEntryUpdate::parse(":1.5", "application://org.telegram.desktop.desktop", &properties).unwrap())
+        };
+
+        let apps = KobelAppDatabase::load_from(vec![], None, vec![]);
+        let mut entries = KobelLauncherEntries::default();
+        assert!(entries.apply(update(&[("count", Value::I64(3))]), &apps));
+        assert_eq!(entries.get("org.telegram.desktop.desktop").unwrap().count(), None);
+
+        assert!(entries.apply(update(&[("count-visible", Value::Bool(true)), ("urgent", Value::Bool(true))]), &apps));
+        let entry = entries.get("org.telegram.desktop.desktop").unwrap();
+        assert_eq!((entry.count(), entry.progress(), entry.urgent()), (Some(3), None, true));
+
+        // Progress beyond the end is shown as finished
+        assert!(entries.apply(update(&[("progress", Value::F64(1.5)), ("progress-visible", Value::Bool(true))]), &apps));
+        assert_eq!(entries.get("org.telegram.desktop.desktop").unwrap().progress(), Some(1.0));
+        assert!(!entries.apply(update(&[("count", Value::I32(3))]), &apps));
+
+        assert!(!entries.apply(KobelLauncherEntryEvent::SenderGone(":1.6".to_string()), &apps));
+        assert!(entries.apply(KobelLauncherEntryEvent::SenderGone(":1.5".to_string()), &apps));
+        assert_eq!(entries.get("org.telegram.desktop.desktop"), None);
+    }
+
+    #[test]
+    fn keys_entries_by_the_matching_app() {
+        let dir = temp_dir("launcher-entry", "matching");
+        write(dir.join("org.mozilla.firefox.desktop"), "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox\n");
+        write(dir.join("org.gnome.Nautilus.desktop"), "[Desktop Entry]\nType=Application\nName=Files\nExec=nautilus\n");
+        let apps = KobelAppDatabase::load_from(vec![dir], None, vec![]);
+
+        let update = |app_uri: &str| {
+            let properties = HashMap::from([("urgent".to_string(), OwnedValue::from(true))]);
+            KobelLauncherEntryEvent::Updated(KobelLauncherEntryUpdate::parse(":1.5", app_uri, &properties).unwrap())
+        };
+
+        let mut entries = KobelLauncherEntries::default();
+        // A Flatpak ID without its prefix, and an ID without its extension
+        entries.apply(update("application://firefox.desktop"), &apps);
+        entries.apply(update("application://org.gnome.Nautilus"), &apps);
+        // Apps that are not installed keep the ID they gave
+        entries.apply(update("application://thunderbird.desktop"), &apps);
+
+        assert!(entries.get("org.mozilla.firefox.desktop").unwrap().urgent());
+        assert!(entries.get("org.gnome.Nautilus.desktop").unwrap().urgent());
+        assert!(entries.get("thunderbird.desktop").unwrap().urgent());
+        assert_eq!(entries.get("firefox.desktop"), None);
+    }
+
+    #[test]
+    fn ignores_uris_without_a_desktop_file() {
+        let properties = HashMap::new();
+        assert_eq!(KobelLauncherEntryUpdate::parse(":1.5", "firefox.desktop", &properties), None);
+        assert_eq!(KobelLauncherEntryUpdate::parse(":1.5", "application://", &properties), None);
+    }
+
+    #[tokio::test]
+    async fn receives_updates_from_the_bus() {
+        let Some(bus) = KobelPrivateBus::start() else {
+            eprintln!("Skipping, as dbus-daemon is not installed");
+            return;
+        };
+        let apps = KobelAppDatabase::load_from(vec![], None, vec![]);
+
+        let shell = bus.connect().await;
+        let app = bus.connect().await;
+        let app_name = app.unique_name().unwrap().to_string();
+
+        let mut watcher = KobelLauncherEntryWatcher::new(&shell).await.unwrap();
+        emit(&app, "application://thunderbird.desktop", &[
+            ("count", Value::I64(12)),
+            ("count-visible", Value::Bool(true)),
+        ]).await;
+
+        let mut entries = KobelLauncherEntries::default();
+        let event = next(&mut watcher).await;
+        assert_eq!(event, KobelLauncherEntryEvent::Updated(KobelLauncherEntryUpdate {
+            sender: app_name.clone(),
+            app_id: "thunderbird.desktop".to_string(),
+            count: Some(12),
+            count_visible: Some(true),
+            ..Default::default()
+        }));
+        entries.apply(event, &apps);
+        assert_eq!(entries.get("thunderbird.desktop").unwrap().count(), Some(12));
+
+        // Quitting the app clears its badge
+        drop(app);
+        let event = next(&mut watcher).await;
+        assert_eq!(event, KobelLauncherEntryEvent::SenderGone(app_name));
+        entries.apply(event, &apps);
+        assert_eq!(entries.get("thunderbird.desktop"), None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Finding the desktop entry a window belongs to from its app ID, which often
+//! differs from the desktop file ID. Flatpaks, snaps, Electron apps, Xwayland
+//! windows and browser web apps all name their windows in their own way.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::apps::{launch, KobelDesktopEntry, DESKTOP_ENTRY_EXTENSION};
+
+/// Programs that run something else, so their name says nothing about the app.
+static EXEC_WRAPPERS: &[&str] = &[
+    "env", "sh", "bash", "flatpak", "snap", "gtk-launch", "xdg-open",
+    "python", "python3", "java", "mono", "wine", "electron",
+];
+
+/// An index of installed entries by every name a window might use for them,
+/// built once when the application database changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KobelAppIdMatcher {
+    ids: HashMap<String, String>,
+    wm_classes: HashMap<String, String>,
+    folded_ids: HashMap<String, String>,
+    folded_wm_classes: HashMap<String, String>,
+    /// Desktop file IDs without their Flatpak reverse-DNS or snap prefix.
+    short_names: HashMap<String, String>,
+    /// The program each entry runs.
+    executables: HashMap<String, String>,
+}
+
+impl KobelAppIdMatcher {
+    pub fn new<'a>(entries: impl IntoIterator<Item = &'a KobelDesktopEntry>) -> Self {
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        // When names collide, entries shown in menus win, then the first ID
+        entries.sort_by_key(|entry| (entry.no_display, &entry.id));
+
+        let mut matcher = Self::default();
+
+        for entry in entries {
+            let stem = entry.id.strip_suffix(&format!(".{}", DESKTOP_ENTRY_EXTENSION)).unwrap_or(&entry.id);
+            let folded_stem = stem.to_lowercase();
+
+            let insert = |map: &mut HashMap<String, String>, key: String| {
+                map.entry(key).or_insert_with(|| entry.id.clone());
+            };
+
+            insert(&mut matcher.ids, stem.to_string());
+            if let Some(short_name) = short_name(&folded_stem) {
+                insert(&mut matcher.short_names, short_name.to_string());
+            }
+            insert(&mut matcher.folded_ids, folded_stem);
+
+            if let Some(wm_class) = &entry.startup_wm_class {
+                insert(&mut matcher.wm_classes, wm_class.clone());
+                insert(&mut matcher.folded_wm_classes, wm_class.to_lowercase());
+            }
+
+            if let Some(executable) = executable(entry) {
+                insert(&mut matcher.executables, executable);
+            }
+        }
+
+        matcher
+    }
+
+    /// The desktop file ID for a window with `app_id`. Exact matches on the ID
+    /// or `StartupWMClass` go first, then the same ignoring case, then names
+    /// with their Flatpak or snap prefix stripped, then the program run.
+    pub fn find(&self, app_id: &str) -> Option<&str> {
+        let app_id = app_id.trim();
+        let app_id = app_id.strip_suffix(&format!(".{}", DESKTOP_ENTRY_EXTENSION)).unwrap_or(app_id);
+
+        if app_id.is_empty() {
+            return None;
+        }
+
+        let folded = app_id.to_lowercase();
+        let short = short_name(&folded);
+
+        self.ids.get(app_id)
+            .or_else(|| self.wm_classes.get(app_id))
+            .or_else(|| self.folded_ids.get(&folded))
+            .or_else(|| self.folded_wm_classes.get(&folded))
+            .or_else(|| self.short_names.get(&folded))
+            .or_else(|| short.and_then(|short| {
+                self.folded_ids.get(short)
+                    .or_else(|| self.folded_wm_classes.get(short))
+                    .or_else(|| self.short_names.get(short))
+            }))
+            .or_else(|| self.executables.get(&folded))
+            .or_else(|| short.and_then(|short| self.executables.get(short)))
+            .map(String::as_str)
+    }
+}
+
+// The app name in a reverse-DNS ID such as `org.gnome.Nautilus`, or in a snap
+// ID such as `firefox_firefox`, which is `<snap>_<app>`
+fn short_name(id: &str) -> Option<&str> {
+    let short = if id.matches('.').count() >= 2 {
+        // Version numbers, as in `gimp-2.10`, are not reverse-DNS IDs
+        id.rsplit('.').next().filter(|name| !name.starts_with(|c: char| c.is_ascii_digit()))?
+    } else {
+        id.split_once('_').map(|(_, app)| app)?
+    };
+
+    (!short.is_empty()).then_some(short)
+}
+
+// The lowercase name of the program an entry runs, looking through `env`
+fn executable(entry: &KobelDesktopEntry) -> Option<String> {
+    let args = launch::split_exec(entry.exec.as_deref()?).ok()?;
+    let mut args = args.iter().map(String::as_str);
+
+    let mut program = args.next()?;
+    if Path::new(program).file_name().is_some_and(|name| name == "env") {
+        program = args.find(|arg| !arg.starts_with('-') && !arg.contains('='))?;
+    }
+
+    let name = Path::new(program).file_name()?.to_str()?.to_lowercase();
+
+    (!EXEC_WRAPPERS.contains(&name.as_str())).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, exec: &str, wm_class: Option<&str>, no_display: bool) -> KobelDesktopEntry {
+        KobelDesktopEntry {
+            id: id.to_string(),
+            name: id.to_string(),
+            kind: "Application".to_string(),
+            exec: Some(exec.to_string()),
+            startup_wm_class: wm_class.map(String::from),
+            no_display,
+            ..Default::default()
+        }
+    }
+
+    fn entries() -> Vec<KobelDesktopEntry> {
+        vec![
+            entry("org.gnome.Nautilus.desktop", "nautilus --new-window %U", None, false),
+            entry("nautilus-autorun-software.desktop", "nautilus-autorun-software", None, true),
+            entry("org.gnome.Console.desktop", "kgx", None, false),
+            entry("code.desktop", "/usr/share/code/code --unity-launch %F", Some("Code"), false),
+            entry("firefox_firefox.desktop", "/snap/bin/firefox %u", None, false),
+            entry(
+                "com.discordapp.Discord.desktop",
+                "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=com.discordapp.Discord com.discordapp.Discord",
+                None,
+                false,
+            ),
+            entry(
+                "chrome-abcdef-Default.desktop",
+                "/opt/google/chrome/google-chrome --profile-directory=Default --app-id=abcdef",
+                Some("crx_abcdef"),
+                false,
+            ),
+            entry(
+                "com.google.Chrome.flextop.chrome-ghijkl-Default.desktop",
+                "/usr/bin/flatpak run --command=/app/bin/chrome com.google.Chrome --app-id=ghijkl",
+                None,
+                false,
+            ),
+            entry("gimp.desktop", "gimp-2.10 %U", None, false),
+            entry("steam.desktop", "env STEAM_RUNTIME=1 /usr/bin/steam-runtime %U", None, false),
+            entry("org.gnome.Calculator.desktop", "gnome-calculator", None, false),
+            entry("calculator.desktop", "gnome-calculator", None, true),
+        ]
+    }
+
+    #[test]
+    fn matches_app_ids() {
+        let entries = entries();
+        let matcher = KobelAppIdMatcher::new(&entries);
+
+        let cases = [
+            // Exact desktop file IDs, with or without the suffix
+            ("org.gnome.Nautilus", Some("org.gnome.Nautilus.desktop")),
+            ("org.gnome.Nautilus.desktop", Some("org.gnome.Nautilus.desktop")),
+            ("com.discordapp.Discord", Some("com.discordapp.Discord.desktop")),
+            ("firefox_firefox", Some("firefox_firefox.desktop")),
+            // StartupWMClass, as Electron and Xwayland windows set it
+            ("Code", Some("code.desktop")),
+            ("crx_abcdef", Some("chrome-abcdef-Default.desktop")),
+            // Case-folding
+            ("org.gnome.nautilus", Some("org.gnome.Nautilus.desktop")),
+            ("CODE", Some("code.desktop")),
+            ("Chrome-abcdef-Default", Some("chrome-abcdef-Default.desktop")),
+            // Flatpak and snap prefixes, on either side
+            ("discord", Some("com.discordapp.Discord.desktop")),
+            ("firefox", Some("firefox_firefox.desktop")),
+            ("org.mozilla.firefox", Some("firefox_firefox.desktop")),
+            ("chrome-ghijkl-Default", Some("com.google.Chrome.flextop.chrome-ghijkl-Default.desktop")),
+            ("nautilus", Some("org.gnome.Nautilus.desktop")),
+            // The program in Exec
+            ("kgx", Some("org.gnome.Console.desktop")),
+            ("Gimp-2.10", Some("gimp.desktop")),
+            ("steam-runtime", Some("steam.desktop")),
+            ("org.gnome.kgx", Some("org.gnome.Console.desktop")),
+            ("gnome-calculator", Some("org.gnome.Calculator.desktop")),
+            // No match
+            ("flatpak", None),
+            ("google-chrome-unstable", None),
+            ("", None),
+        ];
+
+        for (app_id, expected) in cases {
+            assert_eq!(matcher.find(app_id), expected, "app ID '{}'", app_id);
+        }
+    }
+
+    #[test]
+    fn prefers_entries_shown_in_menus() {
+        let entries = entries();
+        let matcher = KobelAppIdMatcher::new(entries.iter().rev());
+
+        assert_eq!(matcher.find("gnome-calculator"), Some("org.gnome.Calculator.desktop"));
+        assert_eq!(matcher.find("nautilus"), Some("org.gnome.Nautilus.desktop"));
+    }
+}
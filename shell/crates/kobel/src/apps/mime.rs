@@ -0,0 +1,258 @@
+//! File types from the shared MIME-info database, guessed from file names
+//! alone, to tell whether an application's `MimeType` key accepts a file.
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use crate::{apps::launch, resources};
+
+pub static MIME_DIR_NAME: &str = "mime";
+/// The files of the database that are read, as `update-mime-database` writes them.
+pub static MIME_DATABASE_FILES: &[&str] = &["globs2", "subclasses", "aliases"];
+/// The format of dragged files and links.
+pub static URI_LIST_MIME: &str = "text/uri-list";
+pub static DIRECTORY_MIME: &str = "inode/directory";
+/// Files whose name gives nothing away.
+pub static FALLBACK_MIME: &str = "application/octet-stream";
+
+#[derive(Debug, Clone, PartialEq)]
+struct KobelMimeGlob {
+    weight: u32,
+    mime: String,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KobelMimeDatabase {
+    globs: Vec<KobelMimeGlob>,
+    /// Parents of each type, such as `text/plain` for `text/x-python`.
+    parents: HashMap<String, Vec<String>>,
+    /// Canonical names of deprecated types.
+    aliases: HashMap<String, String>,
+}
+
+impl KobelMimeDatabase {
+    /// Reads `globs2`, `subclasses` and `aliases` from every `mime` data
+    /// directory. Missing files leave every file `application/octet-stream`.
+    pub fn load() -> Self {
+        let mut database = Self::default();
+
+        for dir in mime_dirs() {
+            let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+
+            database.add_globs(&read("globs2"));
+            database.add_subclasses(&read("subclasses"));
+            database.add_aliases(&read("aliases"));
+        }
+
+        database
+    }
+
+    /// Adds lines of `weight:type:glob[:flags]`.
+    pub fn add_globs(&mut self, contents: &str) {
+        for line in contents.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime), Some(pattern)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            let Ok(weight) = weight.parse() else {
+                continue;
+            };
+
+            self.globs.push(KobelMimeGlob {
+                weight,
+                mime: mime.to_string(),
+                case_sensitive: fields.next().is_some_and(|flags| flags.split(',').any(|flag| flag == "cs")),
+                pattern: pattern.to_string(),
+            });
+        }
+    }
+
+    /// Adds lines of `type parent`.
+    pub fn add_subclasses(&mut self, contents: &str) {
+        for (mime, parent) in contents.lines().filter_map(|line| line.split_once(' ')) {
+            self.parents.entry(mime.to_string()).or_default().push(parent.to_string());
+        }
+    }
+
+    /// Adds lines of `alias type`.
+    pub fn add_aliases(&mut self, contents: &str) {
+        for (alias, mime) in contents.lines().filter_map(|line| line.split_once(' ')) {
+            self.aliases.insert(alias.to_string(), mime.to_string());
+        }
+    }
+
+    /// The type of a file called `name`. Of the globs that match, the one with
+    /// the highest weight wins, then the longest.
+    pub fn for_file_name(&self, name: &str) -> &str {
+        let lowercase = name.to_lowercase();
+
+        self.globs.iter()
+            .filter(|glob| match glob.case_sensitive {
+                true => glob_matches(&glob.pattern, name),
+                false => glob_matches(&glob.pattern.to_lowercase(), &lowercase),
+            })
+            .max_by_key(|glob| (glob.weight, glob.pattern.len()))
+            .map_or(FALLBACK_MIME, |glob| glob.mime.as_str())
+    }
+
+    /// The type of what `uri` points at. Links that are not local files are
+    /// handled by scheme, as `x-scheme-handler/https` for example.
+    pub fn for_uri(&self, uri: &str) -> String {
+        let Some(path) = launch::uri_to_path(uri) else {
+            let scheme = uri.split_once(':').map_or(uri, |(scheme, _)| scheme);
+            return format!("x-scheme-handler/{}", scheme.to_lowercase());
+        };
+
+        let path = Path::new(&path);
+        if path.is_dir() {
+            return DIRECTORY_MIME.to_string();
+        }
+
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        self.for_file_name(&name).to_string()
+    }
+
+    fn canonical<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map_or(mime, String::as_str)
+    }
+
+    /// Whether `mime` is `parent` or a more specific kind of it.
+    pub fn is_subclass(&self, mime: &str, parent: &str) -> bool {
+        let (mime, parent) = (self.canonical(mime), self.canonical(parent));
+        if mime == parent {
+            return true;
+        }
+
+        // Implied by the specification rather than listed
+        if parent == "text/plain" && mime.starts_with("text/") {
+            return true;
+        }
+        if parent == FALLBACK_MIME && !mime.starts_with("inode/") && !mime.starts_with("x-scheme-handler/") {
+            return true;
+        }
+
+        self.parents.get(mime)
+            .is_some_and(|parents| parents.iter().any(|next| self.is_subclass(next, parent)))
+    }
+
+    /// Whether an application with these `MimeType`s can open every one of `uris`.
+    pub fn accepts(&self, mime_types: &[String], uris: &[String]) -> bool {
+        !uris.is_empty() && uris.iter().all(|uri| {
+            let mime = self.for_uri(uri);
+            mime_types.iter().any(|accepted| self.is_subclass(&mime, accepted))
+        })
+    }
+}
+
+/// Where the MIME database is installed, most important first.
+pub fn mime_dirs() -> Vec<PathBuf> {
+    resources::data_home()
+        .into_iter()
+        .chain(resources::data_dirs())
+        .map(|dir| dir.join(MIME_DIR_NAME))
+        .collect()
+}
+
+/// Reads the URIs out of `text/uri-list` data, skipping comments.
+pub fn parse_uri_list(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+// Shell-style globs, with `*`, `?` and `[...]` classes such as `[0-9]`
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_from(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches_from(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some(end) = rest.iter().position(|c| *c == ']') else {
+                return name.first() == Some(&'[') && matches_from(rest, &name[1..]);
+            };
+            let Some(c) = name.first() else {
+                return false;
+            };
+
+            let class = &rest[..end];
+            let mut in_class = false;
+            let mut i = 0;
+            while i < class.len() {
+                if let Some(['-', last]) = class.get(i + 1..i + 3) {
+                    in_class |= (class[i]..=*last).contains(c);
+                    i += 3;
+                } else {
+                    in_class |= class[i] == *c;
+                    i += 1;
+                }
+            }
+
+            in_class && matches_from(&rest[end + 1..], &name[1..])
+        },
+        Some((literal, rest)) => name.first() == Some(literal) && matches_from(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> KobelMimeDatabase {
+        let mut database = KobelMimeDatabase::default();
+        database.add_globs("# comment\n\
+            50:text/x-python:*.py\n\
+            50:image/png:*.png\n\
+            50:application/x-troff-man:*.[1-9]\n\
+            50:text/x-makefile:Makefile:cs\n\
+            10:text/x-readme:README*\n\
+            50:application/x-compressed-tar:*.tar.gz\n\
+            20:application/gzip:*.gz\n");
+        database.add_subclasses("application/x-compressed-tar application/gzip\n");
+        database.add_aliases("application/x-gzip application/gzip\n");
+        database
+    }
+
+    #[test]
+    fn guesses_types_from_file_names() {
+        let database = database();
+
+        assert_eq!(database.for_file_name("script.py"), "text/x-python");
+        assert_eq!(database.for_file_name("PHOTO.PNG"), "image/png");
+        assert_eq!(database.for_file_name("ls.1"), "application/x-troff-man");
+        assert_eq!(database.for_file_name("Makefile"), "text/x-makefile");
+        assert_eq!(database.for_file_name("makefile"), FALLBACK_MIME);
+        assert_eq!(database.for_file_name("README.md"), "text/x-readme");
+
+        // The heavier glob wins over the shorter one
+        assert_eq!(database.for_file_name("backup.tar.gz"), "application/x-compressed-tar");
+        assert_eq!(database.for_file_name("notes"), FALLBACK_MIME);
+    }
+
+    #[test]
+    fn accepts_subclasses_of_listed_types() {
+        let database = database();
+        let text_editor = vec!["text/plain".to_string()];
+        let archiver = vec!["application/x-gzip".to_string()];
+
+        assert!(database.accepts(&text_editor, &["file:///tmp/script.py".to_string()]));
+        assert!(!database.accepts(&text_editor, &["file:///tmp/script.py".to_string(), "file:///tmp/a.png".to_string()]));
+        assert!(database.accepts(&archiver, &["file:///tmp/backup.tar.gz".to_string()]));
+        assert!(database.accepts(&["x-scheme-handler/https".to_string()], &["https://example.com".to_string()]));
+        assert!(!database.accepts(&text_editor, &[]));
+    }
+
+    #[test]
+    fn reads_uri_lists() {
+        let data = b"# from a file manager\r\nfile:///tmp/a%20b.txt\r\nhttps://example.com\r\n\r\n";
+        assert_eq!(parse_uri_list(data), ["file:///tmp/a%20b.txt", "https://example.com"]);
+    }
+}
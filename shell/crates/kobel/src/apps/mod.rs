@@ -0,0 +1,333 @@
+use std::{collections::{HashMap, HashSet}, os::unix::fs::PermissionsExt, path::{Path, PathBuf}, time::SystemTime};
+
+use iced::{futures::StreamExt, Subscription};
+
+use crate::{resources, util::watch, KobelRootMessage};
+
+pub mod desktop_entry;
+pub mod launch;
+pub mod launcher_entry;
+pub mod matching;
+pub mod mime;
+
+pub use desktop_entry::{KobelDesktopAction, KobelDesktopEntry, KobelLocale};
+pub use matching::KobelAppIdMatcher;
+
+pub static APPLICATIONS_DIR_NAME: &str = "applications";
+pub static DESKTOP_ENTRY_EXTENSION: &str = "desktop";
+
+/// Where `.desktop` files are installed, most important first.
+pub fn application_dirs() -> Vec<PathBuf> {
+    resources::data_home()
+        .into_iter()
+        .chain(resources::data_dirs())
+        .map(|dir| dir.join(APPLICATIONS_DIR_NAME))
+        .collect()
+}
+
+/// The desktop names in `$XDG_CURRENT_DESKTOP`, matched against `OnlyShowIn` and `NotShowIn`.
+pub fn current_desktops() -> Vec<String> {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .map(|desktops| desktops.split(':').filter(|d| !d.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Finds the executable `program`, either an absolute path or a name in `$PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+
+    let path = Path::new(program);
+    if path.has_root() {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[derive(Debug, Clone)]
+struct KobelDesktopFile {
+    modified: Option<SystemTime>,
+    /// `None` when the file is not a valid desktop entry, which has been logged.
+    entry: Option<KobelDesktopEntry>,
+}
+
+/// Every installed application, by desktop file ID. Files are only parsed again
+/// when they change, so refreshing after an install is cheap.
+#[derive(Debug, Clone, Default)]
+pub struct KobelAppDatabase {
+    dirs: Vec<PathBuf>,
+    locale: Option<KobelLocale>,
+    desktops: Vec<String>,
+    files: HashMap<PathBuf, KobelDesktopFile>,
+    entries: HashMap<String, KobelDesktopEntry>,
+    /// IDs of entries that belong in menus and the dock.
+    visible: HashSet<String>,
+    app_ids: KobelAppIdMatcher,
+}
+
+impl KobelAppDatabase {
+    pub fn load() -> Self {
+        Self::load_from(application_dirs(), KobelLocale::from_env(), current_desktops())
+    }
+
+    pub fn load_from(dirs: Vec<PathBuf>, locale: Option<KobelLocale>, desktops: Vec<String>) -> Self {
+        let mut apps = Self {
+            dirs,
+            locale,
+            desktops,
+            ..Default::default()
+        };
+        apps.refresh();
+
+        log::info!("Found {} applications", apps.entries.len());
+
+        apps
+    }
+
+    /// Scans the application directories again. Returns whether any entry was
+    /// added, removed or changed.
+    pub fn refresh(&mut self) -> bool {
+        let mut files = HashMap::new();
+        let mut entries = HashMap::new();
+        let mut seen = HashSet::new();
+
+        for dir in &self.dirs {
+            for (id, path) in desktop_files(dir) {
+                let modified = path.metadata().and_then(|m| m.modified()).ok();
+                let file = match self.files.remove(&path) {
+                    Some(file) if file.modified == modified && modified.is_some() => file,
+                    _ => KobelDesktopFile {
+                        modified,
+                        entry: self.parse(&id, &path),
+                    },
+                };
+
+                // The first file with an ID wins, even a Hidden one, which deletes the entry
+                if let Some(entry) = &file.entry
+                    && seen.insert(id.clone())
+                    && !entry.hidden
+                    && entry.is_application()
+                {
+                    entries.insert(id, entry.clone());
+                }
+
+                files.insert(path, file);
+            }
+        }
+
+        let visible = entries.values()
+            .filter(|entry| self.should_show(entry))
+            .map(|entry| entry.id.clone())
+            .collect();
+
+        let changed = entries != self.entries;
+
+        if changed {
+            self.app_ids = KobelAppIdMatcher::new(entries.values());
+        }
+
+        self.files = files;
+        self.entries = entries;
+        self.visible = visible;
+
+        changed
+    }
+
+    /// Looks up an entry by desktop file ID, with or without the `.desktop` suffix.
+    pub fn get(&self, id: &str) -> Option<&KobelDesktopEntry> {
+        self.entries.get(id)
+            .or_else(|| self.entries.get(&format!("{}.{}", id, DESKTOP_ENTRY_EXTENSION)))
+    }
+
+    /// Finds the entry for a window's app ID, as [`KobelAppIdMatcher`] does.
+    pub fn find_by_app_id(&self, app_id: &str) -> Option<&KobelDesktopEntry> {
+        self.app_ids.find(app_id).and_then(|id| self.entries.get(id))
+    }
+
+    /// The ID the dock groups a window with `app_id` under: the desktop file
+    /// ID of its entry, or the app ID itself if it has none.
+    pub fn id_for_app_id(&self, app_id: &str) -> String {
+        self.find_by_app_id(app_id).map_or_else(|| app_id.to_string(), |entry| entry.id.clone())
+    }
+
+    /// Every application, including those hidden from menus.
+    pub fn iter(&self) -> impl Iterator<Item = &KobelDesktopEntry> {
+        self.entries.values()
+    }
+
+    /// Applications that belong in menus, sorted by name.
+    pub fn visible(&self) -> Vec<&KobelDesktopEntry> {
+        let mut visible: Vec<_> = self.entries.values()
+            .filter(|entry| self.visible.contains(&entry.id))
+            .collect();
+        visible.sort_by_cached_key(|entry| (entry.name.to_lowercase(), entry.id.clone()));
+
+        visible
+    }
+
+    pub fn is_visible(&self, id: &str) -> bool {
+        self.visible.contains(id)
+    }
+
+    fn parse(&self, id: &str, path: &Path) -> Option<KobelDesktopEntry> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| KobelDesktopEntry::parse(id, path.to_path_buf(), &contents, self.locale.as_ref()));
+
+        match contents {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::debug!("Ignoring desktop entry '{}': {:#}", path.display(), e);
+                None
+            },
+        }
+    }
+
+    // Checks TryExec here, so that asking for visible apps never touches the disk
+    fn should_show(&self, entry: &KobelDesktopEntry) -> bool {
+        !entry.no_display
+            && entry.shows_in(&self.desktops)
+            && entry.try_exec.as_deref().is_none_or(|program| find_program(program).is_some())
+    }
+}
+
+/// Sends [`KobelRootMessage::AppsChanged`] when applications are installed,
+/// removed or updated, and [`KobelRootMessage::MimeTypesChanged`] when the
+/// MIME database is rebuilt, as it is along with installing them.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct AppsWatcher;
+
+    let mime_dirs = mime::mime_dirs();
+    let dirs = application_dirs().into_iter().chain(mime_dirs.clone()).collect();
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<AppsWatcher>(),
+        watch::watch_paths(dirs, true)
+            .flat_map(move |paths| {
+                let (mime_paths, app_paths): (Vec<PathBuf>, Vec<PathBuf>) = paths.into_iter()
+                    .partition(|path| mime_dirs.iter().any(|dir| path.starts_with(dir)));
+
+                // The type definitions next to the database are not read
+                let mime_changed = mime_paths.iter().any(|path| {
+                    path.file_name().is_some_and(|name| mime::MIME_DATABASE_FILES.iter().any(|file| name == *file))
+                });
+
+                let messages = [
+                    (!app_paths.is_empty()).then_some(KobelRootMessage::AppsChanged),
+                    mime_changed.then_some(KobelRootMessage::MimeTypesChanged),
+                ];
+                iced::futures::stream::iter(messages.into_iter().flatten())
+            }),
+    )
+}
+
+// Desktop file IDs are the path below the applications directory, with `/`
+// replaced by `-`, so `kde/konsole.desktop` is `kde-konsole.desktop`
+fn desktop_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut files = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == DESKTOP_ENTRY_EXTENSION) {
+                let Ok(relative) = path.strip_prefix(dir) else {
+                    continue;
+                };
+                let id = relative.components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("-");
+
+                files.push((id, path));
+            }
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::{temp_dir, write};
+
+    fn app(name: &str, extra: &str) -> String {
+        format!("[Desktop Entry]\nType=Application\nName={}\nExec={}\n{}", name, name.to_lowercase(), extra)
+    }
+
+    fn ids(apps: &[&KobelDesktopEntry]) -> Vec<String> {
+        apps.iter().map(|entry| entry.id.clone()).collect()
+    }
+
+    #[test]
+    fn earlier_directories_and_hidden_entries_take_precedence() {
+        let user = temp_dir("apps", "user");
+        let system = temp_dir("apps", "system");
+
+        write(system.join("firefox.desktop"), &app("Firefox", ""));
+        write(user.join("firefox.desktop"), &app("Firefox Nightly", ""));
+        write(system.join("org.gnome.Nautilus.desktop"), &app("Files", ""));
+        write(user.join("org.gnome.Nautilus.desktop"), &app("Files", "Hidden=true"));
+        write(system.join("kde/konsole.desktop"), &app("Konsole", "OnlyShowIn=KDE;"));
+        write(system.join("mimeinfo.cache"), "");
+
+        let apps = KobelAppDatabase::load_from(vec![user, system], None, vec!["GNOME".to_string()]);
+
+        assert_eq!(apps.get("firefox").unwrap().name, "Firefox Nightly");
+        assert_eq!(apps.get("org.gnome.Nautilus.desktop"), None);
+        assert!(apps.get("kde-konsole.desktop").is_some());
+        assert_eq!(ids(&apps.visible()), ["firefox.desktop"]);
+    }
+
+    #[test]
+    fn hides_entries_by_no_display_and_try_exec() {
+        let dir = temp_dir("apps", "visibility");
+
+        write(dir.join("a.desktop"), &app("Alpha", ""));
+        write(dir.join("b.desktop"), &app("Beta", "NoDisplay=true"));
+        write(dir.join("c.desktop"), &app("Gamma", "TryExec=/nonexistent/gamma"));
+        write(dir.join("d.desktop"), &app("Delta", "TryExec=/bin/sh"));
+        write(dir.join("e.desktop"), "[Desktop Entry]\nType=Link\nName=Website\nURL=https://example.com\n");
+
+        let apps = KobelAppDatabase::load_from(vec![dir], None, vec![]);
+
+        assert_eq!(apps.iter().count(), 4);
+        assert_eq!(ids(&apps.visible()), ["a.desktop", "d.desktop"]);
+        assert!(!apps.is_visible("b.desktop"));
+    }
+
+    #[test]
+    fn refreshes_added_changed_and_removed_files() {
+        let dir = temp_dir("apps", "refresh");
+        write(dir.join("foot.desktop"), &app("Foot", ""));
+
+        let mut apps = KobelAppDatabase::load_from(vec![dir.clone()], None, vec![]);
+        assert!(!apps.refresh());
+
+        write(dir.join("org.gnome.Console.desktop"), &app("Console", ""));
+        std::fs::remove_file(dir.join("foot.desktop")).unwrap();
+
+        assert!(apps.refresh());
+        assert_eq!(ids(&apps.visible()), ["org.gnome.Console.desktop"]);
+
+        write(dir.join("org.gnome.Console.desktop"), &app("Console", "NoDisplay=true"));
+        // Make sure the change is visible even on filesystems with coarse timestamps
+        apps.files.values_mut().for_each(|file| file.modified = None);
+
+        assert!(apps.refresh());
+        assert!(apps.visible().is_empty());
+    }
+}
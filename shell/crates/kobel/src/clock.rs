@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use iced::{futures::SinkExt, Subscription};
+
+use crate::KobelRootMessage;
+
+/// Sends a `Tick` right after every wall-clock second boundary, so a seconds
+/// display changes exactly when the second does and the shell sleeps in between.
+/// Panels showing the time return this from their `subscription`.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct ClockTicker;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<ClockTicker>(),
+        iced::stream::channel(1, |mut output| async move {
+            loop {
+                tokio::time::sleep(until_next_second(Local::now())).await;
+
+                if output.send(KobelRootMessage::Tick(Local::now())).await.is_err() {
+                    break;
+                }
+            }
+        }),
+    )
+}
+
+fn until_next_second(now: DateTime<Local>) -> Duration {
+    // chrono reports leap seconds as nanoseconds past 1_000_000_000
+    let nanos = now.timestamp_subsec_nanos() % 1_000_000_000;
+
+    Duration::from_nanos(1_000_000_000 - nanos as u64)
+}
@@ -0,0 +1,468 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use anyhow::Context;
+use iced::{futures::StreamExt, Subscription};
+use serde::Deserialize;
+
+use crate::{appearance::KobelColorSchemeMode, panel::{bar::{BAR_DEFAULT_HEIGHT, BAR_DEFAULT_MARGIN, BAR_DEFAULT_PADDING, BAR_DEFAULT_RADII}, dock::{DOCK_DEFAULT_HEIGHT, DOCK_DEFAULT_MARGIN, DOCK_DEFAULT_PADDING, DOCK_DEFAULT_PINNED, DOCK_DEFAULT_RADII}, search::{SEARCH_DEFAULT_HEIGHT, SEARCH_DEFAULT_MARGIN, SEARCH_DEFAULT_PADDING, SEARCH_DEFAULT_RADII}}, theme::{KobelThemeVariant, DARK_THEME_ID, LIGHT_THEME_ID}, util::watch, KobelRootMessage};
+
+pub static CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelConfig {
+    pub font: KobelFontConfig,
+    pub theme: KobelThemeConfig,
+    pub bar: KobelBarConfig,
+    pub dock: KobelDockConfig,
+    pub search: KobelSearchConfig,
+    pub animation: KobelAnimationConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelFontConfig {
+    pub family: String,
+    pub base_size: f32,
+    pub icon_base_size: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelThemeConfig {
+    pub variant: KobelThemeVariant,
+    /// Id of the theme used for the light variant.
+    pub light: String,
+    /// Id of the theme used for the dark variant.
+    pub dark: String,
+    pub color_scheme: KobelColorSchemeMode,
+    /// Name of the freedesktop icon theme, such as `Adwaita` or `Papirus`.
+    pub icon_theme: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelBarConfig {
+    pub height: i32,
+    pub margin: i32,
+    pub padding: f32,
+    pub radii: f32,
+    pub radii_bottom_only: bool,
+    /// Outputs to show a bar on, by connector name or description. A trailing
+    /// `*` matches a prefix.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelDockConfig {
+    pub height: i32,
+    pub margin: i32,
+    pub padding: f32,
+    pub radii: f32,
+    /// Outputs to show a dock on, matched like `bar.outputs`.
+    pub outputs: Vec<String>,
+    /// Desktop file IDs of the apps kept in the dock, in order.
+    pub pinned: Vec<String>,
+    pub visibility: KobelDockVisibility,
+    /// Shows the trash at the end of the dock.
+    pub show_trash: bool,
+}
+
+/// When the dock is on screen. Hidden docks come back while the pointer rests
+/// on the bottom edge of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KobelDockVisibility {
+    /// Always shown, with windows kept clear of it.
+    #[default]
+    Always,
+    /// Hidden unless the pointer is on it.
+    Autohide,
+    /// Hidden only while a window covers it.
+    Intellihide,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelSearchConfig {
+    pub height: i32,
+    /// Offset from the top of the screen, as a fraction of the screen height.
+    pub margin: f32,
+    pub padding: f32,
+    pub radii: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KobelAnimationConfig {
+    /// Skip transitions and show their end state right away.
+    pub reduce_motion: bool,
+}
+
+impl Default for KobelFontConfig {
+    fn default() -> Self {
+        Self {
+            family: "Cantarell".to_string(),
+            base_size: 14.6666,
+            icon_base_size: 16.0,
+        }
+    }
+}
+
+impl Default for KobelThemeConfig {
+    fn default() -> Self {
+        Self {
+            variant: KobelThemeVariant::default(),
+            light: LIGHT_THEME_ID.to_string(),
+            dark: DARK_THEME_ID.to_string(),
+            color_scheme: KobelColorSchemeMode::default(),
+            icon_theme: "Adwaita".to_string(),
+        }
+    }
+}
+
+impl Default for KobelBarConfig {
+    fn default() -> Self {
+        Self {
+            height: BAR_DEFAULT_HEIGHT,
+            margin: BAR_DEFAULT_MARGIN,
+            padding: BAR_DEFAULT_PADDING,
+            radii: BAR_DEFAULT_RADII,
+            radii_bottom_only: false,
+            outputs: vec!["*".to_string()],
+        }
+    }
+}
+
+impl Default for KobelDockConfig {
+    fn default() -> Self {
+        Self {
+            height: DOCK_DEFAULT_HEIGHT,
+            margin: DOCK_DEFAULT_MARGIN,
+            padding: DOCK_DEFAULT_PADDING,
+            radii: DOCK_DEFAULT_RADII,
+            outputs: vec!["*".to_string()],
+            pinned: DOCK_DEFAULT_PINNED.iter().map(|id| id.to_string()).collect(),
+            visibility: KobelDockVisibility::default(),
+            show_trash: true,
+        }
+    }
+}
+
+impl Default for KobelSearchConfig {
+    fn default() -> Self {
+        Self {
+            height: SEARCH_DEFAULT_HEIGHT,
+            margin: SEARCH_DEFAULT_MARGIN,
+            padding: SEARCH_DEFAULT_PADDING,
+            radii: SEARCH_DEFAULT_RADII,
+        }
+    }
+}
+
+impl KobelConfig {
+    /// Loads the user's config file, falling back to the defaults if it does not exist.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = config_path();
+
+        if !path.exists() {
+            log::info!("No config file at '{}', using defaults", path.display());
+            return Ok(Self::default());
+        }
+
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+
+        Self::parse(&contents)
+            .with_context(|| format!("Invalid config file '{}'", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks values that parse fine but would produce a broken layout.
+    /// Every problem is reported at once, rather than one per reload.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        if self.font.family.trim().is_empty() {
+            errors.push("font.family must not be empty".to_string());
+        }
+        check_positive(&mut errors, "font.base_size", self.font.base_size);
+        check_positive(&mut errors, "font.icon_base_size", self.font.icon_base_size);
+
+        if self.theme.light.trim().is_empty() {
+            errors.push("theme.light must not be empty".to_string());
+        }
+        if self.theme.dark.trim().is_empty() {
+            errors.push("theme.dark must not be empty".to_string());
+        }
+        if self.theme.icon_theme.trim().is_empty() {
+            errors.push("theme.icon_theme must not be empty".to_string());
+        }
+
+        check_positive(&mut errors, "bar.height", self.bar.height as f32);
+        check_non_negative(&mut errors, "bar.margin", self.bar.margin as f32);
+        check_non_negative(&mut errors, "bar.padding", self.bar.padding);
+        check_non_negative(&mut errors, "bar.radii", self.bar.radii);
+        check_padding_fits(&mut errors, "bar", self.bar.padding, self.bar.height);
+
+        check_positive(&mut errors, "dock.height", self.dock.height as f32);
+        check_non_negative(&mut errors, "dock.margin", self.dock.margin as f32);
+        check_non_negative(&mut errors, "dock.padding", self.dock.padding);
+        check_non_negative(&mut errors, "dock.radii", self.dock.radii);
+        check_padding_fits(&mut errors, "dock", self.dock.padding, self.dock.height);
+        if self.dock.pinned.iter().any(|id| id.trim().is_empty()) {
+            errors.push("dock.pinned must not contain empty app IDs".to_string());
+        }
+
+        check_positive(&mut errors, "search.height", self.search.height as f32);
+        check_non_negative(&mut errors, "search.padding", self.search.padding);
+        check_non_negative(&mut errors, "search.radii", self.search.radii);
+        if !(0.0..=1.0).contains(&self.search.margin) {
+            errors.push(format!(
+                "search.margin is a fraction of the screen height and must be between 0.0 and 1.0 (got {})",
+                self.search.margin
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{}", errors.join("\n"))
+        }
+    }
+}
+
+fn check_positive(errors: &mut Vec<String>, key: &str, value: f32) {
+    // TOML allows `nan`, which no comparison lets through
+    if value.is_nan() || value <= 0.0 {
+        errors.push(format!("{} must be greater than 0 (got {})", key, value));
+    }
+}
+
+fn check_non_negative(errors: &mut Vec<String>, key: &str, value: f32) {
+    if value.is_nan() || value < 0.0 {
+        errors.push(format!("{} must not be negative (got {})", key, value));
+    }
+}
+
+fn check_padding_fits(errors: &mut Vec<String>, section: &str, padding: f32, height: i32) {
+    if padding * 2.0 >= height as f32 {
+        errors.push(format!(
+            "{section}.padding ({padding}) leaves no room for content in a {section}.height of {height}"
+        ));
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("/etc/xdg"));
+
+    config_home.join("kobel")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join(CONFIG_FILE_NAME)
+}
+
+/// Stores `pinned` as `dock.pinned` in the config file. The rest of the file,
+/// comments included, is kept as it is.
+pub fn save_pinned_apps(pinned: &[String]) -> anyhow::Result<()> {
+    save_pinned_apps_to(&config_path(), pinned)
+}
+
+fn save_pinned_apps_to(path: &Path, pinned: &[String]) -> anyhow::Result<()> {
+    edit_config_file(path, |document| {
+        document["dock"]["pinned"] = toml_edit::value(pinned.iter().collect::<toml_edit::Array>());
+    })
+}
+
+fn edit_config_file(path: &Path, edit: impl FnOnce(&mut toml_edit::DocumentMut)) -> anyhow::Result<()> {
+    // A config file linked in from elsewhere is edited where it lives, keeping the link
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to resolve config file '{}'", path.display())),
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read config file '{}'", path.display())),
+    };
+    let mut document: toml_edit::DocumentMut = contents.parse()
+        .with_context(|| format!("Invalid config file '{}'", path.display()))?;
+
+    edit(&mut document);
+
+    // Written next to the file and renamed over it, so the watcher never reads half a file
+    let temp_path = path.with_extension("toml.tmp");
+    std::fs::create_dir_all(path.parent().unwrap_or(Path::new("/")))
+        .and_then(|_| std::fs::write(&temp_path, document.to_string()))
+        .and_then(|_| std::fs::rename(&temp_path, &path))
+        .with_context(|| format!("Failed to write config file '{}'", path.display()))
+}
+
+/// Reloads the config file whenever it changes on disk. Invalid edits are
+/// logged and ignored, so the shell keeps running with the last good config.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct ConfigWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<ConfigWatcher>(),
+        watch::watch_path(config_path())
+            .filter_map(|_| async {
+                match KobelConfig::load() {
+                    Ok(config) => {
+                        log::info!("Reloaded config from '{}'", config_path().display());
+                        Some(KobelRootMessage::ConfigReloaded(Arc::new(config)))
+                    },
+                    Err(e) => {
+                        log::error!("{:#}", e);
+                        None
+                    }
+                }
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::{temp_dir, write};
+
+    #[test]
+    fn empty_files_use_the_defaults() {
+        assert_eq!(KobelConfig::parse("").unwrap(), KobelConfig::default());
+        assert!(KobelConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = KobelConfig::parse("\
+            [font]\n\
+            family = \"Inter\"\n\
+            [theme]\n\
+            variant = \"dark\"\n\
+            color_scheme = \"publish\"\n\
+            [dock]\n\
+            height = 96\n\
+            visibility = \"intellihide\"\n").unwrap();
+
+        assert_eq!(config.font.family, "Inter");
+        assert_eq!(config.font.base_size, KobelFontConfig::default().base_size);
+        assert_eq!(config.theme.variant, KobelThemeVariant::Dark);
+        assert_eq!(config.theme.color_scheme, KobelColorSchemeMode::Publish);
+        assert_eq!(config.theme.light, LIGHT_THEME_ID);
+        assert_eq!(config.dock.height, 96);
+        assert_eq!(config.dock.margin, DOCK_DEFAULT_MARGIN);
+        assert_eq!(config.dock.visibility, KobelDockVisibility::Intellihide);
+        assert_eq!(config.bar, KobelBarConfig::default());
+    }
+
+    #[test]
+    fn rejects_invalid_toml_and_unknown_keys() {
+        assert!(KobelConfig::parse("[dock\nheight = 96\n").is_err());
+        assert!(KobelConfig::parse("[dock]\nheight = \"tall\"\n").is_err());
+        assert!(KobelConfig::parse("[dock]\nhieght = 96\n").is_err());
+        assert!(KobelConfig::parse("[dock]\nvisibility = \"sometimes\"\n").is_err());
+        assert!(KobelConfig::parse("[panel]\nheight = 96\n").is_err());
+    }
+
+    #[test]
+    fn reports_every_invalid_value_at_once() {
+        let error = KobelConfig::parse("\
+            [font]\n\
+            family = \" \"\n\
+            [bar]\n\
+            height = 0\n\
+            [dock]\n\
+            padding = 50.0\n\
+            pinned = [\"firefox.desktop\", \"\"]\n\
+            [search]\n\
+            margin = 1.5\n").unwrap_err();
+        let message = format!("{:#}", error);
+
+        assert!(message.contains("font.family must not be empty"));
+        assert!(message.contains("bar.height must be greater than 0 (got 0)"));
+        assert!(message.contains("dock.padding (50) leaves no room for content"));
+        assert!(message.contains("dock.pinned must not contain empty app IDs"));
+        assert!(message.contains("search.margin is a fraction of the screen height"));
+    }
+
+    #[test]
+    fn missing_files_fail_to_load() {
+        let error = KobelConfig::load_from(Path::new("/nonexistent/kobel/config.toml")).unwrap_err();
+        assert!(format!("{:#}", error).contains("Failed to read config file '/nonexistent/kobel/config.toml'"));
+    }
+
+    fn pinned(apps: &[&str]) -> Vec<String> {
+        apps.iter().map(|app| app.to_string()).collect()
+    }
+
+    #[test]
+    fn saving_pinned_apps_keeps_the_rest_of_the_file() {
+        let dir = temp_dir("config", "round-trip");
+        let path = write(dir.join("kobel.toml"), "\
+            # My dock\n\
+            [dock]\n\
+            height = 96 # Large\n\
+            pinned = [\"firefox.desktop\"]\n\
+            \n\
+            [bar]\n\
+            # Slim\n\
+            height = 24\n");
+
+        save_pinned_apps_to(&path, &pinned(&["org.gnome.Console.desktop", "firefox.desktop"])).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("# My dock"));
+        assert!(contents.contains("height = 96 # Large"));
+        assert!(contents.contains("# Slim"));
+
+        let config = KobelConfig::parse(&contents).unwrap();
+        assert_eq!(config.dock.pinned, pinned(&["org.gnome.Console.desktop", "firefox.desktop"]));
+        assert_eq!(config.dock.height, 96);
+        assert_eq!(config.bar.height, 24);
+        assert!(!dir.join("kobel.toml.tmp").exists());
+    }
+
+    #[test]
+    fn saving_pinned_apps_creates_the_file() {
+        let path = temp_dir("config", "create").join("kobel").join("kobel.toml");
+
+        save_pinned_apps_to(&path, &pinned(&["firefox.desktop"])).unwrap();
+
+        let config = KobelConfig::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(config.dock.pinned, pinned(&["firefox.desktop"]));
+    }
+
+    #[test]
+    fn saving_pinned_apps_follows_symlinks() {
+        let dir = temp_dir("config", "symlink");
+        let target = write(dir.join("dotfiles").join("kobel.toml"), "# Shared\n[dock]\nheight = 96\n");
+        let link = dir.join("config").join("kobel.toml");
+        std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        save_pinned_apps_to(&link, &pinned(&["firefox.desktop"])).unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        let contents = std::fs::read_to_string(&target).unwrap();
+        assert!(contents.contains("# Shared"));
+        assert_eq!(KobelConfig::parse(&contents).unwrap().dock.pinned, pinned(&["firefox.desktop"]));
+    }
+}
@@ -1,5 +1,7 @@
 use std::time::{Duration, Instant};
 
+static IDLE_THRESHOLD: f64 = 0.25;
+
 #[derive(Debug)]
 pub struct FpsCounter {
     last_frame: Instant,
@@ -21,6 +23,13 @@ impl FpsCounter {
         let dt = now.duration_since(self.last_frame).as_secs_f64();
         self.last_frame = now;
 
+        // Frames only arrive while something animates, so a long gap is the
+        // shell idling rather than a slow frame
+        if dt > IDLE_THRESHOLD {
+            self.frame_times.clear();
+            return;
+        }
+
         self.frame_times.push(dt);
         if self.frame_times.len() > self.max_samples {
             self.frame_times.remove(0);
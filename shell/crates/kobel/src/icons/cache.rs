@@ -0,0 +1,129 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use iced::widget::{image, svg};
+
+use crate::{icons::xpm, resources::KobelResource};
+
+/// A handle ready to be drawn, so views never build one from a path themselves.
+#[derive(Debug, Clone)]
+pub enum KobelIconHandle {
+    Svg(svg::Handle),
+    Image(image::Handle),
+}
+
+impl KobelIconHandle {
+    /// Creates the handle for `resource`. SVG and PNG files are read lazily by
+    /// the renderer, XPM files are decoded here.
+    pub fn load(resource: &KobelResource) -> Option<Self> {
+        match resource.extension() {
+            Some("svg") => Some(Self::Svg(resource.svg_handle())),
+            Some("png") => Some(Self::Image(resource.image_handle())),
+            Some("xpm") => {
+                let image = resource.read()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|bytes| xpm::decode(&String::from_utf8_lossy(&bytes)));
+
+                match image {
+                    Ok(image) => Some(Self::Image(image.to_handle())),
+                    Err(e) => {
+                        log::warn!("Failed to load icon '{}': {:#}", resource, e);
+                        None
+                    },
+                }
+            },
+            _ => {
+                log::warn!("Unsupported icon format for '{}'. Only SVG, PNG and XPM are supported.", resource);
+                None
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct KobelIconKey {
+    name: String,
+    size: u32,
+    scale: u32,
+    symbolic: bool,
+}
+
+/// Icons already resolved and loaded, including the ones that turned out to
+/// be missing, so a redraw does not touch the disk.
+#[derive(Debug, Default)]
+pub struct KobelIconCache {
+    handles: RwLock<HashMap<KobelIconKey, Option<KobelIconHandle>>>,
+}
+
+impl KobelIconCache {
+    /// Returns the cached handle, or resolves it with `resolve` the first time.
+    pub fn get_or_load(
+        &self,
+        name: &str,
+        size: u32,
+        scale: u32,
+        symbolic: bool,
+        resolve: impl FnOnce() -> Option<KobelResource>,
+    ) -> Option<KobelIconHandle> {
+        let key = KobelIconKey {
+            name: name.to_string(),
+            size,
+            scale,
+            symbolic,
+        };
+
+        if let Some(handle) = self.handles.read().unwrap().get(&key) {
+            return handle.clone();
+        }
+
+        let handle = resolve().and_then(|resource| KobelIconHandle::load(&resource));
+        self.handles.write().unwrap().insert(key, handle.clone());
+
+        handle
+    }
+
+    pub fn clear(&self) {
+        self.handles.write().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn resolves_each_key_once() {
+        let cache = KobelIconCache::default();
+        let resolved = Cell::new(0);
+        let resolve = || {
+            resolved.set(resolved.get() + 1);
+            Some(KobelResource::File("/usr/share/icons/hicolor/scalable/apps/firefox.svg".into()))
+        };
+
+        assert!(matches!(cache.get_or_load("firefox", 48, 1, false, resolve), Some(KobelIconHandle::Svg(_))));
+        assert!(cache.get_or_load("firefox", 48, 1, false, resolve).is_some());
+        assert_eq!(resolved.get(), 1);
+
+        cache.get_or_load("firefox", 48, 2, false, resolve);
+        assert_eq!(resolved.get(), 2);
+
+        cache.clear();
+        cache.get_or_load("firefox", 48, 1, false, resolve);
+        assert_eq!(resolved.get(), 3);
+    }
+
+    #[test]
+    fn remembers_missing_icons() {
+        let cache = KobelIconCache::default();
+        let resolved = Cell::new(0);
+        let resolve = || {
+            resolved.set(resolved.get() + 1);
+            None
+        };
+
+        assert!(cache.get_or_load("spotify", 48, 1, false, resolve).is_none());
+        assert!(cache.get_or_load("spotify", 48, 1, false, resolve).is_none());
+        assert_eq!(resolved.get(), 1);
+    }
+}
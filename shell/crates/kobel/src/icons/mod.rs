@@ -0,0 +1,230 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use iced::{futures::StreamExt, Subscription};
+
+use crate::{resources, util::watch, KobelRootMessage};
+
+pub mod cache;
+pub mod theme;
+pub mod xpm;
+
+pub use cache::{KobelIconCache, KobelIconHandle};
+pub use theme::KobelIconTheme;
+
+/// Searched after every other theme, as all applications install their icons into it.
+pub static FALLBACK_ICON_THEME: &str = "hicolor";
+
+/// Where icon themes are installed, most important first, as defined by the
+/// Icon Theme Specification.
+pub fn icon_base_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".icons"));
+    }
+    dirs.extend(resources::data_home().map(|dir| dir.join("icons")));
+    dirs.extend(resources::data_dirs().into_iter().map(|dir| dir.join("icons")));
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+
+    dirs
+}
+
+/// The icon theme chosen by the user together with every theme it inherits
+/// from, in lookup order, followed by icons that belong to no theme.
+#[derive(Debug, Clone, Default)]
+pub struct KobelIconThemes {
+    name: String,
+    themes: Vec<KobelIconTheme>,
+    unthemed: HashMap<String, PathBuf>,
+    /// Changes with every load, so the watch restarts on the new directories.
+    generation: u64,
+    /// Every directory the lookups depend on.
+    watch_dirs: Arc<[PathBuf]>,
+}
+
+impl KobelIconThemes {
+    pub fn load(name: &str) -> Self {
+        Self::load_from(name, &icon_base_dirs())
+    }
+
+    pub fn load_from(name: &str, base_dirs: &[PathBuf]) -> Self {
+        static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+        let mut themes = vec![];
+
+        add_theme(name, base_dirs, &mut themes);
+        if themes.is_empty() {
+            log::warn!("Icon theme '{}' not found, using '{}'", name, FALLBACK_ICON_THEME);
+        }
+        add_theme(FALLBACK_ICON_THEME, base_dirs, &mut themes);
+
+        let mut unthemed = HashMap::new();
+        for dir in base_dirs {
+            theme::list_icons(dir, &mut unthemed);
+        }
+
+        log::info!(
+            "Using icon themes {}",
+            themes.iter().map(|theme| theme.name.as_str()).collect::<Vec<_>>().join(", ")
+        );
+
+        let watch_dirs = base_dirs.iter()
+            .filter(|dir| dir.is_dir())
+            .cloned()
+            .chain(themes.iter().flat_map(|theme| theme.existing_dirs(base_dirs)))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            themes,
+            unthemed,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            watch_dirs,
+        }
+    }
+
+    /// Name of the theme as configured, even if it turned out not to exist.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Later loads have a higher generation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Finds the icon called `name` for `size` logical pixels at `scale`.
+    /// Like GTK, a missing `a-b-c` falls back to `a-b` and then `a`, and a
+    /// symbolic lookup prefers `-symbolic` variants but settles for full color.
+    pub fn lookup(&self, name: &str, size: u32, scale: u32, symbolic: bool) -> Option<&Path> {
+        let names = lookup_names(name, symbolic);
+
+        for theme in &self.themes {
+            for name in &names {
+                if let Some(path) = theme.lookup(name, size, scale.max(1)) {
+                    return Some(path);
+                }
+            }
+        }
+
+        names.iter().find_map(|name| self.unthemed.get(name).map(PathBuf::as_path))
+    }
+}
+
+/// Sends [`KobelRootMessage::IconsChanged`] when icons are installed, removed
+/// or changed in any directory `icon_themes` was loaded from, or in the
+/// resource directories.
+pub fn subscription(icon_themes: &KobelIconThemes) -> Subscription<KobelRootMessage> {
+    struct IconsWatcher;
+
+    let dirs = icon_themes.watch_dirs.iter()
+        .cloned()
+        .chain(resources::resource_dirs())
+        .collect();
+
+    Subscription::run_with_id(
+        (std::any::TypeId::of::<IconsWatcher>(), icon_themes.generation),
+        watch::watch_paths(dirs, false)
+            .map(|_| KobelRootMessage::IconsChanged),
+    )
+}
+
+// Themes are searched depth first, each before the themes it inherits from
+fn add_theme(name: &str, base_dirs: &[PathBuf], themes: &mut Vec<KobelIconTheme>) {
+    if themes.iter().any(|theme| theme.name == name) {
+        return;
+    }
+
+    let Some(theme) = KobelIconTheme::load(name, base_dirs) else {
+        return;
+    };
+
+    let inherits = theme.inherits.clone();
+    themes.push(theme);
+
+    for parent in inherits {
+        add_theme(&parent, base_dirs, themes);
+    }
+}
+
+fn lookup_names(name: &str, symbolic: bool) -> Vec<String> {
+    let base = name.strip_suffix("-symbolic").unwrap_or(name);
+
+    let mut generic = vec![base.to_string()];
+    let mut rest = base;
+    while let Some((prefix, _)) = rest.rsplit_once('-') {
+        generic.push(prefix.to_string());
+        rest = prefix;
+    }
+
+    if symbolic || base != name {
+        generic.iter()
+            .map(|name| format!("{}-symbolic", name))
+            .chain(generic.iter().cloned())
+            .collect()
+    } else {
+        generic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::{temp_dir, write};
+
+    fn index(inherits: &str) -> String {
+        format!("[Icon Theme]\nInherits={}\nDirectories=48x48/apps,symbolic/apps\n\n[48x48/apps]\nSize=48\n\n[symbolic/apps]\nSize=16\nMinSize=8\nMaxSize=512\nType=Scalable\n", inherits)
+    }
+
+    #[test]
+    fn builds_lookup_names() {
+        assert_eq!(lookup_names("firefox-nightly", false), ["firefox-nightly", "firefox"]);
+        assert_eq!(
+            lookup_names("audio-volume-high", true),
+            ["audio-volume-high-symbolic", "audio-volume-symbolic", "audio-symbolic", "audio-volume-high", "audio-volume", "audio"]
+        );
+        assert_eq!(lookup_names("go-next-symbolic", false)[0], "go-next-symbolic");
+    }
+
+    #[test]
+    fn resolves_through_inheritance_and_fallbacks() {
+        let base = temp_dir("icons", "chain");
+        let pixmaps = temp_dir("icons", "pixmaps");
+
+        write(base.join("Child/index.theme"), &index("Parent"));
+        write(base.join("Parent/index.theme"), &index("Child"));
+        write(base.join("hicolor/index.theme"), &index(""));
+
+        let child_icon = write(base.join("Child/48x48/apps/foot.png"), "");
+        write(base.join("Parent/48x48/apps/foot.png"), "");
+        let parent_icon = write(base.join("Parent/48x48/apps/firefox.svg"), "");
+        let symbolic_icon = write(base.join("Parent/symbolic/apps/firefox-symbolic.svg"), "");
+        let hicolor_icon = write(base.join("hicolor/48x48/apps/org.gnome.Nautilus.png"), "");
+        let pixmap = write(pixmaps.join("vscode.xpm"), "");
+
+        let themes = KobelIconThemes::load_from("Child", &[base.clone(), pixmaps.clone()]);
+
+        assert_eq!(themes.themes.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["Child", "Parent", "hicolor"]);
+        assert_eq!(themes.lookup("foot", 48, 1, false), Some(child_icon.as_path()));
+        assert_eq!(themes.lookup("firefox-nightly", 48, 1, false), Some(parent_icon.as_path()));
+        assert_eq!(themes.lookup("firefox", 16, 1, true), Some(symbolic_icon.as_path()));
+        assert_eq!(themes.lookup("org.gnome.Nautilus", 64, 2, false), Some(hicolor_icon.as_path()));
+        assert_eq!(themes.lookup("vscode", 48, 1, false), Some(pixmap.as_path()));
+        assert_eq!(themes.lookup("spotify", 48, 1, false), None);
+
+        assert!(themes.watch_dirs.contains(&base.join("Parent/symbolic/apps")));
+        assert!(themes.watch_dirs.contains(&pixmaps));
+    }
+
+    #[test]
+    fn missing_themes_fall_back_to_hicolor() {
+        let base = temp_dir("icons", "missing");
+        write(base.join("hicolor/index.theme"), &index(""));
+        let icon = write(base.join("hicolor/48x48/apps/foot.png"), "");
+
+        let themes = KobelIconThemes::load_from("Papirus", &[base]);
+
+        assert_eq!(themes.name(), "Papirus");
+        assert_eq!(themes.lookup("foot", 32, 1, false), Some(icon.as_path()));
+    }
+}
@@ -0,0 +1,325 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use crate::util::key_file;
+
+/// File types an icon can have, in the order the Icon Theme Specification
+/// prefers them when a directory has more than one.
+pub static ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KobelIconSizeType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// One of the subdirectories listed in `index.theme`, holding icons of one size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelIconDirectory {
+    pub path: String,
+    pub size: u32,
+    pub scale: u32,
+    pub size_type: KobelIconSizeType,
+    pub min_size: u32,
+    pub max_size: u32,
+    pub threshold: u32,
+}
+
+impl KobelIconDirectory {
+    fn from_section(path: &str, keys: &HashMap<String, String>) -> Option<Self> {
+        let number = |key: &str| keys.get(key).and_then(|value| value.trim().parse::<u32>().ok());
+
+        let size = number("Size")?;
+        let size_type = match keys.get("Type").map(|t| t.trim()) {
+            Some("Fixed") => KobelIconSizeType::Fixed,
+            Some("Scalable") => KobelIconSizeType::Scalable,
+            _ => KobelIconSizeType::Threshold,
+        };
+
+        Some(Self {
+            path: path.to_string(),
+            size,
+            scale: number("Scale").unwrap_or(1).max(1),
+            size_type,
+            min_size: number("MinSize").unwrap_or(size),
+            max_size: number("MaxSize").unwrap_or(size),
+            threshold: number("Threshold").unwrap_or(2),
+        })
+    }
+
+    /// `DirectoryMatchesSize` from the specification.
+    pub fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.size_type {
+            KobelIconSizeType::Fixed => self.size == size,
+            KobelIconSizeType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            KobelIconSizeType::Threshold => {
+                (self.size.saturating_sub(self.threshold)..=self.size + self.threshold).contains(&size)
+            },
+        }
+    }
+
+    /// `DirectorySizeDistance` from the specification, in device pixels.
+    pub fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+
+        match self.size_type {
+            KobelIconSizeType::Fixed => (self.size * self.scale).abs_diff(wanted),
+            KobelIconSizeType::Scalable => {
+                Self::distance_outside(wanted, self.min_size * self.scale, self.max_size * self.scale)
+            },
+            KobelIconSizeType::Threshold => {
+                let min = self.size.saturating_sub(self.threshold) * self.scale;
+                let max = (self.size + self.threshold) * self.scale;
+
+                if (min..=max).contains(&wanted) {
+                    0
+                } else {
+                    Self::distance_outside(wanted, self.min_size * self.scale, self.max_size * self.scale)
+                }
+            },
+        }
+    }
+
+    fn distance_outside(wanted: u32, min: u32, max: u32) -> u32 {
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+/// An icon theme, merged from its directories in every icon base directory.
+/// The icons are listed once when the theme is loaded, so lookups never touch
+/// the disk.
+#[derive(Debug, Clone, Default)]
+pub struct KobelIconTheme {
+    pub name: String,
+    pub inherits: Vec<String>,
+    pub directories: Vec<KobelIconDirectory>,
+    /// Icons in each entry of `directories`, by name without extension.
+    icons: Vec<HashMap<String, PathBuf>>,
+}
+
+impl KobelIconTheme {
+    /// Loads the theme called `name` from the first base directory that has
+    /// an `index.theme` for it.
+    pub fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        let index_path = base_dirs.iter()
+            .map(|dir| dir.join(name).join("index.theme"))
+            .find(|path| path.is_file())?;
+
+        let contents = match std::fs::read_to_string(&index_path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                log::warn!("Failed to read icon theme '{}': {}", index_path.display(), e);
+                return None;
+            },
+        };
+
+        let mut theme = Self::parse(name, &contents);
+        theme.index(base_dirs);
+
+        Some(theme)
+    }
+
+    pub fn parse(name: &str, index: &str) -> Self {
+        let sections = key_file::parse_groups(index);
+        let header = sections.get("Icon Theme").cloned().unwrap_or_default();
+
+        let list = |key: &str| -> Vec<String> {
+            header.get(key)
+                .map(|value| value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect())
+                .unwrap_or_default()
+        };
+
+        let mut directories = vec![];
+        for path in list("Directories").into_iter().chain(list("ScaledDirectories")) {
+            if directories.iter().any(|d: &KobelIconDirectory| d.path == path) {
+                continue;
+            }
+
+            match sections.get(&path).and_then(|keys| KobelIconDirectory::from_section(&path, keys)) {
+                Some(directory) => directories.push(directory),
+                None => log::debug!("Icon theme '{}' has no valid section for '{}'", name, path),
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            inherits: list("Inherits"),
+            icons: vec![HashMap::new(); directories.len()],
+            directories,
+        }
+    }
+
+    /// The directories of this theme that exist in `base_dirs`, including
+    /// each theme root with its `index.theme`.
+    pub fn existing_dirs(&self, base_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        base_dirs.iter()
+            .map(|base_dir| base_dir.join(&self.name))
+            .filter(|root| root.is_dir())
+            .flat_map(|root| {
+                let directories = self.directories.iter().map(|directory| root.join(&directory.path)).collect::<Vec<_>>();
+                std::iter::once(root).chain(directories)
+            })
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    fn index(&mut self, base_dirs: &[PathBuf]) {
+        for (directory, icons) in self.directories.iter().zip(self.icons.iter_mut()) {
+            // Earlier base directories win, so ~/.local/share/icons can override /usr/share/icons
+            for base_dir in base_dirs {
+                list_icons(&base_dir.join(&self.name).join(&directory.path), icons);
+            }
+        }
+    }
+
+    /// `LookupIcon` from the specification: an icon from a directory for
+    /// exactly this size if there is one, otherwise from the closest size.
+    pub fn lookup(&self, name: &str, size: u32, scale: u32) -> Option<&Path> {
+        let mut closest: Option<(u32, &Path)> = None;
+
+        for (directory, icons) in self.directories.iter().zip(&self.icons) {
+            let Some(path) = icons.get(name) else {
+                continue;
+            };
+
+            if directory.matches_size(size, scale) {
+                return Some(path);
+            }
+
+            let distance = directory.size_distance(size, scale);
+            if closest.is_none_or(|(closest_distance, _)| distance < closest_distance) {
+                closest = Some((distance, path));
+            }
+        }
+
+        closest.map(|(_, path)| path)
+    }
+}
+
+/// Adds the icons in `dir` to `icons`, keeping entries already present and
+/// preferring extensions that come first in [`ICON_EXTENSIONS`].
+pub fn list_icons(dir: &Path, icons: &mut HashMap<String, PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut found: HashMap<String, (usize, PathBuf)> = HashMap::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+            continue;
+        };
+        let Some(rank) = ICON_EXTENSIONS.iter().position(|e| *e == extension) else {
+            continue;
+        };
+
+        let stem = stem.to_string_lossy().to_string();
+        if found.get(&stem).is_none_or(|(existing, _)| rank < *existing) {
+            found.insert(stem, (rank, path));
+        }
+    }
+
+    for (stem, (_, path)) in found {
+        icons.entry(stem).or_insert(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static INDEX: &str = "
+[Icon Theme]
+Name=Test
+Inherits=Adwaita, hicolor
+Directories=16x16/apps,48x48/apps,scalable/apps,missing/apps
+ScaledDirectories=16x16@2/apps
+
+# Comment
+[16x16/apps]
+Size=16
+Type=Fixed
+
+[16x16@2/apps]
+Size=16
+Scale=2
+Type=Fixed
+
+[48x48/apps]
+Size=48
+
+[scalable/apps]
+Size=128
+MinSize=8
+MaxSize=512
+Type=Scalable
+";
+
+    fn theme_with(icons: &[(&str, &str)]) -> KobelIconTheme {
+        let mut theme = KobelIconTheme::parse("Test", INDEX);
+
+        for (dir, name) in icons {
+            let index = theme.directories.iter().position(|d| d.path == *dir).unwrap();
+            theme.icons[index].insert(name.to_string(), PathBuf::from(format!("{}/{}", dir, name)));
+        }
+
+        theme
+    }
+
+    #[test]
+    fn parses_index_theme() {
+        let theme = KobelIconTheme::parse("Test", INDEX);
+
+        assert_eq!(theme.inherits, ["Adwaita", "hicolor"]);
+        assert_eq!(
+            theme.directories.iter().map(|d| d.path.as_str()).collect::<Vec<_>>(),
+            ["16x16/apps", "48x48/apps", "scalable/apps", "16x16@2/apps"]
+        );
+
+        let threshold = &theme.directories[1];
+        assert_eq!(threshold.size_type, KobelIconSizeType::Threshold);
+        assert_eq!((threshold.min_size, threshold.max_size, threshold.threshold), (48, 48, 2));
+        assert_eq!(theme.directories[3].scale, 2);
+    }
+
+    #[test]
+    fn matches_sizes_by_directory_type() {
+        let theme = KobelIconTheme::parse("Test", INDEX);
+        let [fixed, threshold, scalable, scaled] = &theme.directories[..] else {
+            panic!();
+        };
+
+        assert!(fixed.matches_size(16, 1) && !fixed.matches_size(17, 1));
+        assert!(threshold.matches_size(46, 1) && threshold.matches_size(50, 1) && !threshold.matches_size(51, 1));
+        assert!(scalable.matches_size(8, 1) && scalable.matches_size(512, 1) && !scalable.matches_size(513, 1));
+        assert!(scaled.matches_size(16, 2) && !scaled.matches_size(16, 1));
+
+        assert_eq!(fixed.size_distance(24, 1), 8);
+        assert_eq!(scaled.size_distance(24, 1), 8);
+        assert_eq!(threshold.size_distance(64, 1), 16);
+        assert_eq!(scalable.size_distance(600, 1), 88);
+    }
+
+    #[test]
+    fn prefers_exact_then_closest_sizes() {
+        let theme = theme_with(&[("16x16/apps", "foot"), ("48x48/apps", "foot"), ("16x16@2/apps", "foot")]);
+
+        assert_eq!(theme.lookup("foot", 16, 1), Some(Path::new("16x16/apps/foot")));
+        assert_eq!(theme.lookup("foot", 16, 2), Some(Path::new("16x16@2/apps/foot")));
+        assert_eq!(theme.lookup("foot", 40, 1), Some(Path::new("48x48/apps/foot")));
+        assert_eq!(theme.lookup("foot", 20, 1), Some(Path::new("16x16/apps/foot")));
+        assert_eq!(theme.lookup("firefox", 16, 1), None);
+
+        let scalable = theme_with(&[("16x16/apps", "foot"), ("scalable/apps", "foot")]);
+        assert_eq!(scalable.lookup("foot", 96, 1), Some(Path::new("scalable/apps/foot")));
+    }
+}
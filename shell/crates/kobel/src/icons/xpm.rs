@@ -0,0 +1,201 @@
+//! A decoder for XPM images, which older applications still install as icons
+//! in `/usr/share/pixmaps`.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+
+/// Larger images are rejected rather than decoded, as the header is not to
+/// be trusted. Icons are far smaller than this.
+pub static XPM_MAX_SIZE: usize = 1024;
+/// Real images use one or two characters, and rarely more than four.
+pub static XPM_MAX_CHARS_PER_PIXEL: usize = 8;
+
+/// A decoded image as rows of RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KobelXpmImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl KobelXpmImage {
+    pub fn to_handle(self) -> iced::widget::image::Handle {
+        iced::widget::image::Handle::from_rgba(self.width, self.height, self.pixels)
+    }
+}
+
+pub fn decode(contents: &str) -> anyhow::Result<KobelXpmImage> {
+    let mut strings = quoted_strings(contents).into_iter();
+
+    let header = strings.next().context("XPM has no header")?;
+    let values: Vec<usize> = header.split_whitespace()
+        .take(4)
+        .map(|v| v.parse())
+        .collect::<Result<_, _>>()
+        .context("Invalid XPM header")?;
+    let [width, height, num_colors, chars_per_pixel] = values[..] else {
+        bail!("XPM header has too few values");
+    };
+    if chars_per_pixel == 0 || chars_per_pixel > XPM_MAX_CHARS_PER_PIXEL {
+        bail!("XPM has {} characters per pixel", chars_per_pixel);
+    }
+    if width > XPM_MAX_SIZE || height > XPM_MAX_SIZE {
+        bail!("XPM is too large at {}x{}", width, height);
+    }
+    let size = width.checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .context("XPM is too large")?;
+
+    // Any colors beyond one per pixel go unused
+    let mut colors: HashMap<&str, [u8; 4]> = HashMap::with_capacity(num_colors.min(width * height));
+    for _ in 0..num_colors {
+        let line = strings.next().context("XPM ends in its color table")?;
+        let key = line.get(..chars_per_pixel).context("Invalid XPM color")?;
+
+        colors.insert(key, parse_color_definition(&line[chars_per_pixel..]));
+    }
+
+    let mut pixels = Vec::with_capacity(size);
+    for _ in 0..height {
+        let row = strings.next().context("XPM ends before its last row")?;
+
+        for x in 0..width {
+            let key = row.get(x * chars_per_pixel..(x + 1) * chars_per_pixel).context("XPM row is too short")?;
+            pixels.extend(colors.get(key).copied().unwrap_or([0, 0, 0, 0]));
+        }
+    }
+
+    Ok(KobelXpmImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+    })
+}
+
+// XPM files are C source, the image is every string literal in order
+fn quoted_strings(contents: &str) -> Vec<&str> {
+    let mut strings = vec![];
+    let mut rest = contents;
+
+    while let Some(start) = rest.find('"') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('"') else {
+            break;
+        };
+
+        strings.push(&rest[..end]);
+        rest = &rest[end + 1..];
+    }
+
+    strings
+}
+
+// A definition lists colors per visual, such as `c #ff0000 m black`. Only the
+// color visual is used, falling back to monochrome and grayscale.
+fn parse_color_definition(definition: &str) -> [u8; 4] {
+    let tokens: Vec<&str> = definition.split_whitespace().collect();
+
+    for visual in ["c", "g", "g4", "m"] {
+        if let Some(index) = tokens.iter().position(|t| *t == visual) {
+            // Color names may contain spaces, such as "dark slate gray"
+            let value: Vec<&str> = tokens[index + 1..].iter()
+                .take_while(|t| !matches!(**t, "c" | "g" | "g4" | "m" | "s"))
+                .copied()
+                .collect();
+
+            if let Some(color) = parse_color(&value.join(" ")) {
+                return color;
+            }
+        }
+    }
+
+    [0, 0, 0, 0]
+}
+
+fn parse_color(value: &str) -> Option<[u8; 4]> {
+    if let Some(hex) = value.strip_prefix('#') {
+        // #rgb, #rrggbb and #rrrrggggbbbb all occur, use the most significant byte
+        let digits = hex.len() / 3;
+        if digits == 0 || hex.len() % 3 != 0 || !hex.is_ascii() {
+            return None;
+        }
+
+        let channel = |i: usize| -> Option<u8> {
+            let component = &hex[i * digits..(i + 1) * digits];
+            let value = u16::from_str_radix(&component[..digits.min(2)], 16).ok()?;
+            Some(if digits == 1 { (value * 17) as u8 } else { value as u8 })
+        };
+
+        return Some([channel(0)?, channel(1)?, channel(2)?, 255]);
+    }
+
+    let rgb = match value.to_ascii_lowercase().replace(' ', "").as_str() {
+        "none" | "transparent" => return Some([0, 0, 0, 0]),
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "green" => [0, 255, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" => [0, 255, 255],
+        "magenta" => [255, 0, 255],
+        "gray" | "grey" => [190, 190, 190],
+        "darkgray" | "darkgrey" => [169, 169, 169],
+        "lightgray" | "lightgrey" => [211, 211, 211],
+        _ => {
+            log::debug!("Unknown XPM color '{}'", value);
+            return None;
+        },
+    };
+
+    Some([rgb[0], rgb[1], rgb[2], 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_pixels_and_transparency() {
+        let xpm = r##"/* XPM */
+static char *icon[] = {
+/* width height colors chars */
+"3 2 3 1",
+"  c None",
+". c #ff0000",
+"X c white",
+".X ",
+" .X"
+};"##;
+
+        let image = decode(xpm).unwrap();
+
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, [
+            255, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 0,
+            0, 0, 0, 0, 255, 0, 0, 255, 255, 255, 255, 255,
+        ]);
+    }
+
+    #[test]
+    fn parses_color_notations() {
+        assert_eq!(parse_color("#fff"), Some([255, 255, 255, 255]));
+        assert_eq!(parse_color("#ffff00000000"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_color_definition("s background m white c #00ff00"), [0, 255, 0, 255]);
+        assert_eq!(parse_color_definition("c dark gray"), [169, 169, 169, 255]);
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        assert!(decode("\"2 2 1 1\", \"a c black\", \"aa\"").is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        assert!(decode("\"1025 1 1 1\", \"a c black\"").is_err());
+        assert!(decode("\"18446744073709551615 18446744073709551615 1 1\"").is_err());
+        assert!(decode("\"1 1 18446744073709551615 1\", \"a c black\", \"a\"").is_err());
+        assert!(decode("\"1 1 1 18446744073709551615\", \"a c black\", \"a\"").is_err());
+    }
+}
@@ -1,27 +1,45 @@
+mod animation;
+mod appearance;
+mod apps;
+mod clock;
+mod config;
 mod fps;
+mod icons;
+mod output;
 mod widget;
 mod panel;
+mod resources;
 mod state;
+mod theme;
+mod trash;
 mod util;
+mod wayfire;
+mod wayland;
 
 use chrono::{DateTime, Local};
 
 use iced::advanced::subscription;
 use iced::daemon::Appearance;
-use iced::widget::row;
 use iced::window::Id;
 use iced::{event, keyboard, mouse, Color, Element, Subscription, Task, Theme};
+use wayland_client::protocol::wl_output::WlOutput;
 
 use std::sync::{Arc};
-use std::time::{Duration};
-
-use crate::panel::bar::KobelBar;
-use crate::panel::context_menu::KobelContextMenu;
-use crate::panel::debug::KobelDebug;
-use crate::panel::dock::KobelDock;
-use crate::panel::search::{self, KobelSearch};
-use crate::panel::wallpaper::KobelWallpaper;
+use std::time::Instant;
+
+use crate::appearance::KobelColorScheme;
+use crate::apps::launch::KobelLaunchEvent;
+use crate::apps::launcher_entry::KobelLauncherEntryEvent;
+use crate::config::KobelConfig;
+use crate::icons::KobelIconThemes;
+use crate::output::KobelOutput;
+use crate::panel::registry::KobelPanelRegistry;
 use crate::state::KobelShellState;
+use crate::theme::KobelThemeVariant;
+use crate::wayfire::KobelWayfireEvent;
+use crate::wayfire::protocol::KobelWayfireView;
+use crate::wayland::foreign_toplevel::KobelToplevelEvent;
+use crate::wayland::wayfire_shell::KobelHotspotEvent;
 
 #[tokio::main]
 pub async fn main() -> iced::Result {
@@ -37,18 +55,15 @@ pub async fn main() -> iced::Result {
 struct App {
     state: Arc<KobelShellState>,
 
-    wallpaper: KobelWallpaper,
-    bar: KobelBar,
-    dock: KobelDock,
-    debug: KobelDebug,
-    search: KobelSearch,
-
-    context_menu: Option<KobelContextMenu>,
+    panels: KobelPanelRegistry,
 }
 
 #[derive(Debug, Clone)]
 pub enum KobelRootMessage {
+    /// Sent on every wall-clock second while a panel shows the time.
     Tick(DateTime<Local>),
+    /// Sent for every rendered frame while a panel is animating.
+    AnimationFrame(Instant),
     Noop,
 
     CursorMoved {
@@ -62,47 +77,95 @@ pub enum KobelRootMessage {
         modifiers: keyboard::Modifiers,
         keys: Vec<keyboard::Key>,
     },
-    ScreenSizeChanged {
-        size: iced::Size,
+    /// A pointer button went down on the surface `window_id`. `captured` is
+    /// set when a widget there handled the press.
+    PointerPressed {
+        window_id: Id,
+        button: mouse::Button,
+        captured: bool,
     },
+    /// The surface with this ID lost keyboard focus.
+    SurfaceUnfocused(Id),
+    OutputAdded(KobelOutput),
+    OutputUpdated(KobelOutput),
+    OutputRemoved(WlOutput),
+    ConfigReloaded(Arc<KobelConfig>),
+    SetThemeVariant(KobelThemeVariant),
+    ThemesChanged,
+    ThemeChanged,
+    /// Icons were installed, removed or changed on disk.
+    IconsChanged,
+    /// Icon themes were loaded in the background after icons or the
+    /// configured icon theme changed.
+    IconThemesLoaded(Arc<KobelIconThemes>),
+    /// Desktop entries were installed, removed or changed on disk.
+    AppsChanged,
+    /// The shared MIME database was rebuilt.
+    MimeTypesChanged,
+    /// Pins or unpins the app with this desktop file ID in the dock.
+    SetAppPinned(String, bool),
+    /// Replaces the dock's pinned apps, in this order.
+    SetPinnedApps(Vec<String>),
+    ColorSchemeChanged(KobelColorScheme),
+    AccentColorChanged(Option<Color>),
+    /// Sent whenever the connection to the compositor's IPC is (re)established.
+    WayfireConnected,
+    WayfireDisconnected,
+    WayfireEvent(Box<KobelWayfireEvent>),
+    /// The compositor's views, listed after connecting or switching workspaces.
+    WayfireViewsListed(Vec<KobelWayfireView>),
+    /// A window opened, changed or closed.
+    Toplevel(KobelToplevelEvent),
+    /// The pointer reached or left the bottom edge of an output.
+    Hotspot(KobelHotspotEvent),
+    /// An app published a count, progress or urgency for its launcher.
+    LauncherEntry(KobelLauncherEntryEvent),
+    /// An app launched from the shell was spawned, failed or gave up on
+    /// showing a window.
+    Launch(KobelLaunchEvent),
+    /// Files were trashed, restored or deleted.
+    TrashChanged,
+    /// Whether anything is in the trash, as checked in the background after
+    /// it changed.
+    TrashChecked(bool),
 
     Panel(panel::KobelPanelMessage),
 
     OpenContextMenu {
+        /// The output of the surface the pointer is on, which the menu opens on.
+        output: KobelOutput,
         width: f32,
-        height: f32,
+        items: Vec<panel::context_menu::KobelContextMenuItem>,
+        /// Where the surface the pointer is on sits on the screen, as pointer
+        /// positions are relative to it.
+        surface_offset: iced::Vector,
+    },
+    /// Shows the windows of the app with this ID above the dock on `output`,
+    /// centered on `x`. Sent while the app is hovered.
+    ShowWindowPreviews {
+        output: KobelOutput,
+        app_id: String,
+        x: f32,
     },
+    /// The pointer left the app the previews are for. They close shortly
+    /// after, unless the pointer moves onto them.
+    HideWindowPreviews,
+    CloseWindowPreviews,
 }
 
 impl App {
     fn new() -> (App, Task<KobelRootMessage>) {
         let state = Arc::new(KobelShellState::new());
 
-        let (wallpaper, wallpaper_task) = KobelWallpaper::new(state.clone());
-        let (bar, bar_task) = KobelBar::new(state.clone());
-        let (dock, dock_task) = KobelDock::new(state.clone());
-        let (debug, debug_task) = KobelDebug::new(state.clone());
-        let (search, search_task) = KobelSearch::new(state.clone());
+        let (panels, panels_task) = KobelPanelRegistry::new(state.clone());
 
         (
             Self {
                 state,
-
-                wallpaper,
-                bar,
-                dock,
-                debug,
-                search,
-
-                context_menu: None,
+                panels,
             },
-            Task::batch(vec![
-                wallpaper_task,
-                bar_task,
-                dock_task,
-                debug_task,
-                search_task,
-            ]),
+            // Looking through the trash of every mount waits for the first update
+            Task::batch(vec![panels_task, Task::done(KobelRootMessage::TrashChanged)]),
         )
     }
 
@@ -111,68 +174,48 @@ impl App {
     }
 
     fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
-        let mut command = Task::batch(vec![
-            self.state.update(message.clone()),
-            self.wallpaper.update(message.clone()),
-            self.bar.update(message.clone()),
-            self.dock.update(message.clone()),
-            self.debug.update(message.clone()),
-            self.search.update(message.clone()),
-        ]);
-
-        match message {
-            KobelRootMessage::Noop => {
-                log::warn!("noop");
-            },
-            KobelRootMessage::OpenContextMenu { width, height } => {
-                let rect = iced::Rectangle {
-                    x: self.state.pointer_position.read().unwrap().x,
-                    y: self.state.pointer_position.read().unwrap().y,
-                    width,
-                    height,
-                };
-
-                let (context_menu, task) = KobelContextMenu::new(self.state.clone(), rect);
-                self.context_menu = Some(context_menu);
-                command = command.chain(task);
-            },
-            _ => {}
+        if let KobelRootMessage::Noop = message {
+            log::warn!("noop");
         }
 
-        return command;
+        // The shared state goes first, so panels see the new config, theme and outputs
+        let state_task = self.state.update(message.clone());
+
+        Task::batch(vec![
+            state_task,
+            self.panels.update(message),
+        ])
     }
 
     fn view(&self, id: Id) -> Element<KobelRootMessage> {
-        match id {
-            id if id == self.wallpaper.id => self.wallpaper.view(),
-            id if id == self.bar.id => self.bar.view(),
-            id if id == self.dock.id => self.dock.view(),
-            id if id == self.debug.id => self.debug.view(),
-            id if id == self.search.id => self.search.view(),
-            id if self.context_menu.as_ref().map_or(false, |cm| cm.id == id) => {
-                if let Some(context_menu) = &self.context_menu {
-                    context_menu.view()
-                } else {
-                    row![].into()
-                }
-            },
-            _ => {
-                log::warn!("Unknown window ID: {:?}", id);
-                row![].into()
-            },
-        }
-
+        self.panels.view(id)
     }
 
     fn subscription(&self) -> Subscription<KobelRootMessage> {
         Subscription::batch(vec![
-            iced::time::every(Duration::from_millis(8))
-                .map(|_| KobelRootMessage::Tick(Local::now())),
+            self.panels.subscription(),
+            config::subscription(),
+            theme::subscription(),
+            icons::subscription(&self.state.icon_themes()),
+            apps::subscription(),
+            apps::launcher_entry::subscription(),
+            trash::subscription(),
+            wayfire::subscription(),
+            wayland::foreign_toplevel::subscription(self.state.toplevels.clone()),
+            wayland::wayfire_shell::subscription(),
+            appearance::subscription(self.state.config().theme.color_scheme, self.state.appearance()),
             iced::event::listen_with(|evt, status, window_id| 
                 match evt {
                     event::Event::Mouse(mouse::Event::CursorMoved { position }) => {
                         Some(KobelRootMessage::CursorMoved { position })
                     },
+                    event::Event::Mouse(mouse::Event::ButtonPressed(button)) => {
+                        Some(KobelRootMessage::PointerPressed {
+                            window_id,
+                            button,
+                            captured: status == event::Status::Captured,
+                        })
+                    },
                     event::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
                         Some(KobelRootMessage::KeysPressed {
                             modifiers,
@@ -187,16 +230,27 @@ impl App {
                     },
                     event::Event::PlatformSpecific(event::PlatformSpecific::Wayland(evt)) => {
                         match evt {
-                            event::wayland::Event::Output(output_evt, _) => match output_evt {
+                            event::wayland::Event::Output(output_evt, wl_output) => match output_evt {
+                                event::wayland::OutputEvent::Created(Some(info)) => {
+                                    Some(KobelRootMessage::OutputAdded(
+                                        KobelOutput::new(wl_output, info.name, info.description, info.logical_size, info.scale_factor)
+                                    ))
+                                },
+                                // The info follows in a later update
+                                event::wayland::OutputEvent::Created(None) => {
+                                    Some(KobelRootMessage::OutputAdded(KobelOutput::new(wl_output, None, None, None, 1)))
+                                },
                                 event::wayland::OutputEvent::InfoUpdate(info) => {
-                                    Some(KobelRootMessage::ScreenSizeChanged {
-                                        size: iced::Size {
-                                            width: info.logical_size.unwrap_or_default().0 as f32,
-                                            height: info.logical_size.unwrap_or_default().1 as f32,
-                                        },
-                                    })
+                                    Some(KobelRootMessage::OutputUpdated(
+                                        KobelOutput::new(wl_output, info.name, info.description, info.logical_size, info.scale_factor)
+                                    ))
                                 },
-                                _ => None,
+                                event::wayland::OutputEvent::Removed => {
+                                    Some(KobelRootMessage::OutputRemoved(wl_output))
+                                },
+                            },
+                            event::wayland::Event::Layer(event::wayland::LayerEvent::Unfocused, ..) => {
+                                Some(KobelRootMessage::SurfaceUnfocused(window_id))
                             },
                             _ => {
                                 None
@@ -210,7 +264,7 @@ impl App {
     }
 
     fn theme(&self, _id: Id) -> iced::Theme {
-        Theme::KanagawaLotus
+        self.state.iced_theme()
     }
 
     fn style(&self, theme: &Theme) -> Appearance {
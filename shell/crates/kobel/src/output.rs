@@ -0,0 +1,62 @@
+use wayland_client::protocol::wl_output::WlOutput;
+
+/// A monitor as announced by the compositor. The name and size only become
+/// known once the compositor has sent the output's info.
+#[derive(Debug, Clone)]
+pub struct KobelOutput {
+    pub wl_output: WlOutput,
+
+    /// Connector name, such as `eDP-1` or `DP-2`.
+    pub name: Option<String>,
+    /// Human readable description, usually the make and model.
+    pub description: Option<String>,
+    pub size: iced::Size,
+    pub scale_factor: i32,
+}
+
+impl KobelOutput {
+    pub fn new(
+        wl_output: WlOutput,
+        name: Option<String>,
+        description: Option<String>,
+        logical_size: Option<(i32, i32)>,
+        scale_factor: i32,
+    ) -> Self {
+        let (width, height) = logical_size.unwrap_or_default();
+
+        Self {
+            wl_output,
+            name,
+            description,
+            size: iced::Size::new(width as f32, height as f32),
+            scale_factor,
+        }
+    }
+
+    pub fn has_info(&self) -> bool {
+        self.name.is_some()
+    }
+
+    /// Checks the output against a list of patterns from the user's config.
+    /// A pattern matches the connector name or the description, and may end in
+    /// a `*` to match a prefix; a lone `*` matches every output.
+    pub fn matches(&self, patterns: &[String]) -> bool {
+        patterns.iter().any(|pattern| {
+            [&self.name, &self.description]
+                .into_iter()
+                .flatten()
+                .any(|value| match pattern.strip_suffix('*') {
+                    Some(prefix) => value.starts_with(prefix),
+                    None => value == pattern,
+                })
+        })
+    }
+
+    pub fn display_name(&self) -> String {
+        match (&self.name, &self.description) {
+            (Some(name), Some(description)) => format!("{} ({})", name, description),
+            (Some(name), None) => name.clone(),
+            _ => "Unknown output".to_string(),
+        }
+    }
+}
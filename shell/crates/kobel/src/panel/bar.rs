@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use iced::{core::{Element, Widget}, platform_specific::shell::commands::{layer_surface::get_layer_surface, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::Row, Background, Color, Padding, Radius, Task};
-use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, SctkLayerSurfaceSettings};
+use iced::{core::{Element, Widget}, platform_specific::shell::commands::{layer_surface::{set_exclusive_zone, set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::Row, Background, Color, Padding, Radius, Subscription, Task};
+use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, IcedOutput, SctkLayerSurfaceSettings};
 use iced::widget::{container, row, column, text, svg};
-use crate::{widget::{k_button::{k_button, KobelShellButtonType}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
+use crate::{clock, output::KobelOutput, panel::{KobelPanel, KobelPanelKind}, widget::{k_button::{k_button, KobelShellButtonType}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
 
 use crate::{state::KobelShellState};
 
@@ -20,46 +20,99 @@ pub enum KobelBarMessage {
 #[derive(Debug)]
 pub struct KobelBar {
     pub id: iced::window::Id,
+    pub output: KobelOutput,
     state: Arc<KobelShellState>,
 }
 
 impl KobelBar {
-    pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
+    pub fn new(state: Arc<KobelShellState>, output: KobelOutput) -> (Self, Task<KobelRootMessage>) {
         let id = iced::window::Id::unique();
 
-        let surface = get_layer_surface(SctkLayerSurfaceSettings {
-            id,
+        (
+            KobelBar {
+                id,
+                output,
+                state,
+            },
+            Task::none()
+        )
+    }
+
+    fn resize_surface(&self) -> Task<KobelRootMessage> {
+        let bar = self.state.config().bar.clone();
+
+        Task::batch(vec![
+            set_size(self.id, None, Some(bar.height as u32)),
+            set_exclusive_zone(self.id, bar.height + (bar.margin * 2)),
+            set_margin(self.id, bar.margin, bar.margin, bar.margin, bar.margin),
+        ])
+    }
+}
+
+impl KobelPanel for KobelBar {
+    fn id(&self) -> iced::window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::Bar
+    }
+
+    fn output(&self) -> Option<&KobelOutput> {
+        Some(&self.output)
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        let bar = self.state.config().bar.clone();
+
+        SctkLayerSurfaceSettings {
+            id: self.id,
             namespace: "kobelwm".to_string(),
+            output: IcedOutput::Output(self.output.wl_output.clone()),
             layer: Layer::Overlay,
             anchor: Anchor::TOP | Anchor::LEFT | Anchor::RIGHT,
-            size: Some((None, Some(state.bar_height as u32))),
-            exclusive_zone: state.bar_height + (state.bar_margin * 2),
+            size: Some((None, Some(bar.height as u32))),
+            exclusive_zone: bar.height + (bar.margin * 2),
             margin: IcedMargin {
-                top: state.bar_margin,
-                bottom: state.bar_margin,
-                left: state.bar_margin,
-                right: state.bar_margin,
+                top: bar.margin,
+                bottom: bar.margin,
+                left: bar.margin,
+                right: bar.margin,
             },
             keyboard_interactivity: KeyboardInteractivity::OnDemand,
             pointer_interactivity: true,
             ..Default::default()
-        });
+        }
+    }
 
-        (
-            KobelBar {
-                id,
-                state,
-            },
-            surface
-        )
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        match message {
+            KobelRootMessage::ConfigReloaded(_) => true,
+            KobelRootMessage::OutputUpdated(output) => output.wl_output == self.output.wl_output,
+            _ => false,
+        }
+    }
+
+    fn subscription(&self) -> Subscription<KobelRootMessage> {
+        clock::subscription()
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
-        Task::none()
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+        match message {
+            KobelRootMessage::ConfigReloaded(_) => self.resize_surface(),
+            KobelRootMessage::OutputUpdated(output) if output.wl_output == self.output.wl_output => {
+                self.output = output;
+                Task::none()
+            },
+            _ => Task::none(),
+        }
     }
 
-    pub fn view(&self) -> Element<KobelRootMessage, iced::Theme, iced::Renderer> {
-        let button_radii = self.state.bar_radii - self.state.bar_padding;
+    fn view(&self) -> Element<KobelRootMessage, iced::Theme, iced::Renderer> {
+        let bar = self.state.config().bar.clone();
+        let palette = self.state.palette();
+
+        let button_radii = bar.radii - bar.padding;
 
         let time_str = self.state.now
             .read()
@@ -121,7 +174,7 @@ impl KobelBar {
                 .into(),
             k_button(&self.state, k_icon(&self.state, "search.svg")
                 .color(if *self.state.search_panel_visible.read().unwrap() {
-                    Some(Color::WHITE)
+                    Some(palette.accent_text)
                 } else {
                     None
                 })
@@ -140,7 +193,7 @@ impl KobelBar {
             actions_row = actions_row.push(
                 k_button(&self.state, k_icon(&self.state, "inspector.svg")
                     .color(if *self.state.debug_panel_visible.read().unwrap() {
-                        Some(Color::WHITE)
+                        Some(palette.accent_text)
                     } else {
                         None
                     })
@@ -167,7 +220,7 @@ impl KobelBar {
             .width(iced::Length::Fill)
             .height(iced::Length::Fill)
             .align_y(iced::Alignment::Center)
-            .padding(self.state.bar_padding);
+            .padding(bar.padding);
 
         container(bar_row)
             .width(iced::Length::Fill)
@@ -175,23 +228,23 @@ impl KobelBar {
             .align_x(iced::Alignment::Center)
             .align_y(iced::Alignment::Center)
             .style(move |_| container::Style {
-                background: Some(self.state.shell_background.clone()),
-                text_color: Some(self.state.shell_text_color),
+                background: Some(Background::Color(palette.background)),
+                text_color: Some(palette.text),
                 border: iced::Border {
                     width: 0.0,
                     radius: Radius {
-                        top_left: if self.state.bar_radii_bottom_only {
+                        top_left: if bar.radii_bottom_only {
                             0.0.into()
                         } else {
-                            self.state.bar_radii.into()
+                            bar.radii.into()
                         },
-                        top_right: if self.state.bar_radii_bottom_only {
+                        top_right: if bar.radii_bottom_only {
                             0.0.into()
                         } else {
-                            self.state.bar_radii.into()
+                            bar.radii.into()
                         },
-                        bottom_left: self.state.bar_radii.into(),
-                        bottom_right: self.state.bar_radii.into(),
+                        bottom_left: bar.radii.into(),
+                        bottom_right: bar.radii.into(),
                     },
                     color: Color::TRANSPARENT,
                 },
@@ -199,4 +252,4 @@ impl KobelBar {
             })
             .into()
     }
-}
\ No newline at end of file
+}
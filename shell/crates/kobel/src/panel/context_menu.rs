@@ -1,14 +1,80 @@
 use std::{path::PathBuf, sync::Arc};
 
-use iced::{core::window, platform_specific::shell::commands::{layer_surface::get_layer_surface, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{checkbox, column, container, horizontal_rule, row, slider, svg, text, text_input, tooltip, vertical_rule}, window::Position, Background, Color, Element, Point, Rectangle, Shadow, Task};
-use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, SctkLayerSurfaceSettings};
+use iced::{core::window, keyboard, mouse, platform_specific::shell::commands::{layer_surface::{set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{checkbox, column, container, horizontal_rule, row, slider, svg, text, text_input, tooltip, vertical_rule}, window::Position, Background, Color, Element, Point, Rectangle, Shadow, Task};
+use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, IcedOutput, SctkLayerSurfaceSettings};
 
-use crate::{state::KobelShellState, widget::{k_button::{k_button, KobelShellButtonMode}, k_text::k_text, primitives::button}, KobelRootMessage};
+use crate::{output::KobelOutput, panel::{KobelPanel, KobelPanelKind}, state::KobelShellState, widget::{k_button::{k_button, KobelShellButtonMode}, k_text::k_text, primitives::button}, KobelRootMessage};
+
+static CONTEXT_MENU_PADDING: f32 = 6.0;
+static CONTEXT_MENU_SPACING: f32 = 2.0;
+static CONTEXT_MENU_ITEM_HEIGHT: f32 = 30.0;
+static CONTEXT_MENU_SEPARATOR_HEIGHT: f32 = 10.0;
 
 #[derive(Debug, Clone)]
 pub enum KobelContextMenuMessage {
     Toggle,
     DebugBorderStyleToggled(bool),
+    /// An item was picked: the menu closes and `message` is sent.
+    Activate(Box<KobelRootMessage>),
+    /// The item at this index of the menu on this surface was picked, and
+    /// asks for confirmation first.
+    Confirm(window::Id, usize),
+    /// Closes the menu without doing anything.
+    Dismiss,
+}
+
+#[derive(Debug, Clone)]
+pub enum KobelContextMenuItem {
+    /// An item that sends `message` when picked, or is disabled without one.
+    Action {
+        label: String,
+        message: Option<KobelRootMessage>,
+    },
+    /// An item for something that cannot be undone. Picking it turns the
+    /// menu into `prompt`, which sends `message` only if picked again.
+    Confirm {
+        label: String,
+        prompt: String,
+        message: KobelRootMessage,
+    },
+    Separator,
+}
+
+impl KobelContextMenuItem {
+    pub fn action(label: impl Into<String>, message: KobelRootMessage) -> Self {
+        Self::Action {
+            label: label.into(),
+            message: Some(message),
+        }
+    }
+
+    pub fn disabled(label: impl Into<String>) -> Self {
+        Self::Action {
+            label: label.into(),
+            message: None,
+        }
+    }
+
+    pub fn confirm(label: impl Into<String>, prompt: impl Into<String>, message: KobelRootMessage) -> Self {
+        Self::Confirm {
+            label: label.into(),
+            prompt: prompt.into(),
+            message,
+        }
+    }
+
+    /// Height of a menu showing `items`, for sizing its surface.
+    pub fn menu_height(items: &[KobelContextMenuItem]) -> f32 {
+        let content: f32 = items.iter()
+            .map(|item| match item {
+                KobelContextMenuItem::Action { .. } | KobelContextMenuItem::Confirm { .. } => CONTEXT_MENU_ITEM_HEIGHT,
+                KobelContextMenuItem::Separator => CONTEXT_MENU_SEPARATOR_HEIGHT,
+            })
+            .sum();
+        let spacing = CONTEXT_MENU_SPACING * items.len().saturating_sub(1) as f32;
+
+        content + spacing + CONTEXT_MENU_PADDING * 2.0
+    }
 }
 
 impl Into<KobelRootMessage> for KobelContextMenuMessage {
@@ -21,68 +87,150 @@ impl Into<KobelRootMessage> for KobelContextMenuMessage {
 pub struct KobelContextMenu {
     pub id: window::Id,
     state: Arc<KobelShellState>,
+    output: KobelOutput,
+    rect: Rectangle,
+    items: Vec<KobelContextMenuItem>,
 }
 
 impl KobelContextMenu {
-    pub fn new(state: Arc<KobelShellState>, rect: Rectangle) -> (Self, Task<KobelRootMessage>) {
+    pub fn new(state: Arc<KobelShellState>, output: KobelOutput, rect: Rectangle, items: Vec<KobelContextMenuItem>) -> (Self, Task<KobelRootMessage>) {
         let id = window::Id::unique();
 
-        let surface = get_layer_surface(SctkLayerSurfaceSettings {
-            id,
+        (
+            Self {
+                id,
+                state,
+                output,
+                rect,
+                items,
+            },
+            Task::none()
+        )
+    }
+
+    // The menu stays where it is, moving up only if the prompt would no
+    // longer fit on the output
+    fn confirm(&mut self, index: usize) -> Task<KobelRootMessage> {
+        let Some(KobelContextMenuItem::Confirm { label, prompt, message }) = self.items.get(index).cloned() else {
+            return Task::none();
+        };
+
+        self.items = vec![
+            KobelContextMenuItem::disabled(prompt),
+            KobelContextMenuItem::Separator,
+            KobelContextMenuItem::action(label, message),
+            KobelContextMenuItem::action("Cancel", KobelContextMenuMessage::Dismiss.into()),
+        ];
+
+        let screen = self.output.size;
+        self.rect.height = KobelContextMenuItem::menu_height(&self.items);
+        self.rect.y = self.rect.y.min(screen.height - self.rect.height).max(0.0);
+
+        Task::batch(vec![
+            set_size(self.id, Some(self.rect.width as u32), Some(self.rect.height as u32)),
+            set_margin(self.id, self.rect.y as i32, 0, 0, self.rect.x as i32),
+        ])
+    }
+}
+
+/// Whether `message` closes the menu on surface `id`: Escape, a press
+/// anywhere else in the shell, or the menu losing keyboard focus.
+fn dismisses(id: window::Id, message: &KobelRootMessage) -> bool {
+    match message {
+        KobelRootMessage::KeysPressed { keys, .. } => keys.contains(&keyboard::Key::Named(keyboard::key::Named::Escape)),
+        // A right press taken by a widget opens another menu, which is
+        // already in place of this one by the time the press arrives here
+        KobelRootMessage::PointerPressed { window_id, button, captured } => {
+            *window_id != id && !(*button == mouse::Button::Right && *captured)
+        },
+        KobelRootMessage::SurfaceUnfocused(window_id) => *window_id == id,
+        _ => false,
+    }
+}
+
+impl KobelPanel for KobelContextMenu {
+    fn id(&self) -> window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::ContextMenu
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        SctkLayerSurfaceSettings {
+            id: self.id,
             namespace: "kobelwm".to_string(),
             layer: Layer::Overlay,
             anchor: Anchor::TOP | Anchor::LEFT,
-            size: Some((Some(rect.width as u32), Some(rect.height as u32))),
+            output: IcedOutput::Output(self.output.wl_output.clone()),
+            size: Some((Some(self.rect.width as u32), Some(self.rect.height as u32))),
             exclusive_zone: -1,
             margin: IcedMargin {
-                top: rect.y as i32,
-                left: rect.x as i32,
+                top: self.rect.y as i32,
+                left: self.rect.x as i32,
                 right: 0,
                 bottom: 0,
             },
             keyboard_interactivity: KeyboardInteractivity::OnDemand,
             pointer_interactivity: true,
             ..Default::default()
-        });
+        }
+    }
 
-        (
-            Self {
-                id,
-                state,
-            },
-            surface
-        )
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        match message {
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::ContextMenu(KobelContextMenuMessage::Confirm(id, _))) => *id == self.id,
+            message => dismisses(self.id, message),
+        }
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
-        Task::none()
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+        match message {
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::ContextMenu(KobelContextMenuMessage::Confirm(_, index))) => {
+                self.confirm(index)
+            },
+            message if dismisses(self.id, &message) => Task::done(KobelContextMenuMessage::Dismiss.into()),
+            _ => Task::none(),
+        }
     }
 
-    pub fn view(&self) -> Element<KobelRootMessage> {
-        let debug_ui = column![
-            k_button(&self.state, k_text(&self.state, "Change wallpaper..."))
-                .mode(KobelShellButtonMode::MenuItem),
-            horizontal_rule(10.0),
-            k_button(&self.state, k_text(&self.state, "Display Settings"))
-                .mode(KobelShellButtonMode::MenuItem),
-            k_button(&self.state, k_text(&self.state, "Settings"))
-                .mode(KobelShellButtonMode::MenuItem),
-            horizontal_rule(10.0),
-            k_button(&self.state, k_text(&self.state, "Lock"))
-                .mode(KobelShellButtonMode::MenuItem)
-        ]
-            .spacing(2);
+    fn view(&self) -> Element<KobelRootMessage> {
+        let mut menu_ui = column![].spacing(CONTEXT_MENU_SPACING);
 
-        container(container(debug_ui)
-            .padding(6)
+        for (index, item) in self.items.iter().enumerate() {
+            menu_ui = match item {
+                KobelContextMenuItem::Action { label, message } => {
+                    let mut item_ui = k_button(&self.state, k_text(&self.state, label.clone()))
+                        .mode(KobelShellButtonMode::MenuItem);
+
+                    if let Some(message) = message {
+                        item_ui = item_ui.on_press(KobelContextMenuMessage::Activate(Box::new(message.clone())).into());
+                    }
+
+                    menu_ui.push(item_ui)
+                },
+                KobelContextMenuItem::Confirm { label, .. } => {
+                    menu_ui.push(k_button(&self.state, k_text(&self.state, format!("{}…", label)))
+                        .mode(KobelShellButtonMode::MenuItem)
+                        .on_press(KobelContextMenuMessage::Confirm(self.id, index).into()))
+                },
+                KobelContextMenuItem::Separator => menu_ui.push(horizontal_rule(CONTEXT_MENU_SEPARATOR_HEIGHT)),
+            };
+        }
+
+        let palette = self.state.palette();
+
+        container(container(menu_ui)
+            .padding(CONTEXT_MENU_PADDING)
             .width(iced::Length::Fill)
             .height(iced::Length::Fill)
             .style(move |_| container::Style {
-                background: Some(self.state.shell_background.clone().scale_alpha(2.0)),
-                text_color: Some(self.state.shell_text_color),
+                background: Some(Background::Color(palette.background).scale_alpha(2.0)),
+                text_color: Some(palette.text),
                 border: iced::Border {
                     width: 1.0,
-                    color: self.state.shell_text_color.scale_alpha(0.15),
+                    color: palette.border,
                     radius: 14.0.into(),
                     ..Default::default()
                 },
@@ -95,4 +243,58 @@ impl KobelContextMenu {
             .align_y(iced::Alignment::Center)
             .into()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: keyboard::Key) -> KobelRootMessage {
+        KobelRootMessage::KeysPressed {
+            modifiers: keyboard::Modifiers::empty(),
+            keys: vec![key],
+        }
+    }
+
+    fn press(window_id: window::Id, button: mouse::Button, captured: bool) -> KobelRootMessage {
+        KobelRootMessage::PointerPressed { window_id, button, captured }
+    }
+
+    #[test]
+    fn dismisses_on_escape() {
+        let menu = window::Id::unique();
+
+        assert!(dismisses(menu, &key(keyboard::Key::Named(keyboard::key::Named::Escape))));
+        assert!(!dismisses(menu, &key(keyboard::Key::Named(keyboard::key::Named::Enter))));
+        assert!(!dismisses(menu, &key(keyboard::Key::Character("q".into()))));
+    }
+
+    #[test]
+    fn dismisses_on_presses_outside_the_menu() {
+        let menu = window::Id::unique();
+        let dock = window::Id::unique();
+
+        assert!(dismisses(menu, &press(dock, mouse::Button::Left, false)));
+        assert!(dismisses(menu, &press(dock, mouse::Button::Left, true)));
+        assert!(dismisses(menu, &press(dock, mouse::Button::Right, false)));
+        assert!(!dismisses(menu, &press(menu, mouse::Button::Left, true)));
+        assert!(!dismisses(menu, &press(menu, mouse::Button::Left, false)));
+    }
+
+    #[test]
+    fn keeps_the_menu_a_right_press_opened() {
+        let menu = window::Id::unique();
+        let dock = window::Id::unique();
+
+        assert!(!dismisses(menu, &press(dock, mouse::Button::Right, true)));
+    }
+
+    #[test]
+    fn dismisses_on_focus_loss() {
+        let menu = window::Id::unique();
+        let dock = window::Id::unique();
+
+        assert!(dismisses(menu, &KobelRootMessage::SurfaceUnfocused(menu)));
+        assert!(!dismisses(menu, &KobelRootMessage::SurfaceUnfocused(dock)));
+    }
+}
@@ -1,9 +1,9 @@
 use std::{path::PathBuf, sync::Arc};
 
-use iced::{core::window, platform_specific::shell::commands::{layer_surface::get_layer_surface, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{checkbox, column, container, horizontal_rule, row, slider, svg, text, text_input, tooltip, vertical_rule}, Background, Color, Element, Shadow, Task};
+use iced::{core::window, platform_specific::shell::commands::{subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{checkbox, column, container, horizontal_rule, row, slider, svg, text, text_input, tooltip, vertical_rule}, Background, Color, Element, Shadow, Subscription, Task};
 use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, SctkLayerSurfaceSettings};
 
-use crate::{state::KobelShellState, widget::{k_text::k_text, primitives::button}, KobelRootMessage};
+use crate::{clock, panel::{KobelPanel, KobelPanelKind}, state::KobelShellState, widget::{k_text::k_text, primitives::button}, KobelRootMessage};
 
 #[derive(Debug, Clone)]
 pub enum KobelDebugMessage {
@@ -27,8 +27,28 @@ impl KobelDebug {
     pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
         let id = window::Id::unique();
 
-        let surface = get_layer_surface(SctkLayerSurfaceSettings {
-            id,
+        (
+            Self {
+                id,
+                state,
+            },
+            Task::none()
+        )
+    }
+}
+
+impl KobelPanel for KobelDebug {
+    fn id(&self) -> window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::Debug
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        SctkLayerSurfaceSettings {
+            id: self.id,
             namespace: "kobelwm".to_string(),
             layer: Layer::Overlay,
             anchor: Anchor::TOP | Anchor::RIGHT,
@@ -42,18 +62,22 @@ impl KobelDebug {
             keyboard_interactivity: KeyboardInteractivity::None,
             pointer_interactivity: true,
             ..Default::default()
-        });
+        }
+    }
 
-        (
-            Self {
-                id,
-                state,
-            },
-            surface
-        )
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        matches!(message, KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Debug(_)))
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+    fn subscription(&self) -> Subscription<KobelRootMessage> {
+        if self.is_visible() {
+            clock::subscription()
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
         match message {
             KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Debug(debug_message)) => {
                 match debug_message {
@@ -73,15 +97,11 @@ impl KobelDebug {
         Task::none()
     }
 
-    pub fn is_visible(&self) -> bool {
+    fn is_visible(&self) -> bool {
         *self.state.debug_panel_visible.read().unwrap()
     }
 
-    pub fn view(&self) -> Element<KobelRootMessage> {
-        if !self.is_visible() {
-            return row![].into();
-        }
-
+    fn view(&self) -> Element<KobelRootMessage> {
         let wm_info = format!(
             "KobelWM v{}",
             env!("CARGO_PKG_VERSION")
@@ -89,7 +109,7 @@ impl KobelDebug {
 
         let sha = env!("VERGEN_GIT_SHA").to_string()[0..7].to_string();
 
-        let debug_ui = column![
+        let mut debug_ui = column![
             k_text(&self.state, wm_info)
                 .bold(true)
                 .size(1.5),
@@ -107,21 +127,30 @@ impl KobelDebug {
             k_text(&self.state, format!("Now: {}", self.state.now.read().unwrap().format("%Y-%m-%d %H:%M:%S"))),
             horizontal_rule(2.0),
             k_text(&self.state, format!("Mouse Position: {:?}", self.state.pointer_position.read().unwrap())),
-            k_text(&self.state, format!("Screen Size: {:?}", self.state.screen_size.read().unwrap())),
-            horizontal_rule(2.0),
+        ];
+
+        for output in self.state.outputs.read().unwrap().iter() {
+            debug_ui = debug_ui.push(
+                k_text(&self.state, format!("Output: {} {}x{}", output.display_name(), output.size.width, output.size.height))
+            );
+        }
+
+        debug_ui = debug_ui.push(horizontal_rule(2.0)).push(
             checkbox("Enable debug borders", *self.state.debug_border_style.read().unwrap())
                 .on_toggle(|enabled| {
                     KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Debug(KobelDebugMessage::DebugBorderStyleToggled(enabled)))
                 })
-        ];
+        );
+
+        let palette = self.state.palette();
 
         container(container(debug_ui)
             .padding(6)
             .width(iced::Length::Fill)
             .height(iced::Length::Fill)
             .style(move |_| container::Style {
-                background: Some(self.state.shell_background.clone()),
-                text_color: Some(self.state.shell_text_color),
+                background: Some(Background::Color(palette.background)),
+                text_color: Some(palette.text),
                 border: iced::Border {
                     radius: 10.0.into(),
                     ..Default::default()
@@ -135,4 +164,4 @@ impl KobelDebug {
             .align_y(iced::Alignment::Center)
             .into()
     }
-}
\ No newline at end of file
+}
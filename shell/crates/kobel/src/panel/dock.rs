@@ -1,136 +1,1177 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{borrow::Cow, sync::Arc, time::{Duration, Instant}};
 
-use iced::{core::window, platform_specific::shell::commands::{layer_surface::get_layer_surface, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{column, container, row, svg, text, tooltip, vertical_rule}, Background, Color, Element, Task};
-use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, SctkLayerSurfaceSettings};
+use iced::{clipboard::mime::AllowedMimeTypes, core::window, platform_specific::shell::commands::{layer_surface::{set_exclusive_zone, set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{column, container, dnd_destination::DndDestination, horizontal_space, mouse_area, row, stack, svg, text, tooltip, vertical_rule, Space}, Background, Color, Element, Padding, Point, Rectangle, Size, Task};
+use iced_runtime::{dnd::peek_dnd, platform_specific::wayland::layer_surface::{IcedMargin, IcedOutput, SctkLayerSurfaceSettings}};
 
-use crate::{panel::dock, state::KobelShellState, util::debug::debug_border_style_or_default, widget::{k_button::{k_button, KobelShellButtonMode}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
+use crate::{animation::KobelSpring, apps::{launch, mime, KobelDesktopEntry}, config::KobelDockVisibility, output::KobelOutput, panel::{context_menu::KobelContextMenuItem, dock, KobelPanel, KobelPanelKind}, state::KobelShellState, trash, util::{debug::debug_border_style_or_default, notification}, wayfire::protocol::{KobelCloseView, KobelWayfireGeometry}, wayland::{foreign_toplevel::{KobelToplevel, KobelToplevelAction, KobelToplevelId}, wayfire_shell::KobelHotspotEvent}, widget::{k_button::{k_button, KobelShellButtonMode}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
 
 pub static DOCK_DEFAULT_HEIGHT: i32 = 84;
 pub static DOCK_DEFAULT_MARGIN: i32 = 8;
 pub static DOCK_DEFAULT_PADDING: f32 = 6.0;
 pub static DOCK_DEFAULT_RADII: f32 = 24.0;
+pub static DOCK_DEFAULT_PINNED: &[&str] = &[
+    "org.gnome.Nautilus.desktop",
+    "firefox.desktop",
+    "org.gnome.Console.desktop",
+    "org.gnome.SystemMonitor.desktop",
+    "org.gnome.Settings.desktop",
+];
+/// Running apps get one dot per window, up to this many.
+pub static DOCK_MAX_RUNNING_DOTS: usize = 3;
+pub static DOCK_RUNNING_DOT_SIZE: f32 = 5.0;
+pub static DOCK_BADGE_HEIGHT: f32 = 20.0;
+pub static DOCK_PROGRESS_HEIGHT: f32 = 4.0;
+/// How long a launching app takes to pulse once.
+pub static DOCK_LAUNCH_PULSE_PERIOD: Duration = Duration::from_millis(1200);
+/// Longer window titles are cut short in an app's context menu.
+pub static DOCK_MENU_MAX_TITLE_CHARS: usize = 32;
+pub static DOCK_TRASH_ICON: &str = "user-trash";
+pub static DOCK_TRASH_FULL_ICON: &str = "user-trash-full";
+/// How long a dock that hides waits after the pointer leaves it.
+pub static DOCK_HIDE_DELAY: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone)]
 pub enum KobelDockMessage {
+    /// The app with this desktop file ID, or app ID if it has no desktop entry,
+    /// was clicked in the dock on this surface. Focuses, minimizes or cycles
+    /// through its windows, or launches it if it has none.
+    Activate(window::Id, String),
+    /// The app with this ID is being dragged on this surface.
+    Drag(window::Id, String, button::DragEvent),
+    /// The pointer entered or left the dock on this surface.
+    Hover(window::Id, bool),
+    /// The dock on this surface may hide now, unless it was needed again.
+    HideTimeout(window::Id),
+    /// The pointer entered the running app with this ID on this surface, or
+    /// left it with `None`.
+    Preview(window::Id, Option<String>),
+    /// Launches the desktop action with this ID of an app, picked from its
+    /// context menu on this surface.
+    LaunchAction(window::Id, String, String),
+    /// Focuses a window picked from an app's context menu on this surface.
+    ActivateWindow(window::Id, KobelToplevelId),
+    /// Asks every window of the app with this ID to close.
+    Quit(window::Id, String),
+    /// Shows the app with this desktop file ID in a software center.
+    ShowDetails(window::Id, String),
+    /// Files are being dragged over the app with this ID on this surface,
+    /// or no longer are with `false`.
+    DropHover(window::Id, String, bool),
+    /// The URIs being dragged over this surface, read before they are dropped.
+    DropPeeked(window::Id, Option<Vec<String>>),
+    /// These URIs were dropped onto the app with this ID on this surface.
+    Drop(window::Id, String, Vec<String>),
+    /// Shows the trash in the file manager.
+    OpenTrash(window::Id),
+    /// Deletes everything in the trash, once confirmed.
+    EmptyTrash(window::Id),
+    /// Files are being dragged over the trash on this surface, or no longer
+    /// are with `false`.
+    TrashDropHover(window::Id, bool),
+    /// These URIs were dropped onto the trash on this surface.
+    TrashDrop(window::Id, Vec<String>),
+}
+
+/// Files and links being dragged, read from their `text/uri-list`.
+#[derive(Debug, Clone)]
+struct KobelDroppedUris(Vec<String>);
+
+impl AllowedMimeTypes for KobelDroppedUris {
+    fn allowed() -> Cow<'static, [String]> {
+        Cow::Owned(vec![mime::URI_LIST_MIME.to_string()])
+    }
+}
+
+impl TryFrom<(Vec<u8>, String)> for KobelDroppedUris {
+    type Error = ();
+
+    fn try_from((data, _): (Vec<u8>, String)) -> Result<Self, Self::Error> {
+        Ok(Self(mime::parse_uri_list(&data)))
+    }
+}
 
+impl Into<KobelRootMessage> for KobelDockMessage {
+    fn into(self) -> KobelRootMessage {
+        KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(self))
+    }
+}
+
+/// An app in the dock, either pinned or only there while it has windows.
+#[derive(Debug, Clone)]
+struct KobelDockItem {
+    /// The desktop file ID, or the app ID of a window with no desktop entry.
+    id: String,
+    entry: Option<KobelDesktopEntry>,
+    windows: Vec<KobelToplevel>,
+    pinned: bool,
+}
+
+/// An app being dragged to a new place in the dock, or off it.
+#[derive(Debug, Clone)]
+struct KobelDockDrag {
+    id: String,
+    /// Where the app is among the pinned apps, if it is pinned.
+    pinned_index: Option<usize>,
+    /// The pinned apps when the drag started. If they change in the meantime,
+    /// dropping does nothing rather than guessing.
+    pinned_before: Vec<String>,
+    /// Running apps can only be pinned if they have a desktop entry to launch.
+    pinnable: bool,
+    /// The pointer, relative to the dock surface.
+    position: Point,
 }
 
 #[derive(Debug)]
 pub struct KobelDock {
     pub id: window::Id,
+    pub output: KobelOutput,
     state: Arc<KobelShellState>,
+
+    drag: Option<KobelDockDrag>,
+    /// While dragging, the space opening before each pinned app other than
+    /// the dragged one, with the last after all of them.
+    gaps: Vec<KobelSpring<f32>>,
+
+    /// How far the dock has slid down out of its place, zero when shown.
+    offset: KobelSpring<f32>,
+    hovered: bool,
+    /// Whether the pointer rests on the bottom edge of the output.
+    at_edge: bool,
+    /// When the dock hides, if nothing needs it until then.
+    hide_at: Option<Instant>,
+    /// Whether this dock opened the window previews that are showing.
+    previewing: bool,
+    /// The app that files are being dragged over.
+    drop_target: Option<String>,
+    /// What is being dragged over the dock, once it has been read.
+    drop_uris: Option<Vec<String>>,
+    /// Whether files are being dragged over the trash.
+    trash_drop_target: bool,
 }
 
 impl KobelDock {
-    pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
+    pub fn new(state: Arc<KobelShellState>, output: KobelOutput) -> (Self, Task<KobelRootMessage>) {
         let id = window::Id::unique();
 
-        let dock_height = state.dock_height + (state.dock_margin * 2);
-
-        let surface = get_layer_surface(SctkLayerSurfaceSettings {
+        let mut dock = Self {
             id,
+            output,
+            state,
+
+            drag: None,
+            gaps: vec![],
+
+            offset: KobelSpring::new(0.0),
+            hovered: false,
+            at_edge: false,
+            hide_at: None,
+            previewing: false,
+            drop_target: None,
+            drop_uris: None,
+            trash_drop_target: false,
+        };
+        // A dock that hides shows itself briefly on startup
+        let task = dock.update_visibility();
+
+        (dock, task)
+    }
+
+    /// Pinned apps in their configured order, then running apps that are not
+    /// pinned in the order they opened in.
+    fn items(&self) -> Vec<KobelDockItem> {
+        let apps = self.state.apps();
+
+        let mut items: Vec<KobelDockItem> = self.state.config().dock.pinned.iter()
+            .map(|id| {
+                let entry = apps.get(id).cloned();
+
+                KobelDockItem {
+                    id: entry.as_ref().map_or_else(|| id.clone(), |entry| entry.id.clone()),
+                    entry,
+                    windows: vec![],
+                    pinned: true,
+                }
+            })
+            .collect();
+
+        // Windows without an app ID cannot be told apart from other apps' windows
+        for toplevel in self.state.toplevels.list().iter().filter(|t| !t.app_id.is_empty()) {
+            let entry = apps.find_by_app_id(&toplevel.app_id);
+            let id = entry.map_or(&toplevel.app_id, |entry| &entry.id);
+
+            match items.iter_mut().find(|item| item.id == *id) {
+                Some(item) => item.windows.push(toplevel.clone()),
+                None => items.push(KobelDockItem {
+                    id: id.clone(),
+                    entry: entry.cloned(),
+                    windows: vec![toplevel.clone()],
+                    pinned: false,
+                }),
+            }
+        }
+
+        items
+    }
+
+    // Iconic buttons pad their content by 8 on each side
+    fn icon_size(&self) -> f32 {
+        let dock = &self.state.config().dock;
+        dock.height as f32 - (dock.padding * 2.0) - 16.0
+    }
+
+    fn slot_width(&self) -> f32 {
+        self.icon_size() + 16.0
+    }
+
+    fn surface_bounds(&self) -> Rectangle {
+        let dock = &self.state.config().dock;
+        Rectangle::new(Point::ORIGIN, Size::new(self.output.size.width - (dock.margin * 2) as f32, dock.height as f32))
+    }
+
+    /// Where the first app starts on the surface, and the distance from one
+    /// app to the next, as the dock is laid out when nothing is dragged.
+    fn slot_geometry(&self, items: &[KobelDockItem]) -> (f32, f32) {
+        let dock = self.state.config().dock.clone();
+        let spacing = dock.padding * 1.5;
+        let slot = self.slot_width();
+
+        let mut width = items.len() as f32 * slot + items.len().saturating_sub(1) as f32 * spacing;
+        if is_separated(items) {
+            width += 1.0 + spacing;
+        }
+
+        // The trash comes last, after a separator of its own
+        if dock.show_trash {
+            width += slot;
+            if !items.is_empty() {
+                width += 1.0 + spacing * 2.0;
+            }
+        }
+
+        ((self.surface_bounds().width - width) / 2.0, slot + spacing)
+    }
+
+    /// The middle of the app at `index`, relative to the output.
+    fn item_center(&self, items: &[KobelDockItem], index: usize) -> f32 {
+        let dock = self.state.config().dock.clone();
+        let (left, step) = self.slot_geometry(items);
+
+        let mut center = dock.margin as f32 + left + index as f32 * step + self.slot_width() / 2.0;
+        if index >= items.iter().filter(|item| item.pinned).count() && is_separated(items) {
+            center += 1.0 + dock.padding * 1.5;
+        }
+
+        center
+    }
+
+    fn preview(&mut self, app_id: Option<String>) -> Task<KobelRootMessage> {
+        let Some(app_id) = app_id else {
+            return match self.previewing {
+                true => Task::done(KobelRootMessage::HideWindowPreviews),
+                false => Task::none(),
+            };
+        };
+
+        if self.drag.is_some() {
+            return Task::none();
+        }
+
+        let items = self.items();
+        let Some(index) = items.iter().position(|item| item.id == app_id && !item.windows.is_empty()) else {
+            return Task::none();
+        };
+
+        self.previewing = true;
+
+        Task::done(KobelRootMessage::ShowWindowPreviews {
+            output: self.output.clone(),
+            x: self.item_center(&items, index),
+            app_id,
+        })
+    }
+
+    /// Where among the other pinned apps the dragged app would be dropped,
+    /// or `None` if dropping it would unpin it or leave it where it is.
+    fn drop_index(&self) -> Option<usize> {
+        let drag = self.drag.as_ref()?;
+
+        if !self.surface_bounds().contains(drag.position) {
+            return None;
+        }
+
+        let items = self.items();
+        let (left, step) = self.slot_geometry(&items);
+        let pinned = items.iter().filter(|item| item.pinned).count();
+
+        // The gap opens before the first app whose middle is right of the pointer
+        let index = (0..pinned)
+            .filter(|index| Some(*index) != drag.pinned_index)
+            .filter(|index| left + *index as f32 * step + self.slot_width() / 2.0 < drag.position.x)
+            .count();
+
+        match drag.pinned_index {
+            Some(_) => Some(index),
+            // Running apps are pinned by dropping them among the pinned ones
+            None => (drag.pinnable && drag.position.x < left + pinned as f32 * step + self.slot_width() / 2.0)
+                .then_some(index),
+        }
+    }
+
+    fn drag(&mut self, id: String, event: button::DragEvent) -> Task<KobelRootMessage> {
+        match event {
+            button::DragEvent::Started(position) => {
+                let items = self.items();
+                let Some((index, item)) = items.iter().enumerate().find(|(_, item)| item.id == id) else {
+                    return Task::none();
+                };
+
+                let pinned_before = self.state.config().dock.pinned.clone();
+                let others = items.iter().filter(|item| item.pinned).count() - usize::from(item.pinned);
+
+                self.gaps = vec![KobelSpring::new(0.0); others + 1];
+                // The app leaves its place open, so nothing moves until the pointer does
+                if item.pinned {
+                    self.gaps[index].snap_to(self.slot_width());
+                }
+
+                self.drag = Some(KobelDockDrag {
+                    pinned_index: item.pinned.then_some(index),
+                    pinned_before,
+                    pinnable: item.entry.is_some(),
+                    position,
+                    id,
+                });
+                self.update_gaps();
+
+                match self.previewing {
+                    true => Task::done(KobelRootMessage::CloseWindowPreviews),
+                    false => Task::none(),
+                }
+            },
+            button::DragEvent::Moved(position) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.position = position;
+                }
+                self.update_gaps();
+
+                Task::none()
+            },
+            button::DragEvent::Ended(position) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.position = position;
+                }
+
+                self.drop_dragged()
+            },
+            button::DragEvent::Cancelled => {
+                self.drag = None;
+                self.gaps.clear();
+
+                Task::none()
+            },
+        }
+    }
+
+    fn update_gaps(&mut self) {
+        let clock = self.state.animation_clock();
+        let drop_index = self.drop_index();
+        let slot_width = self.slot_width();
+
+        for (index, gap) in self.gaps.iter_mut().enumerate() {
+            gap.set_target(if drop_index == Some(index) { slot_width } else { 0.0 }, &clock);
+        }
+    }
+
+    // Moves, pins or unpins the dragged app, saving the new order
+    fn drop_dragged(&mut self) -> Task<KobelRootMessage> {
+        let drop_index = self.drop_index();
+        self.gaps.clear();
+
+        let Some(drag) = self.drag.take() else {
+            return Task::none();
+        };
+
+        let mut pinned = self.state.config().dock.pinned.clone();
+        if pinned != drag.pinned_before {
+            return Task::none();
+        }
+
+        match (drag.pinned_index, drop_index) {
+            (Some(from), None) => {
+                pinned.remove(from);
+            },
+            (Some(from), Some(to)) => {
+                let id = pinned.remove(from);
+                pinned.insert(to, id);
+            },
+            (None, Some(to)) => pinned.insert(to, drag.id),
+            (None, None) => return Task::none(),
+        }
+
+        Task::done(KobelRootMessage::SetPinnedApps(pinned))
+    }
+
+    /// The part of the output the dock covers when shown, for telling whether
+    /// windows overlap it.
+    fn area(&self) -> KobelWayfireGeometry {
+        let dock = self.state.config().dock.clone();
+        let (left, _) = self.slot_geometry(&self.items());
+        let inset = (left - dock.padding).max(0.0);
+
+        KobelWayfireGeometry {
+            x: (dock.margin as f32 + inset) as i32,
+            y: self.output.size.height as i32 - dock.height - dock.margin,
+            width: (self.surface_bounds().width - inset * 2.0) as i32,
+            height: dock.height,
+        }
+    }
+
+    fn wants_visible(&self) -> bool {
+        let visibility = self.state.config().dock.visibility;
+
+        match visibility {
+            KobelDockVisibility::Always => true,
+            _ if self.hovered || self.at_edge || self.drag.is_some() || self.previewing || self.drop_target.is_some() || self.trash_drop_target => true,
+            KobelDockVisibility::Autohide => false,
+            KobelDockVisibility::Intellihide => !self.output.name.as_ref()
+                .is_some_and(|name| self.state.wayfire_views().any_overlapping(name, &self.area())),
+        }
+    }
+
+    // Far enough down to be off the output entirely
+    fn hidden_offset(&self) -> f32 {
+        let dock = &self.state.config().dock;
+        (dock.height + dock.margin) as f32
+    }
+
+    /// Shows the dock right away if something needs it, or hides it once
+    /// nothing has needed it for a moment.
+    fn update_visibility(&mut self) -> Task<KobelRootMessage> {
+        if self.wants_visible() {
+            self.hide_at = None;
+            return self.slide_to(0.0);
+        }
+
+        if self.offset.target() != 0.0 {
+            return self.slide_to(self.hidden_offset());
+        }
+        if self.hide_at.is_some() {
+            return Task::none();
+        }
+
+        self.hide_at = Some(Instant::now() + DOCK_HIDE_DELAY);
+
+        let id = self.id;
+        Task::future(tokio::time::sleep(DOCK_HIDE_DELAY))
+            .map(move |_| KobelDockMessage::HideTimeout(id).into())
+    }
+
+    fn hide_timeout(&mut self) -> Task<KobelRootMessage> {
+        // Timers from before the dock was last needed are stale
+        if !self.hide_at.is_some_and(|hide_at| hide_at <= Instant::now()) {
+            return Task::none();
+        }
+        self.hide_at = None;
+
+        if self.wants_visible() {
+            return Task::none();
+        }
+
+        self.slide_to(self.hidden_offset())
+    }
+
+    fn slide_to(&mut self, offset: f32) -> Task<KobelRootMessage> {
+        if self.offset.target() == offset {
+            return Task::none();
+        }
+
+        self.offset.set_target(offset, &self.state.animation_clock());
+        self.move_surface()
+    }
+
+    fn activate(&self, id: &str) -> Task<KobelRootMessage> {
+        let windows = self.items().into_iter()
+            .find(|item| item.id == id)
+            .map(|item| item.windows)
+            .unwrap_or_default();
+
+        match KobelToplevelAction::for_windows(&windows) {
+            // Clicking again while the app is starting would start it twice
+            KobelToplevelAction::Launch if self.state.launches().get(id).is_some() => {},
+            KobelToplevelAction::Launch => return self.state.launch_app(id, None, vec![], self.id),
+            KobelToplevelAction::Activate(toplevel) => self.state.toplevels.activate(toplevel),
+            KobelToplevelAction::Minimize(toplevel) => self.state.toplevels.set_minimized(toplevel, true),
+        }
+
+        Task::none()
+    }
+
+    fn item_view(&self, item: &KobelDockItem) -> Element<KobelRootMessage> {
+        let dock = self.state.config().dock.clone();
+        let palette = self.state.palette();
+        let (icon, name) = item_icon(item);
+
+        let icon_element: Element<KobelRootMessage> = k_icon(&self.state, icon)
+            .size(iced::Length::Fixed(self.icon_size()))
+            .scale(self.output.scale_factor as u32)
+            .symbolic(false)
+            .into();
+
+        let (id, app_id) = (self.id, item.id.clone());
+        let button_ui = k_button(&self.state, icon_element)
+            .radii(dock.radii - dock.padding)
+            .mode(KobelShellButtonMode::Iconic)
+            .on_press(KobelDockMessage::Activate(self.id, item.id.clone()).into())
+            .on_drag(move |event| KobelDockMessage::Drag(id, app_id.clone(), event).into());
+
+        // Drawn over the bottom padding of the button, so running apps do not shift
+        let focused = item.windows.iter().any(|window| window.activated);
+        let dots_ui = row(
+            (0..item.windows.len().min(DOCK_MAX_RUNNING_DOTS)).map(|_| {
+                container(row![])
+                    .width(DOCK_RUNNING_DOT_SIZE)
+                    .height(DOCK_RUNNING_DOT_SIZE)
+                    .style(move |_| container::Style {
+                        background: Some(Background::Color(if focused { palette.accent } else { palette.text })),
+                        border: iced::Border {
+                            radius: (DOCK_RUNNING_DOT_SIZE / 2.0).into(),
+                            ..Default::default()
+                        },
+                        ..container::Style::default()
+                    })
+                    .into()
+            })
+        )
+            .spacing(DOCK_RUNNING_DOT_SIZE / 2.0);
+
+        let launcher_entry = self.state.launcher_entries().get(&item.id).cloned().unwrap_or_default();
+        let radii = dock.radii - dock.padding;
+
+        // Apps light up under files they can open, and pulse while starting
+        // until their first window shows
+        let is_drop_target = self.drop_target.as_ref() == Some(&item.id) && self.accepts_drop(item);
+        let highlight = match self.state.launches().get(&item.id) {
+            _ if is_drop_target => Some(0.35),
+            Some(launch) => Some(0.1 + 0.25 * self.launch_pulse(launch.started_at)),
+            None => None,
+        };
+
+        let highlight_ui: Element<KobelRootMessage> = match highlight {
+            Some(alpha) => {
+                container(Space::new(iced::Length::Fill, iced::Length::Fill))
+                    .style(move |_| container::Style {
+                        background: Some(Background::Color(palette.accent.scale_alpha(alpha))),
+                        border: iced::Border {
+                            radius: radii.into(),
+                            ..Default::default()
+                        },
+                        ..container::Style::default()
+                    })
+                    .into()
+            },
+            None => Space::new(0.0, 0.0).into(),
+        };
+
+        // Urgent apps are ringed until they are dealt with
+        let urgency_ui: Element<KobelRootMessage> = if launcher_entry.urgent() {
+            container(Space::new(iced::Length::Fill, iced::Length::Fill))
+                .style(move |_| container::Style {
+                    border: iced::Border {
+                        width: 2.0,
+                        color: palette.accent,
+                        radius: radii.into(),
+                    },
+                    ..container::Style::default()
+                })
+                .into()
+        } else {
+            Space::new(0.0, 0.0).into()
+        };
+
+        // Across the bottom of the icon, above the running dots
+        let progress_ui: Element<KobelRootMessage> = match launcher_entry.progress() {
+            Some(progress) => container(
+                container(Space::new(self.icon_size() * progress as f32, DOCK_PROGRESS_HEIGHT))
+                    .style(move |_| container::Style {
+                        background: Some(Background::Color(palette.accent)),
+                        ..container::Style::default()
+                    })
+            )
+                .width(self.icon_size())
+                .height(DOCK_PROGRESS_HEIGHT)
+                .style(move |_| container::Style {
+                    background: Some(Background::Color(palette.text.scale_alpha(0.25))),
+                    border: iced::Border {
+                        radius: (DOCK_PROGRESS_HEIGHT / 2.0).into(),
+                        ..Default::default()
+                    },
+                    ..container::Style::default()
+                })
+                .clip(true)
+                .into(),
+            None => Space::new(0.0, 0.0).into(),
+        };
+
+        let badge_ui: Element<KobelRootMessage> = match launcher_entry.count() {
+            Some(count) => container(k_text(&self.state, badge_label(count)).size(0.75).bold(true))
+                .height(DOCK_BADGE_HEIGHT)
+                .padding(Padding::from([0, 6]))
+                .align_y(iced::Alignment::Center)
+                .style(move |_| container::Style {
+                    background: Some(Background::Color(palette.accent)),
+                    text_color: Some(palette.accent_text),
+                    border: iced::Border {
+                        radius: (DOCK_BADGE_HEIGHT / 2.0).into(),
+                        ..Default::default()
+                    },
+                    ..container::Style::default()
+                })
+                .into(),
+            None => Space::new(0.0, 0.0).into(),
+        };
+
+        let item_ui = stack![
+            button_ui,
+            highlight_ui,
+            urgency_ui,
+            container(progress_ui)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .align_x(iced::Alignment::Center)
+                .align_y(iced::Alignment::End)
+                .padding(Padding { bottom: 8.0 + DOCK_PROGRESS_HEIGHT / 2.0, ..Padding::ZERO }),
+            container(dots_ui)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .align_x(iced::Alignment::Center)
+                .align_y(iced::Alignment::End)
+                .padding(1.0),
+            container(badge_ui)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .align_x(iced::Alignment::End)
+                .align_y(iced::Alignment::Start),
+        ];
+
+        let item_ui = mouse_area(item_ui)
+            .on_right_press(self.context_menu(item));
+
+        // Running apps show their windows instead, titles included
+        let item_ui: Element<KobelRootMessage> = if item.windows.is_empty() {
+            tooltip(item_ui, k_text(&self.state, name), tooltip::Position::FollowCursor).into()
+        } else {
+            item_ui
+                .on_enter(KobelDockMessage::Preview(self.id, Some(item.id.clone())).into())
+                .on_exit(KobelDockMessage::Preview(self.id, None).into())
+                .into()
+        };
+
+        let (id, enter_id, leave_id, drop_id) = (self.id, item.id.clone(), item.id.clone(), item.id.clone());
+        DndDestination::new(item_ui, vec![Cow::Borrowed(mime::URI_LIST_MIME)])
+            .on_enter(move |_, _, _| KobelDockMessage::DropHover(id, enter_id.clone(), true).into())
+            .on_leave(move || KobelDockMessage::DropHover(id, leave_id.clone(), false).into())
+            .on_data_received(move |_, data| KobelDockMessage::Drop(id, drop_id.clone(), mime::parse_uri_list(&data)).into())
+            .into()
+    }
+
+    /// Whether the app opens every file being dragged over the dock, as far
+    /// as they have been read.
+    fn accepts_drop(&self, item: &KobelDockItem) -> bool {
+        let (Some(entry), Some(uris)) = (&item.entry, &self.drop_uris) else {
+            return false;
+        };
+
+        launch::takes_files(entry) && self.state.mime_types().accepts(&entry.mime_types, uris)
+    }
+
+    fn drop_uris(&mut self, app_id: String, uris: Vec<String>) -> Task<KobelRootMessage> {
+        self.drop_target = None;
+        self.drop_uris = None;
+
+        let Some(entry) = self.state.apps().get(&app_id).cloned() else {
+            return self.update_visibility();
+        };
+
+        if !launch::takes_files(&entry) || !self.state.mime_types().accepts(&entry.mime_types, &uris) {
+            log::info!("'{}' does not open the dropped files", entry.id);
+            return self.update_visibility();
+        }
+
+        Task::batch(vec![
+            self.state.launch_app(&entry.id, None, uris, self.id),
+            self.update_visibility(),
+        ])
+    }
+
+    /// How strongly a launching app is lit, from 0 to 1. It holds still
+    /// when motion is reduced.
+    fn launch_pulse(&self, started_at: Instant) -> f32 {
+        let clock = self.state.animation_clock();
+        if clock.reduce_motion() {
+            return 0.5;
+        }
+
+        let cycles = clock.now().saturating_duration_since(started_at).as_secs_f32() / DOCK_LAUNCH_PULSE_PERIOD.as_secs_f32();
+        0.5 - 0.5 * (cycles * std::f32::consts::TAU).cos()
+    }
+
+    fn context_menu(&self, item: &KobelDockItem) -> KobelRootMessage {
+        let dock = self.state.config().dock.clone();
+        let mut items = vec![];
+
+        // Such as "New Private Window"
+        if let Some(entry) = &item.entry && !entry.actions.is_empty() {
+            items.extend(entry.actions.iter().map(|action| KobelContextMenuItem::action(
+                action.name.clone(),
+                KobelDockMessage::LaunchAction(self.id, item.id.clone(), action.id.clone()).into(),
+            )));
+            items.push(KobelContextMenuItem::Separator);
+        }
+
+        if !item.windows.is_empty() {
+            items.extend(item.windows.iter().map(|window| {
+                let title = if window.title.is_empty() { item_icon(item).1 } else { window.title.clone() };
+                KobelContextMenuItem::action(
+                    truncate_title(&title),
+                    KobelDockMessage::ActivateWindow(self.id, window.id).into(),
+                )
+            }));
+            items.push(KobelContextMenuItem::Separator);
+        }
+
+        // Apps without a desktop entry cannot be launched, so only be unpinned
+        let is_pinned = dock.pinned.iter().any(|id| *id == item.id);
+        if is_pinned || item.entry.is_some() {
+            items.push(KobelContextMenuItem::action(
+                if is_pinned { "Unpin from Dock" } else { "Pin to Dock" },
+                KobelRootMessage::SetAppPinned(item.id.clone(), !is_pinned),
+            ));
+        }
+
+        if item.entry.is_some() {
+            items.push(match launch::details_command(&item.id) {
+                Some(_) => KobelContextMenuItem::action("Show Details", KobelDockMessage::ShowDetails(self.id, item.id.clone()).into()),
+                None => KobelContextMenuItem::disabled("Show Details"),
+            });
+        }
+
+        if !item.windows.is_empty() {
+            items.push(KobelContextMenuItem::Separator);
+            items.push(KobelContextMenuItem::action(
+                "Quit All Windows",
+                KobelDockMessage::Quit(self.id, item.id.clone()).into(),
+            ));
+        }
+
+        KobelRootMessage::OpenContextMenu {
+            output: self.output.clone(),
+            width: 240.0,
+            items,
+            surface_offset: self.surface_offset(),
+        }
+    }
+
+    // The dock is anchored to the bottom of its output, inset by its margin
+    fn surface_offset(&self) -> iced::Vector {
+        let dock = &self.state.config().dock;
+        iced::Vector::new(dock.margin as f32, self.output.size.height - (dock.height + dock.margin) as f32)
+    }
+
+    fn trash_view(&self) -> Element<KobelRootMessage> {
+        let dock = self.state.config().dock.clone();
+        let palette = self.state.palette();
+        let radii = dock.radii - dock.padding;
+
+        let icon = if self.state.trash_full() { DOCK_TRASH_FULL_ICON } else { DOCK_TRASH_ICON };
+        let icon_element: Element<KobelRootMessage> = k_icon(&self.state, icon)
+            .size(iced::Length::Fixed(self.icon_size()))
+            .scale(self.output.scale_factor as u32)
+            .symbolic(false)
+            .into();
+
+        let button_ui = k_button(&self.state, icon_element)
+            .radii(radii)
+            .mode(KobelShellButtonMode::Iconic)
+            .on_press(KobelDockMessage::OpenTrash(self.id).into());
+
+        let highlight_ui: Element<KobelRootMessage> = if self.trash_drop_target {
+            container(Space::new(iced::Length::Fill, iced::Length::Fill))
+                .style(move |_| container::Style {
+                    background: Some(Background::Color(palette.accent.scale_alpha(0.35))),
+                    border: iced::Border {
+                        radius: radii.into(),
+                        ..Default::default()
+                    },
+                    ..container::Style::default()
+                })
+                .into()
+        } else {
+            Space::new(0.0, 0.0).into()
+        };
+
+        let trash_ui = mouse_area(stack![button_ui, highlight_ui])
+            .on_right_press(self.trash_context_menu());
+        let trash_ui = tooltip(trash_ui, k_text(&self.state, "Trash"), tooltip::Position::FollowCursor);
+
+        let id = self.id;
+        DndDestination::new(trash_ui, vec![Cow::Borrowed(mime::URI_LIST_MIME)])
+            .on_enter(move |_, _, _| KobelDockMessage::TrashDropHover(id, true).into())
+            .on_leave(move || KobelDockMessage::TrashDropHover(id, false).into())
+            .on_data_received(move |_, data| KobelDockMessage::TrashDrop(id, mime::parse_uri_list(&data)).into())
+            .into()
+    }
+
+    fn trash_context_menu(&self) -> KobelRootMessage {
+        let empty_item = match self.state.trash_full() {
+            true => KobelContextMenuItem::confirm(
+                "Empty Trash",
+                "Delete all items permanently?",
+                KobelDockMessage::EmptyTrash(self.id).into(),
+            ),
+            false => KobelContextMenuItem::disabled("Empty Trash"),
+        };
+
+        KobelRootMessage::OpenContextMenu {
+            output: self.output.clone(),
+            width: 240.0,
+            items: vec![
+                KobelContextMenuItem::action("Open", KobelDockMessage::OpenTrash(self.id).into()),
+                KobelContextMenuItem::Separator,
+                empty_item,
+            ],
+            surface_offset: self.surface_offset(),
+        }
+    }
+
+    /// Closes every window of the app through the compositor, which lets the
+    /// app ask about unsaved changes first.
+    fn quit(&self, app_id: &str) -> Task<KobelRootMessage> {
+        let views = self.state.app_views(app_id);
+        if views.is_empty() {
+            log::warn!("Not quitting '{}' as the compositor knows none of its windows", app_id);
+            return Task::none();
+        }
+
+        Task::batch(views.into_iter().map(|view| {
+            let wayfire = self.state.wayfire.clone();
+
+            Task::future(async move {
+                if let Err(e) = wayfire.call(&KobelCloseView { id: view.id }).await {
+                    log::warn!("Failed to close view {}: {:#}", view.id, e);
+                }
+            })
+            .discard()
+        }))
+    }
+
+    /// Space kept clear of windows. A dock that hides keeps none, so windows
+    /// can use the whole output while it is out of the way.
+    fn exclusive_zone(&self) -> i32 {
+        let dock = &self.state.config().dock;
+
+        match dock.visibility {
+            KobelDockVisibility::Always => dock.height + (dock.margin * 2),
+            KobelDockVisibility::Autohide | KobelDockVisibility::Intellihide => 0,
+        }
+    }
+
+    // The dock slides by moving its surface down past the edge of the output
+    fn bottom_margin(&self) -> i32 {
+        self.state.config().dock.margin - self.offset.value().round() as i32
+    }
+
+    fn move_surface(&self) -> Task<KobelRootMessage> {
+        let margin = self.state.config().dock.margin;
+        set_margin(self.id, margin, margin, self.bottom_margin(), margin)
+    }
+
+    fn resize_surface(&self) -> Task<KobelRootMessage> {
+        let dock = self.state.config().dock.clone();
+
+        Task::batch(vec![
+            set_size(self.id, None, Some(dock.height as u32)),
+            set_exclusive_zone(self.id, self.exclusive_zone()),
+            self.move_surface(),
+        ])
+    }
+}
+
+impl KobelPanel for KobelDock {
+    fn id(&self) -> window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::Dock
+    }
+
+    fn output(&self) -> Option<&KobelOutput> {
+        Some(&self.output)
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        let dock = self.state.config().dock.clone();
+
+        SctkLayerSurfaceSettings {
+            id: self.id,
             namespace: "kobelwm".to_string(),
+            output: IcedOutput::Output(self.output.wl_output.clone()),
             layer: Layer::Overlay,
             anchor: Anchor::BOTTOM | Anchor::LEFT | Anchor::RIGHT,
-            size: Some((None, Some(state.dock_height as u32))),
-            exclusive_zone: dock_height,
+            size: Some((None, Some(dock.height as u32))),
+            exclusive_zone: self.exclusive_zone(),
             margin: IcedMargin {
-                top: state.dock_margin,
-                bottom: state.dock_margin,
-                left: state.dock_margin,
-                right: state.dock_margin,
+                top: dock.margin,
+                bottom: self.bottom_margin(),
+                left: dock.margin,
+                right: dock.margin,
             },
             keyboard_interactivity: KeyboardInteractivity::OnDemand,
             pointer_interactivity: true,
             ..Default::default()
-        });
+        }
+    }
 
-        (
-            Self {
-                id,
-                state,
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        match message {
+            KobelRootMessage::ConfigReloaded(_)
+            | KobelRootMessage::SetAppPinned(..)
+            | KobelRootMessage::SetPinnedApps(_) => true,
+            KobelRootMessage::OutputUpdated(output) => output.wl_output == self.output.wl_output,
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(
+                KobelDockMessage::Activate(id, _)
+                | KobelDockMessage::Drag(id, _, _)
+                | KobelDockMessage::Hover(id, _)
+                | KobelDockMessage::HideTimeout(id)
+                | KobelDockMessage::Preview(id, _)
+                | KobelDockMessage::LaunchAction(id, _, _)
+                | KobelDockMessage::ActivateWindow(id, _)
+                | KobelDockMessage::Quit(id, _)
+                | KobelDockMessage::ShowDetails(id, _)
+                | KobelDockMessage::DropHover(id, _, _)
+                | KobelDockMessage::DropPeeked(id, _)
+                | KobelDockMessage::Drop(id, _, _)
+                | KobelDockMessage::OpenTrash(id)
+                | KobelDockMessage::EmptyTrash(id)
+                | KobelDockMessage::TrashDropHover(id, _)
+                | KobelDockMessage::TrashDrop(id, _)
+            )) => *id == self.id,
+            KobelRootMessage::CloseWindowPreviews | KobelRootMessage::OpenContextMenu { .. } => self.previewing,
+            // Previews from the dock on another output replace this dock's own
+            KobelRootMessage::ShowWindowPreviews { output, .. } => self.previewing && output.wl_output != self.output.wl_output,
+            KobelRootMessage::Hotspot(KobelHotspotEvent::Entered(name) | KobelHotspotEvent::Left(name)) => {
+                self.output.name.as_ref() == Some(name)
             },
-            surface
-        )
+            // Windows moving only matter to a dock that gets out of their way
+            KobelRootMessage::WayfireEvent(_)
+            | KobelRootMessage::WayfireViewsListed(_)
+            | KobelRootMessage::WayfireDisconnected => self.state.config().dock.visibility == KobelDockVisibility::Intellihide,
+            KobelRootMessage::AnimationFrame(_) => self.is_animating(),
+            _ => false,
+        }
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
-        Task::none()
-    }
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+        match message {
+            KobelRootMessage::ConfigReloaded(_) => {
+                let visibility_task = self.update_visibility();
+                Task::batch(vec![self.resize_surface(), visibility_task])
+            },
+            // The state has already applied the new pinned apps
+            KobelRootMessage::SetAppPinned(..) | KobelRootMessage::SetPinnedApps(_) => self.resize_surface(),
+            KobelRootMessage::OutputUpdated(output) if output.wl_output == self.output.wl_output => {
+                self.output = output;
+                self.update_visibility()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Activate(_, app_id))) => {
+                self.activate(&app_id)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Drag(_, app_id, event))) => {
+                let drag_task = self.drag(app_id, event);
+                Task::batch(vec![drag_task, self.update_visibility()])
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Hover(_, hovered))) => {
+                self.hovered = hovered;
+                self.update_visibility()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::HideTimeout(_))) => {
+                self.hide_timeout()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Preview(_, app_id))) => {
+                self.preview(app_id)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::LaunchAction(_, app_id, action))) => {
+                self.state.launch_app(&app_id, Some(action), vec![], self.id)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::ActivateWindow(_, toplevel))) => {
+                self.state.toplevels.activate(toplevel);
+                Task::none()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Quit(_, app_id))) => {
+                self.quit(&app_id)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::ShowDetails(_, app_id))) => {
+                Task::future(async move {
+                    if let Err(e) = launch::show_details(&app_id) {
+                        log::error!("Failed to show details of '{}': {:#}", app_id, e);
+                    }
+                })
+                .discard()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::DropHover(_, app_id, hovered))) => {
+                // Leaving one app and entering the next may arrive in either order
+                if hovered {
+                    self.drop_target = Some(app_id);
+                } else if self.drop_target.as_ref() == Some(&app_id) {
+                    self.drop_target = None;
+                    self.drop_uris = None;
+                }
 
-    pub fn view(&self) -> Element<KobelRootMessage> {
-        let icon_size = (self.state.dock_height as f32 - (self.state.dock_padding * 2.0)) / self.state.icon_base_size;
-        let button_radii = self.state.dock_radii - self.state.dock_padding;
+                // Read on entering an app rather than once, as it may be another drag
+                let id = self.id;
+                let peek_task = match hovered {
+                    true => peek_dnd::<KobelDroppedUris>().map(move |uris| KobelDockMessage::DropPeeked(id, uris.map(|uris| uris.0)).into()),
+                    false => Task::none(),
+                };
 
-        let icons = vec![
-            "/usr/share/icons/hicolor/scalable/apps/org.gnome.Nautilus.svg",
-            "/usr/share/icons/hicolor/128x128/apps/firefox-nightly.png",
-            "/home/kieran/Downloads/discord.png",
-            "/usr/share/icons/hicolor/128x128/apps/spotify-client.png",
-            "/usr/share/pixmaps/vscode.png",
-            "/usr/share/icons/hicolor/scalable/apps/org.gnome.Console.svg",
-            "/usr/share/icons/hicolor/scalable/apps/org.gnome.SystemMonitor.svg",
-            "/usr/share/icons/hicolor/scalable/apps/org.gnome.Settings.svg",
-        ]
-            .into_iter()
-            .map(|icon_path| PathBuf::from(icon_path))
-            .collect::<Vec<_>>();
-
-        let mut icons_ui = vec![];
-
-        for icon in icons {
-            let icon_element: Element<KobelRootMessage> = k_icon(&self.state, icon.to_string_lossy().to_string())
-                .size(iced::Length::Shrink)
-                .symbolic(false)
-                .into();
-
-            let tooltip_text = icon.file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
-
-            icons_ui.push(
-                tooltip(
-                    k_button(&self.state, icon_element)
-                        .radii(button_radii)
-                        .mode(KobelShellButtonMode::Iconic),
-                    k_text(&self.state, tooltip_text),
-                    tooltip::Position::FollowCursor
-                )
-            );
+                Task::batch(vec![peek_task, self.update_visibility()])
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::DropPeeked(_, uris))) => {
+                // The drag may have been dropped or left in the meantime
+                if self.drop_target.is_some() {
+                    self.drop_uris = uris;
+                }
+                Task::none()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::Drop(_, app_id, uris))) => {
+                self.drop_uris(app_id, uris)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::OpenTrash(_))) => {
+                Task::future(async {
+                    if let Err(e) = trash::open() {
+                        log::error!("Failed to open the trash: {:#}", e);
+                    }
+                })
+                .discard()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::EmptyTrash(_))) => {
+                change_trash("Failed to empty the trash", trash::empty)
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::TrashDropHover(_, hovered))) => {
+                self.trash_drop_target = hovered;
+                self.update_visibility()
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Dock(KobelDockMessage::TrashDrop(_, uris))) => {
+                self.trash_drop_target = false;
+                Task::batch(vec![
+                    change_trash("Failed to move files to the trash", move || trash::trash_uris(&uris)),
+                    self.update_visibility(),
+                ])
+            },
+            // The previews would cover an app's context menu
+            KobelRootMessage::OpenContextMenu { .. } => Task::done(KobelRootMessage::CloseWindowPreviews),
+            KobelRootMessage::CloseWindowPreviews | KobelRootMessage::ShowWindowPreviews { .. } => {
+                self.previewing = false;
+                self.update_visibility()
+            },
+            KobelRootMessage::Hotspot(event) => {
+                self.at_edge = matches!(event, KobelHotspotEvent::Entered(_));
+                self.update_visibility()
+            },
+            KobelRootMessage::WayfireEvent(_) | KobelRootMessage::WayfireViewsListed(_) | KobelRootMessage::WayfireDisconnected => {
+                self.update_visibility()
+            },
+            KobelRootMessage::AnimationFrame(_) => {
+                let clock = self.state.animation_clock();
+                for gap in &mut self.gaps {
+                    gap.tick(&clock);
+                }
+
+                // The last frame moves the surface too, to where it comes to rest
+                if self.offset.is_animating() {
+                    self.offset.tick(&clock);
+                    return self.move_surface();
+                }
+
+                Task::none()
+            },
+            _ => Task::none(),
         }
+    }
+
+    fn is_animating(&self) -> bool {
+        let pulsing = !self.state.launches().is_empty() && !self.state.animation_clock().reduce_motion();
+        self.gaps.iter().any(KobelSpring::is_animating) || self.offset.is_animating() || pulsing
+    }
+
+    fn view(&self) -> Element<KobelRootMessage> {
+        let dock = self.state.config().dock.clone();
+        let palette = self.state.palette();
+
+        let items = self.items();
+        let pinned = items.iter().filter(|item| item.pinned).count();
+        let gap = |index: usize| self.gaps.get(index).map_or(0.0, |gap| gap.value());
 
         let mut dock_ui = row![
             
         ]
-            .spacing(self.state.dock_padding * 1.5)
+            .spacing(dock.padding * 1.5)
             .width(iced::Length::Shrink)
             .height(iced::Length::Shrink);
 
-        for icon_ui in icons_ui {
-            dock_ui = dock_ui.push(icon_ui);
-        }
+        // The dock keeps the same widgets while dragging, with the dragged app
+        // squeezed to nothing, so the button following the pointer is not lost
+        let mut other_index = 0;
+        for (index, item) in items.iter().enumerate() {
+            let is_dragged = self.drag.as_ref().is_some_and(|drag| match drag.pinned_index {
+                Some(pinned_index) => pinned_index == index,
+                None => !item.pinned && drag.id == item.id,
+            });
+
+            let mut leading_gap = 0.0;
+            if item.pinned && !is_dragged {
+                leading_gap = gap(other_index);
+                other_index += 1;
+            }
+
+            // The gap after every pinned app goes before the first app if none are pinned
+            let trailing_gap = if pinned > 0 && index + 1 == pinned { gap(self.gaps.len().saturating_sub(1)) } else { 0.0 };
+            if pinned == 0 && index == 0 {
+                leading_gap = gap(0);
+            }
 
-        // dock_ui = dock_ui.push(vertical_rule(1.0));
+            // Running apps that are not pinned come after a separator
+            if index == pinned && is_separated(&items) {
+                dock_ui = dock_ui.push(vertical_rule(1.0));
+            }
 
-        // dock_ui = dock_ui.push(
-        //     row![
-        //         k_button(&self.state, k_text(&self.state, "Kieran")),
-        //         k_button(&self.state, k_text(&self.state, "Bingus"))
-        //     ],
-        // );
+            dock_ui = dock_ui.push(row![
+                horizontal_space().width(leading_gap),
+                container(self.item_view(item))
+                    .width(if is_dragged { iced::Length::Fixed(0.0) } else { iced::Length::Shrink })
+                    .clip(true),
+                horizontal_space().width(trailing_gap),
+            ]);
+        }
+
+        if dock.show_trash {
+            if !items.is_empty() {
+                dock_ui = dock_ui.push(vertical_rule(1.0));
+            }
+            dock_ui = dock_ui.push(self.trash_view());
+        }
 
-        container(container(row![dock_ui])
+        let dock_ui = container(container(row![dock_ui])
             .width(iced::Length::Shrink)
             .height(iced::Length::Fill)
             .align_x(iced::Alignment::Center)
             .align_y(iced::Alignment::Center)
-            .padding(self.state.dock_padding)
+            .padding(dock.padding)
             .style(move |_| container::Style {
-                background: Some(self.state.shell_background.clone()),
-                text_color: Some(self.state.shell_text_color),
+                background: Some(Background::Color(palette.background)),
+                text_color: Some(palette.text),
                 border: debug_border_style_or_default(&self.state, iced::Border {
-                    radius: self.state.dock_radii.into(),
+                    radius: dock.radii.into(),
                     ..Default::default()
                 }),
                 ..container::Style::default()
@@ -139,7 +1180,83 @@ impl KobelDock {
             .width(iced::Length::Fill)
             .height(iced::Length::Fill)
             .align_x(iced::Alignment::Center)
-            .align_y(iced::Alignment::Center)
+            .align_y(iced::Alignment::Center);
+
+        // The dragged app follows the pointer along the dock
+        let dragged_ui: Element<KobelRootMessage> = match self.drag.as_ref() {
+            Some(drag) if self.surface_bounds().contains(drag.position) => {
+                let item = items.iter().find(|item| item.id == drag.id);
+                let icon = item.map_or_else(|| drag.id.clone(), |item| item_icon(item).0);
+
+                container(
+                    k_icon(&self.state, icon)
+                        .size(iced::Length::Fixed(self.icon_size()))
+                        .scale(self.output.scale_factor as u32)
+                        .symbolic(false)
+                )
+                    .padding(Padding {
+                        left: (drag.position.x - self.slot_width() / 2.0).max(0.0) + 8.0,
+                        top: dock.padding + 8.0,
+                        ..Padding::ZERO
+                    })
+                    .into()
+            },
+            _ => Space::new(0.0, 0.0).into(),
+        };
+
+        mouse_area(stack![dock_ui, dragged_ui])
+            .on_enter(KobelDockMessage::Hover(self.id, true).into())
+            .on_exit(KobelDockMessage::Hover(self.id, false).into())
             .into()
     }
-}
\ No newline at end of file
+}
+
+/// The icon name and display name of an item. Apps that are not installed
+/// are drawn from their ID.
+fn item_icon(item: &KobelDockItem) -> (String, String) {
+    let (icon, name) = match &item.entry {
+        Some(entry) => (entry.icon.clone(), entry.name.clone()),
+        None => (None, item.id.clone()),
+    };
+
+    (icon.unwrap_or_else(|| item.id.trim_end_matches(".desktop").to_string()), name)
+}
+
+fn truncate_title(title: &str) -> String {
+    match title.char_indices().nth(DOCK_MENU_MAX_TITLE_CHARS) {
+        Some((end, _)) => format!("{}…", title[..end].trim_end()),
+        None => title.to_string(),
+    }
+}
+
+/// Runs `work` on the trash away from the UI, telling the user if it fails.
+/// Trash directories on other mounts are not watched, so the trash is looked
+/// at again either way.
+fn change_trash(failure: &'static str, work: impl FnOnce() -> anyhow::Result<()> + Send + 'static) -> Task<KobelRootMessage> {
+    Task::future(async move {
+        let result = match tokio::task::spawn_blocking(work).await {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            log::error!("{}: {:#}", failure, e);
+            notification::notify(failure, &format!("{:#}", e), Some(DOCK_TRASH_FULL_ICON)).await;
+        }
+
+        KobelRootMessage::TrashChanged
+    })
+}
+
+// Long counts would cover the whole icon
+fn badge_label(count: i64) -> String {
+    match count {
+        ..1000 => count.to_string(),
+        _ => "999+".to_string(),
+    }
+}
+
+// Only shown between pinned and running apps, when there are both
+fn is_separated(items: &[KobelDockItem]) -> bool {
+    items.iter().any(|item| item.pinned) && items.iter().any(|item| !item.pinned)
+}
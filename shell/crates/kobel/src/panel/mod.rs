@@ -1,9 +1,18 @@
+use std::sync::Arc;
+
+use iced::{core::window, Element, Subscription, Task};
+use iced_runtime::platform_specific::wayland::layer_surface::SctkLayerSurfaceSettings;
+
+use crate::{config::KobelConfig, output::KobelOutput, state::KobelShellState, KobelRootMessage};
+
 pub mod bar;
 pub mod dock;
 pub mod wallpaper;
 pub mod debug;
 pub mod context_menu;
 pub mod search;
+pub mod window_previews;
+pub mod registry;
 
 #[derive(Debug, Clone)]
 pub enum KobelPanelMessage {
@@ -13,4 +22,101 @@ pub enum KobelPanelMessage {
     Debug(debug::KobelDebugMessage),
     ContextMenu(context_menu::KobelContextMenuMessage),
     Search(search::KobelSearchMessage),
-}
\ No newline at end of file
+    WindowPreviews(window_previews::KobelWindowPreviewsMessage),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KobelPanelKind {
+    Wallpaper,
+    Bar,
+    Dock,
+    Debug,
+    Search,
+    ContextMenu,
+    WindowPreviews,
+}
+
+/// Kinds that get an instance on every matching output, rather than one for
+/// the whole shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KobelOutputPanelKind {
+    Wallpaper,
+    Bar,
+    Dock,
+}
+
+impl KobelOutputPanelKind {
+    pub const ALL: [KobelOutputPanelKind; 3] = [KobelOutputPanelKind::Wallpaper, KobelOutputPanelKind::Bar, KobelOutputPanelKind::Dock];
+
+    /// Output patterns from the config that the kind is shown on.
+    pub fn output_patterns(self, config: &KobelConfig) -> Vec<String> {
+        match self {
+            KobelOutputPanelKind::Wallpaper => vec!["*".to_string()],
+            KobelOutputPanelKind::Bar => config.bar.outputs.clone(),
+            KobelOutputPanelKind::Dock => config.dock.outputs.clone(),
+        }
+    }
+
+    pub fn new_panel(self, state: Arc<KobelShellState>, output: KobelOutput) -> (Box<dyn KobelPanel>, Task<KobelRootMessage>) {
+        fn boxed<P: KobelPanel + 'static>((panel, task): (P, Task<KobelRootMessage>)) -> (Box<dyn KobelPanel>, Task<KobelRootMessage>) {
+            (Box::new(panel), task)
+        }
+
+        match self {
+            KobelOutputPanelKind::Wallpaper => boxed(wallpaper::KobelWallpaper::new(state, output)),
+            KobelOutputPanelKind::Bar => boxed(bar::KobelBar::new(state, output)),
+            KobelOutputPanelKind::Dock => boxed(dock::KobelDock::new(state, output)),
+        }
+    }
+}
+
+impl From<KobelOutputPanelKind> for KobelPanelKind {
+    fn from(kind: KobelOutputPanelKind) -> Self {
+        match kind {
+            KobelOutputPanelKind::Wallpaper => KobelPanelKind::Wallpaper,
+            KobelOutputPanelKind::Bar => KobelPanelKind::Bar,
+            KobelOutputPanelKind::Dock => KobelPanelKind::Dock,
+        }
+    }
+}
+
+/// A layer surface owned by the shell. Panels are kept in a
+/// [`registry::KobelPanelRegistry`], which creates their surface from
+/// [`KobelPanel::surface`] and only hands them the messages they ask for.
+pub trait KobelPanel {
+    fn id(&self) -> window::Id;
+
+    fn kind(&self) -> KobelPanelKind;
+
+    /// The output the panel was created for, if it is a per-output panel.
+    fn output(&self) -> Option<&KobelOutput> {
+        None
+    }
+
+    /// Settings used to create the panel's layer surface when it is registered.
+    fn surface(&self) -> SctkLayerSurfaceSettings;
+
+    /// Whether `update` should be called with `message`. Messages are cloned
+    /// for every panel that wants them, so keep this as narrow as possible.
+    fn wants(&self, message: &KobelRootMessage) -> bool;
+
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage>;
+
+    fn view(&self) -> Element<KobelRootMessage>;
+
+    fn subscription(&self) -> Subscription<KobelRootMessage> {
+        Subscription::none()
+    }
+
+    /// While any panel returns true, every rendered frame is delivered as an
+    /// `AnimationFrame`. Return false as soon as the animation settles, or the
+    /// shell never goes idle.
+    fn is_animating(&self) -> bool {
+        false
+    }
+
+    /// Hidden panels keep their surface but render nothing.
+    fn is_visible(&self) -> bool {
+        true
+    }
+}
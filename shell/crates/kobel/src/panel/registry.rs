@@ -0,0 +1,186 @@
+use std::{collections::HashMap, sync::Arc};
+
+use iced::{core::window, platform_specific::shell::commands::layer_surface::{destroy_layer_surface, get_layer_surface}, widget::row, Element, Rectangle, Subscription, Task};
+
+use crate::{output::KobelOutput, panel::{context_menu::{KobelContextMenu, KobelContextMenuItem, KobelContextMenuMessage}, debug::KobelDebug, search::KobelSearch, window_previews::KobelWindowPreviews, KobelOutputPanelKind, KobelPanel, KobelPanelKind, KobelPanelMessage}, state::KobelShellState, KobelRootMessage};
+
+/// Every panel the shell currently shows, keyed by the id of its surface.
+pub struct KobelPanelRegistry {
+    state: Arc<KobelShellState>,
+    panels: HashMap<window::Id, Box<dyn KobelPanel>>,
+}
+
+impl KobelPanelRegistry {
+    pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
+        let mut registry = Self {
+            state: state.clone(),
+            panels: HashMap::new(),
+        };
+
+        // Per-output panels follow once the compositor announces its outputs
+        let task = Task::batch(vec![
+            registry.insert(KobelDebug::new(state.clone())),
+            registry.insert(KobelSearch::new(state)),
+        ]);
+
+        (registry, task)
+    }
+
+    /// Registers a panel and creates its surface. `task` is run once the surface exists.
+    pub fn insert<P: KobelPanel + 'static>(&mut self, (panel, task): (P, Task<KobelRootMessage>)) -> Task<KobelRootMessage> {
+        self.insert_boxed(Box::new(panel), task)
+    }
+
+    fn insert_boxed(&mut self, panel: Box<dyn KobelPanel>, task: Task<KobelRootMessage>) -> Task<KobelRootMessage> {
+        let surface = get_layer_surface(panel.surface());
+
+        self.panels.insert(panel.id(), panel);
+
+        surface.chain(task)
+    }
+
+    pub fn remove(&mut self, id: window::Id) -> Task<KobelRootMessage> {
+        match self.panels.remove(&id) {
+            Some(_) => destroy_layer_surface(id),
+            None => Task::none(),
+        }
+    }
+
+    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+        // Previews already open for the same app take the message themselves
+        let previews_open = self.panels.values()
+            .any(|panel| panel.kind() == KobelPanelKind::WindowPreviews && panel.wants(&message));
+
+        let mut tasks: Vec<Task<KobelRootMessage>> = self.panels
+            .values_mut()
+            .filter(|panel| panel.wants(&message))
+            .map(|panel| panel.update(message.clone()))
+            .collect();
+
+        match message {
+            KobelRootMessage::OutputAdded(_)
+            | KobelRootMessage::OutputUpdated(_)
+            | KobelRootMessage::OutputRemoved(_)
+            | KobelRootMessage::ConfigReloaded(_) => {
+                tasks.push(self.sync_output_panels());
+            },
+            KobelRootMessage::OpenContextMenu { output, width, items, surface_offset } => {
+                tasks.push(self.open_context_menu(output, width, items, surface_offset));
+            },
+            KobelRootMessage::Panel(KobelPanelMessage::ContextMenu(KobelContextMenuMessage::Activate(message))) => {
+                tasks.extend(self.ids_of_kind(KobelPanelKind::ContextMenu).into_iter().map(|id| self.remove(id)));
+                tasks.push(Task::done(*message));
+            },
+            KobelRootMessage::Panel(KobelPanelMessage::ContextMenu(KobelContextMenuMessage::Dismiss)) => {
+                tasks.extend(self.ids_of_kind(KobelPanelKind::ContextMenu).into_iter().map(|id| self.remove(id)));
+            },
+            KobelRootMessage::ShowWindowPreviews { output, app_id, x } if !previews_open => {
+                tasks.extend(self.ids_of_kind(KobelPanelKind::WindowPreviews).into_iter().map(|id| self.remove(id)));
+                tasks.push(self.insert(KobelWindowPreviews::new(self.state.clone(), output, app_id, x)));
+            },
+            KobelRootMessage::CloseWindowPreviews => {
+                tasks.extend(self.ids_of_kind(KobelPanelKind::WindowPreviews).into_iter().map(|id| self.remove(id)));
+            },
+            _ => {}
+        }
+
+        Task::batch(tasks)
+    }
+
+    pub fn view(&self, id: window::Id) -> Element<KobelRootMessage> {
+        match self.panels.get(&id) {
+            Some(panel) if panel.is_visible() => panel.view(),
+            Some(_) => row![].into(),
+            None => {
+                log::warn!("Unknown window ID: {:?}", id);
+                row![].into()
+            },
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<KobelRootMessage> {
+        let mut subscriptions: Vec<Subscription<KobelRootMessage>> = self.panels
+            .values()
+            .map(|panel| panel.subscription())
+            .collect();
+
+        // Without this, nothing redraws until the next input or clock event
+        if self.panels.values().any(|panel| panel.is_animating()) {
+            subscriptions.push(iced::window::frames().map(KobelRootMessage::AnimationFrame));
+        }
+
+        Subscription::batch(subscriptions)
+    }
+
+    // Only one context menu is open at a time, kept within the output it opens on
+    fn open_context_menu(&mut self, output: KobelOutput, width: f32, items: Vec<KobelContextMenuItem>, surface_offset: iced::Vector) -> Task<KobelRootMessage> {
+        let position = *self.state.pointer_position.read().unwrap() + surface_offset;
+        let height = KobelContextMenuItem::menu_height(&items);
+        let screen = output.size;
+
+        let rect = Rectangle {
+            x: position.x.min(screen.width - width).max(0.0),
+            y: position.y.min(screen.height - height).max(0.0),
+            width,
+            height,
+        };
+
+        let open_menus = self.ids_of_kind(KobelPanelKind::ContextMenu);
+        let mut tasks: Vec<Task<KobelRootMessage>> = open_menus.into_iter().map(|id| self.remove(id)).collect();
+
+        tasks.push(self.insert(KobelContextMenu::new(self.state.clone(), output, rect, items)));
+
+        Task::batch(tasks)
+    }
+
+    fn ids_of_kind(&self, kind: KobelPanelKind) -> Vec<window::Id> {
+        self.panels.values()
+            .filter(|panel| panel.kind() == kind)
+            .map(|panel| panel.id())
+            .collect()
+    }
+
+    /// Brings the per-output panels in line with the connected outputs and the
+    /// `outputs` lists in the config, creating and destroying surfaces as needed.
+    fn sync_output_panels(&mut self) -> Task<KobelRootMessage> {
+        let outputs: Vec<KobelOutput> = self.state.outputs.read().unwrap()
+            .iter()
+            .filter(|o| o.has_info())
+            .cloned()
+            .collect();
+
+        let mut tasks = vec![];
+
+        for output_kind in KobelOutputPanelKind::ALL {
+            let kind = KobelPanelKind::from(output_kind);
+            let patterns = output_kind.output_patterns(&self.state.config());
+
+            let stale: Vec<window::Id> = self.panels.values()
+                .filter(|panel| panel.kind() == kind)
+                .filter(|panel| panel.output().is_none_or(|output| {
+                    !outputs.iter().any(|o| o.wl_output == output.wl_output) || !output.matches(&patterns)
+                }))
+                .map(|panel| panel.id())
+                .collect();
+
+            for id in stale {
+                tasks.push(self.remove(id));
+            }
+
+            for output in outputs.iter().filter(|o| o.matches(&patterns)) {
+                let exists = self.panels.values().any(|panel| {
+                    panel.kind() == kind && panel.output().is_some_and(|o| o.wl_output == output.wl_output)
+                });
+
+                if !exists {
+                    log::info!("Creating {:?} on {}", kind, output.display_name());
+
+                    let (panel, task) = output_kind.new_panel(self.state.clone(), output.clone());
+                    tasks.push(self.insert_boxed(panel, task));
+                }
+            }
+        }
+
+        Task::batch(tasks)
+    }
+}
@@ -1,9 +1,9 @@
 use std::{path::PathBuf, sync::{Arc, RwLock}};
 
-use iced::{advanced::graphics::text::cache::Key, core::{text::LineHeight, window}, platform_specific::shell::commands::{layer_surface::{destroy_layer_surface, get_layer_surface, set_keyboard_interactivity, set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{column, container, row, svg, text, text_input, tooltip, vertical_rule}, Background, Color, Element, Padding, Task};
+use iced::{advanced::graphics::text::cache::Key, core::{text::LineHeight, window}, platform_specific::shell::commands::{layer_surface::{set_keyboard_interactivity, set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{column, container, row, svg, text, text_input, tooltip, vertical_rule}, Background, Color, Element, Padding, Task};
 use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, SctkLayerSurfaceSettings};
 
-use crate::{panel::dock, state::KobelShellState, util::debug::debug_border_style_or_default, widget::{k_button::{k_button, KobelShellButtonMode}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
+use crate::{panel::{dock, KobelPanel, KobelPanelKind}, state::KobelShellState, util::debug::debug_border_style_or_default, widget::{k_button::{k_button, KobelShellButtonMode}, k_icon::k_icon, k_text::k_text, primitives::button}, KobelRootMessage};
 
 pub static SEARCH_DEFAULT_HEIGHT: i32 = 48;
 pub static SEARCH_DEFAULT_MARGIN: f32 = 0.25;
@@ -32,43 +32,26 @@ impl KobelSearch {
     pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
         let id = window::Id::unique();
 
-        let search_width = state.screen_size.read().unwrap().width * 0.25;
-        let search_margin = state.screen_size.read().unwrap().height * state.search_margin;    
-
-        let surface: Task<KobelRootMessage> = get_layer_surface(SctkLayerSurfaceSettings {
-            id,
-            namespace: "kobelwm".to_string(),
-            layer: Layer::Top,
-            anchor: Anchor::TOP,
-            size: Some((Some(search_width as u32), Some(state.search_height as u32))),
-            exclusive_zone: -1,
-            margin: IcedMargin {
-                top: search_margin as i32,
-                bottom: 0,
-                left: 0,
-                right: 0,
-            },
-            keyboard_interactivity: KeyboardInteractivity::None,
-            pointer_interactivity: true,
-            ..Default::default()
-        });
-
         (
             Self {
                 id,
                 state,
                 query: String::new(),
             },
-            surface
+            Task::none()
         )
     }
 
     pub fn recompute_search_bounds(state: &Arc<KobelShellState>, id: window::Id, visible: bool) -> Task<KobelRootMessage> {
-        let computed_search_width = state.screen_size.read().unwrap().width * 0.25;
-        let computed_search_margin = state.screen_size.read().unwrap().height * state.search_margin;
+        let search = state.config().search.clone();
+
+        let output_size = state.primary_output_size();
+
+        let computed_search_width = output_size.width * 0.25;
+        let computed_search_margin = output_size.height * search.margin;
 
         let search_width = if visible { computed_search_width } else { 1.0 };
-        let search_height = if visible { state.search_height as f32 } else { 1.0 };
+        let search_height = if visible { search.height as f32 } else { 1.0 };
         let search_margin = if visible { computed_search_margin } else { 0.0 };
 
         log::warn!("Recomputing search bounds: width={}, height={}, margin={}", search_width, search_height, search_margin);
@@ -91,15 +74,66 @@ impl KobelSearch {
 
         Self::recompute_search_bounds(&self.state, self.id, visible)
     }
+}
 
-    pub fn is_visible(&self) -> bool {
-        *self.state.search_panel_visible.read().unwrap()
+impl KobelPanel for KobelSearch {
+    fn id(&self) -> window::Id {
+        self.id
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::Search
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        let search = self.state.config().search.clone();
+
+        let output_size = self.state.primary_output_size();
+
+        let search_width = output_size.width * 0.25;
+        let search_margin = output_size.height * search.margin;
+
+        SctkLayerSurfaceSettings {
+            id: self.id,
+            namespace: "kobelwm".to_string(),
+            layer: Layer::Top,
+            anchor: Anchor::TOP,
+            size: Some((Some(search_width as u32), Some(search.height as u32))),
+            exclusive_zone: -1,
+            margin: IcedMargin {
+                top: search_margin as i32,
+                bottom: 0,
+                left: 0,
+                right: 0,
+            },
+            keyboard_interactivity: KeyboardInteractivity::None,
+            pointer_interactivity: true,
+            ..Default::default()
+        }
+    }
+
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        matches!(
+            message,
+            KobelRootMessage::ConfigReloaded(_)
+                | KobelRootMessage::OutputAdded(_)
+                | KobelRootMessage::OutputUpdated(_)
+                | KobelRootMessage::OutputRemoved(_)
+                | KobelRootMessage::KeysReleased { .. }
+                | KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Search(_))
+        )
+    }
+
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
         let mut command = Task::none();
 
         match message {
+            KobelRootMessage::ConfigReloaded(_)
+            | KobelRootMessage::OutputAdded(_)
+            | KobelRootMessage::OutputUpdated(_)
+            | KobelRootMessage::OutputRemoved(_) => {
+                command = command.chain(Self::recompute_search_bounds(&self.state, self.id, self.is_visible()));
+            },
             KobelRootMessage::KeysReleased { modifiers, keys } => {
                 if keys.contains(&iced::keyboard::Key::Named(iced::keyboard::key::Named::Escape)) {
                     command = command.chain(self.set_visible(false));
@@ -125,10 +159,13 @@ impl KobelSearch {
         command
     }
 
-    pub fn view(&self) -> Element<KobelRootMessage> {
-        if !*self.state.search_panel_visible.read().unwrap() {
-            return row![].into();
-        }
+    fn is_visible(&self) -> bool {
+        *self.state.search_panel_visible.read().unwrap()
+    }
+
+    fn view(&self) -> Element<KobelRootMessage> {
+        let search = self.state.config().search.clone();
+        let palette = self.state.palette();
 
         let search_ui = row![
             k_icon(&self.state, "search.svg"),
@@ -137,7 +174,7 @@ impl KobelSearch {
                 .padding(0)
                 .width(iced::Length::Fill)
         ]
-            .spacing(self.state.search_padding * 0.75)
+            .spacing(search.padding * 0.75)
             .align_y(iced::Alignment::Center)
             .height(iced::Length::Fill);
 
@@ -146,12 +183,12 @@ impl KobelSearch {
             .height(iced::Length::Fill)
             .align_x(iced::Alignment::Start)
             .align_y(iced::Alignment::Center)
-            .padding(Padding::from([0.0, self.state.search_padding]))
+            .padding(Padding::from([0.0, search.padding]))
             .style(move |_| container::Style {
-                background: Some(self.state.shell_background.clone()),
-                text_color: Some(self.state.shell_text_color),
+                background: Some(Background::Color(palette.background)),
+                text_color: Some(palette.text),
                 border: debug_border_style_or_default(&self.state, iced::Border {
-                    radius: self.state.search_radii.into(),
+                    radius: search.radii.into(),
                     ..Default::default()
                 }),
                 ..container::Style::default()
@@ -163,4 +200,4 @@ impl KobelSearch {
             .align_y(iced::Alignment::Center)
             .into()
     }
-}
\ No newline at end of file
+}
@@ -1,14 +1,16 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use iced::{core::window, platform_specific::shell::commands::{layer_surface::get_layer_surface, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{container, mouse_area, row}, window::Id, Background, Color, Element, Rectangle, Task};
-use iced_runtime::platform_specific::wayland::layer_surface::SctkLayerSurfaceSettings;
+use iced::{core::window, platform_specific::shell::commands::subsurface::{Anchor, KeyboardInteractivity, Layer}, widget::{container, mouse_area, row}, window::Id, Background, Color, Element, Rectangle, Task};
+use iced_runtime::platform_specific::wayland::layer_surface::{IcedOutput, SctkLayerSurfaceSettings};
 
-use crate::{state::KobelShellState, KobelRootMessage};
+use crate::{animation::{self, KobelTimeline}, output::KobelOutput, panel::{context_menu::KobelContextMenuItem, KobelPanel, KobelPanelKind}, resources, state::KobelShellState, theme::KobelThemeVariant, KobelRootMessage};
+
+static WALLPAPER_FADE_DURATION: Duration = Duration::from_millis(1500);
 
 #[derive(Debug, Clone)]
 pub enum KobelWallpaperMessage {
-    WallpaperLoaded(iced::widget::image::Handle),
-    WallpaperFailed,
+    WallpaperLoaded(window::Id, iced::widget::image::Handle),
+    WallpaperFailed(window::Id),
 }
 
 impl Into<KobelRootMessage> for KobelWallpaperMessage {
@@ -20,20 +22,109 @@ impl Into<KobelRootMessage> for KobelWallpaperMessage {
 #[derive(Debug)]
 pub struct KobelWallpaper {
     pub id: window::Id,
+    pub output: KobelOutput,
     state: Arc<KobelShellState>,
 
-    wallpaper_opacity: f32,
+    wallpaper_name: String,
+    wallpaper_opacity: KobelTimeline<f32>,
     wallpaper_handle: Option<iced::widget::image::Handle>,
-    wallpaper_fade_start_time: Option<std::time::Instant>,
 }
 
 impl KobelWallpaper {
-    pub fn new(state: Arc<KobelShellState>) -> (Self, Task<KobelRootMessage>) {
+    pub fn new(state: Arc<KobelShellState>, output: KobelOutput) -> (Self, Task<KobelRootMessage>) {
         let id = Id::unique();
+        let wallpaper_name = state.theme().wallpaper.clone();
+
+        (
+            Self {
+                id,
+                output,
+                state: state.clone(),
+
+                wallpaper_name: wallpaper_name.clone(),
+                wallpaper_opacity: KobelTimeline::new(0.0)
+                    .duration(WALLPAPER_FADE_DURATION)
+                    .easing(animation::linear),
+                wallpaper_handle: None,
+            },
+            Self::load_wallpaper_task(id, wallpaper_name),
+        )
+    }
 
-        let surface = get_layer_surface(SctkLayerSurfaceSettings {
-            id,
+    fn load_wallpaper_task(id: window::Id, name: String) -> Task<KobelRootMessage> {
+        Task::perform(
+            Self::load_wallpaper(name),
+            move |result| match result {
+                Ok(handle) => KobelWallpaperMessage::WallpaperLoaded(id, handle).into(),
+                Err(_) => KobelWallpaperMessage::WallpaperFailed(id).into(),
+            },
+        )
+    }
+
+    async fn load_wallpaper(name: String) -> anyhow::Result<iced::widget::image::Handle> {
+        log::info!("Loading wallpaper...");
+                
+        let Some(resource) = resources::lookup(&name) else {
+            anyhow::bail!("Wallpaper '{}' not found", name);
+        };
+
+        // load and decode in background thread
+        let handle = tokio::task::spawn_blocking(move || -> anyhow::Result<iced::widget::image::Handle> {
+            let bytes = resource.read()
+                .map_err(|e| anyhow::anyhow!("Failed to read wallpaper '{}': {}", resource, e))?;
+            let image = image::load_from_memory(&bytes)
+                .map_err(|e| anyhow::anyhow!("Failed to load image: {}", e))?;
+            let rgba = image.to_rgba8();
+            let (width, height) = rgba.dimensions();
+            let pixels = rgba.into_raw();
+
+            Ok(iced::widget::image::Handle::from_rgba(width, height, pixels))
+        })
+        .await??;
+
+        log::info!("Wallpaper loaded successfully");
+        Ok(handle)
+    }
+
+    fn context_menu_items(&self) -> Vec<KobelContextMenuItem> {
+        let variant = self.state.theme_variant();
+
+        vec![
+            KobelContextMenuItem::disabled("Change wallpaper..."),
+            KobelContextMenuItem::action(
+                match variant {
+                    KobelThemeVariant::Light => "Switch to Dark Style",
+                    KobelThemeVariant::Dark => "Switch to Light Style",
+                },
+                KobelRootMessage::SetThemeVariant(variant.toggled()),
+            ),
+            KobelContextMenuItem::Separator,
+            KobelContextMenuItem::disabled("Display Settings"),
+            KobelContextMenuItem::disabled("Settings"),
+            KobelContextMenuItem::Separator,
+            KobelContextMenuItem::disabled("Lock"),
+        ]
+    }
+}
+
+impl KobelPanel for KobelWallpaper {
+    fn id(&self) -> window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::Wallpaper
+    }
+
+    fn output(&self) -> Option<&KobelOutput> {
+        Some(&self.output)
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        SctkLayerSurfaceSettings {
+            id: self.id,
             namespace: "kobelwm".to_string(),
+            output: IcedOutput::Output(self.output.wl_output.clone()),
             layer: Layer::Background,
             anchor: Anchor::TOP | Anchor::LEFT | Anchor::RIGHT | Anchor::BOTTOM,
             size: Some((None, None)),
@@ -41,74 +132,67 @@ impl KobelWallpaper {
             keyboard_interactivity: KeyboardInteractivity::None,
             pointer_interactivity: true,
             ..Default::default()
-        });
+        }
+    }
 
-        (
-            Self {
-                id,
-                state: state.clone(),
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        match message {
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Wallpaper(
+                KobelWallpaperMessage::WallpaperLoaded(id, _) | KobelWallpaperMessage::WallpaperFailed(id)
+            )) => *id == self.id,
+            KobelRootMessage::OutputUpdated(output) => output.wl_output == self.output.wl_output,
+            KobelRootMessage::ThemeChanged => true,
+            _ => false,
+        }
+    }
 
-                wallpaper_opacity: 0.0,
-                wallpaper_handle: None,
-                wallpaper_fade_start_time: None,
-            },
-            Task::batch(vec![
-                surface,
-                Task::perform(
-                    Self::load_wallpaper(state.clone()),
-                    |result| match result {
-                        Ok(handle) => KobelWallpaperMessage::WallpaperLoaded(handle).into(),
-                        Err(_) => KobelWallpaperMessage::WallpaperFailed.into(),
-                    },
-                )
-            ])
-        )
+    fn is_animating(&self) -> bool {
+        self.wallpaper_opacity.is_animating(&self.state.animation_clock())
     }
 
-    pub fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
         if let KobelRootMessage::Panel(crate::panel::KobelPanelMessage::Wallpaper(wallpaper_message)) = &message {
             match wallpaper_message {
-                KobelWallpaperMessage::WallpaperLoaded(handle) => {
+                // Every output has its own wallpaper surface, only take our own result
+                KobelWallpaperMessage::WallpaperLoaded(id, handle) if *id == self.id => {
                     self.wallpaper_handle = Some(handle.clone());
-                    self.wallpaper_fade_start_time = Some(std::time::Instant::now());
-
-                    log::info!("Wallpaper loaded successfully");
+                    self.wallpaper_opacity.snap_to(0.0);
+                    self.wallpaper_opacity.animate_to(1.0, &self.state.animation_clock());
                 }
-                KobelWallpaperMessage::WallpaperFailed => {
+                KobelWallpaperMessage::WallpaperFailed(id) if *id == self.id => {
                     log::error!("Failed to load wallpaper");
                 }
+                _ => {}
             }
         }
 
         match message {
-            KobelRootMessage::Tick(_) => {
-                if self.wallpaper_fade_start_time.is_some() {
-                    let elapsed = self.wallpaper_fade_start_time.unwrap().elapsed().as_secs_f32();
-                    let duration = 1.5;
-
-                    if elapsed > duration {
-                        self.wallpaper_opacity = 1.0;
-                    } else {
-                        let opacity = (elapsed / duration).clamp(0.0_f32, 1.0_f32);
-                        let eased_opacity = easing_function::easings::Linear::ease(opacity);
-    
-                        self.wallpaper_opacity = eased_opacity;
-                    }
+            KobelRootMessage::ThemeChanged => {
+                let wallpaper_name = self.state.theme().wallpaper.clone();
+
+                if wallpaper_name != self.wallpaper_name {
+                    self.wallpaper_name = wallpaper_name;
+                    return Self::load_wallpaper_task(self.id, self.wallpaper_name.clone());
                 }
             }
+            KobelRootMessage::OutputUpdated(output) if output.wl_output == self.output.wl_output => {
+                self.output = output;
+            }
             _ => {}
         }
 
         Task::none()
     }
 
-    pub fn view(&self) -> Element<KobelRootMessage> {
+    fn view(&self) -> Element<KobelRootMessage> {
+        let palette = self.state.palette();
+
         let wallpaper_element: Element<KobelRootMessage> = if let Some(handle) = &self.wallpaper_handle {
             iced::widget::image(handle)
                 .width(iced::Length::Fill)
                 .height(iced::Length::Fill)
                 .content_fit(iced::ContentFit::Cover)
-                .opacity(self.wallpaper_opacity)
+                .opacity(self.wallpaper_opacity.value(&self.state.animation_clock()))
                 .into()
         } else {
             row![]
@@ -122,38 +206,16 @@ impl KobelWallpaper {
                 .align_x(iced::Alignment::Center)
                 .align_y(iced::Alignment::Center)
                 .style(move |_| container::Style {
-                    background: Some(Background::Color(Color::BLACK)),
+                    background: Some(Background::Color(Color { a: 1.0, ..palette.background })),
                     ..container::Style::default()
                 })
         )
-            .on_right_press(KobelRootMessage::OpenContextMenu { width: 250.0, height: 187.0 })
+            .on_right_press(KobelRootMessage::OpenContextMenu {
+                output: self.output.clone(),
+                width: 250.0,
+                items: self.context_menu_items(),
+                surface_offset: iced::Vector::ZERO,
+            })
             .into()
     }
-
-    async fn load_wallpaper(state: Arc<KobelShellState>) -> anyhow::Result<iced::widget::image::Handle> {
-        log::info!("Loading wallpaper...");
-                
-        let path = state.get_resource_path(&state.shell_wallpaper_name);
-        if !path.exists() {
-            log::error!("Wallpaper not found: {}", path.display());
-            anyhow::bail!("Wallpaper not found");
-        }
-
-        // load and decode in background thread
-        let handle = tokio::task::spawn_blocking(move || -> anyhow::Result<iced::widget::image::Handle> {
-            let bytes = std::fs::read(path)
-                .map_err(|e| anyhow::anyhow!("Failed to read wallpaper file: {}", e))?;
-            let image = image::load_from_memory(&bytes)
-                .map_err(|e| anyhow::anyhow!("Failed to load image: {}", e))?;
-            let rgba = image.to_rgba8();
-            let (width, height) = rgba.dimensions();
-            let pixels = rgba.into_raw();
-
-            Ok(iced::widget::image::Handle::from_rgba(width, height, pixels))
-        })
-        .await??;
-
-        log::info!("Wallpaper loaded successfully");
-        Ok(handle)
-    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use iced::{core::window, platform_specific::shell::commands::{layer_surface::{set_margin, set_size}, subsurface::{Anchor, KeyboardInteractivity, Layer}}, widget::{column, container, image, mouse_area, row, Space}, Background, Element, Subscription, Task};
+use iced_runtime::platform_specific::wayland::layer_surface::{IcedMargin, IcedOutput, SctkLayerSurfaceSettings};
+
+use crate::{output::KobelOutput, panel::{KobelPanel, KobelPanelKind}, state::KobelShellState, wayfire::protocol::{KobelCloseView, KobelFocusView, KobelWayfireView}, wayland::screencopy::{KobelCaptureRegion, KobelScreencopy}, widget::{k_button::{k_button, KobelShellButtonMode}, k_icon::k_icon, k_text::k_text}, KobelRootMessage};
+
+static WINDOW_PREVIEW_WIDTH: f32 = 220.0;
+static WINDOW_PREVIEW_THUMBNAIL_HEIGHT: f32 = 124.0;
+static WINDOW_PREVIEW_TITLE_HEIGHT: f32 = 32.0;
+static WINDOW_PREVIEW_PADDING: f32 = 8.0;
+static WINDOW_PREVIEW_SPACING: f32 = 8.0;
+/// Space between the previews and the top of the dock.
+static WINDOW_PREVIEW_OFFSET: f32 = 8.0;
+static WINDOW_PREVIEW_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the previews stay after the pointer leaves both them and the app.
+static WINDOW_PREVIEW_CLOSE_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone)]
+pub enum KobelWindowPreviewsMessage {
+    /// The pointer entered or left the previews.
+    Hover(bool),
+    CloseTimeout,
+    /// Time to capture the windows again.
+    Refresh,
+    Captured(Vec<(u64, image::Handle)>),
+    /// Focuses the view with this ID, closing the previews.
+    Focus(u64),
+    /// Asks the view with this ID to close.
+    Close(u64),
+}
+
+impl Into<KobelRootMessage> for KobelWindowPreviewsMessage {
+    fn into(self) -> KobelRootMessage {
+        KobelRootMessage::Panel(crate::panel::KobelPanelMessage::WindowPreviews(self))
+    }
+}
+
+/// The windows of an app in the dock, shown above it while the app is
+/// hovered, with a live thumbnail of each.
+#[derive(Debug)]
+pub struct KobelWindowPreviews {
+    pub id: window::Id,
+    state: Arc<KobelShellState>,
+    output: KobelOutput,
+
+    /// The dock's ID for the app, its desktop file ID or app ID.
+    app_id: String,
+    /// The middle of the app in the dock, relative to the output.
+    anchor_x: f32,
+    /// Number of windows the surface is sized for.
+    shown_windows: usize,
+
+    anchor_hovered: bool,
+    hovered: bool,
+    close_at: Option<Instant>,
+
+    thumbnails: HashMap<u64, image::Handle>,
+    capturing: bool,
+    /// Connected on the first capture and kept for the refreshes.
+    screencopy: Arc<Mutex<Option<KobelScreencopy>>>,
+}
+
+impl KobelWindowPreviews {
+    pub fn new(state: Arc<KobelShellState>, output: KobelOutput, app_id: String, anchor_x: f32) -> (Self, Task<KobelRootMessage>) {
+        let id = window::Id::unique();
+
+        let mut previews = Self {
+            id,
+            state,
+            output,
+
+            app_id,
+            anchor_x,
+            shown_windows: 0,
+
+            anchor_hovered: true,
+            hovered: false,
+            close_at: None,
+
+            thumbnails: HashMap::new(),
+            capturing: false,
+            screencopy: Arc::new(Mutex::new(None)),
+        };
+        previews.shown_windows = previews.windows().len().max(1);
+        let task = previews.capture();
+
+        (previews, task)
+    }
+
+    /// The app's windows, in the order they were opened in.
+    fn windows(&self) -> Vec<KobelWayfireView> {
+        self.state.app_views(&self.app_id)
+    }
+
+    fn size(&self) -> iced::Size {
+        let count = self.shown_windows as f32;
+
+        iced::Size::new(
+            count * WINDOW_PREVIEW_WIDTH + (count - 1.0) * WINDOW_PREVIEW_SPACING + WINDOW_PREVIEW_PADDING * 2.0,
+            WINDOW_PREVIEW_TITLE_HEIGHT + WINDOW_PREVIEW_THUMBNAIL_HEIGHT + WINDOW_PREVIEW_PADDING * 3.0,
+        )
+    }
+
+    // Centered over the app, but kept on the output
+    fn margin(&self) -> IcedMargin {
+        let dock = self.state.config().dock.clone();
+        let size = self.size();
+
+        IcedMargin {
+            top: 0,
+            right: 0,
+            bottom: dock.height + dock.margin * 2 + WINDOW_PREVIEW_OFFSET as i32,
+            left: (self.anchor_x - size.width / 2.0).min(self.output.size.width - size.width).max(0.0) as i32,
+        }
+    }
+
+    fn place_surface(&self) -> Task<KobelRootMessage> {
+        let size = self.size();
+        let margin = self.margin();
+
+        Task::batch(vec![
+            set_size(self.id, Some(size.width as u32), Some(size.height as u32)),
+            set_margin(self.id, margin.top, margin.right, margin.bottom, margin.left),
+        ])
+    }
+
+    fn schedule_close(&mut self) -> Task<KobelRootMessage> {
+        if self.hovered || self.anchor_hovered {
+            self.close_at = None;
+            return Task::none();
+        }
+
+        self.close_at = Some(Instant::now() + WINDOW_PREVIEW_CLOSE_DELAY);
+
+        Task::future(tokio::time::sleep(WINDOW_PREVIEW_CLOSE_DELAY))
+            .map(|_| KobelWindowPreviewsMessage::CloseTimeout.into())
+    }
+
+    /// Captures every window that is on screen. Minimized windows and those
+    /// on other workspaces keep their last thumbnail, or show the app's icon.
+    fn capture(&mut self) -> Task<KobelRootMessage> {
+        if self.capturing {
+            return Task::none();
+        }
+
+        let outputs = self.state.outputs.read().unwrap().clone();
+        let (ids, regions): (Vec<u64>, Vec<KobelCaptureRegion>) = self.windows().into_iter()
+            .filter(|view| !view.minimized)
+            .filter_map(|view| {
+                let output = outputs.iter().find(|o| o.name.as_deref() == Some(view.output_name.as_str()))?;
+                let geometry = view.geometry;
+
+                // Only the part of the window on the output can be copied
+                let left = geometry.x.max(0);
+                let top = geometry.y.max(0);
+                let right = (geometry.x + geometry.width).min(output.size.width as i32);
+                let bottom = (geometry.y + geometry.height).min(output.size.height as i32);
+
+                (right > left && bottom > top).then(|| (view.id, KobelCaptureRegion {
+                    output_name: view.output_name.clone(),
+                    x: left,
+                    y: top,
+                    width: right - left,
+                    height: bottom - top,
+                }))
+            })
+            .unzip();
+
+        if regions.is_empty() {
+            return Task::none();
+        }
+
+        self.capturing = true;
+        let scale = self.output.scale_factor.max(1) as f32;
+        let (max_width, max_height) = ((WINDOW_PREVIEW_WIDTH * scale) as u32, (WINDOW_PREVIEW_THUMBNAIL_HEIGHT * scale) as u32);
+
+        let screencopy = self.screencopy.clone();
+
+        Task::future(async move {
+            let captured = tokio::task::spawn_blocking(move || {
+                let mut screencopy = screencopy.lock().unwrap();
+                if screencopy.is_none() {
+                    *screencopy = Some(KobelScreencopy::connect()?);
+                }
+
+                let thumbnails = screencopy.as_mut().unwrap().capture(&regions, max_width, max_height);
+                // A broken connection is replaced on the next refresh
+                if thumbnails.is_err() {
+                    *screencopy = None;
+                }
+                thumbnails
+            }).await;
+
+            let thumbnails = match captured {
+                Ok(Ok(thumbnails)) => thumbnails,
+                Ok(Err(e)) => {
+                    log::debug!("Failed to capture window thumbnails: {:#}", e);
+                    vec![]
+                },
+                Err(e) => {
+                    log::warn!("Window thumbnail capture panicked: {}", e);
+                    vec![]
+                },
+            };
+
+            KobelWindowPreviewsMessage::Captured(
+                ids.into_iter()
+                    .zip(thumbnails)
+                    .filter_map(|(id, thumbnail)| Some((id, thumbnail?.to_handle())))
+                    .collect()
+            )
+            .into()
+        })
+    }
+
+    fn window_view(&self, window: &KobelWayfireView) -> Element<KobelRootMessage> {
+        let palette = self.state.palette();
+
+        let thumbnail_ui: Element<KobelRootMessage> = match self.thumbnails.get(&window.id) {
+            Some(thumbnail) => image(thumbnail.clone())
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .into(),
+            None => {
+                let icon = self.state.apps().get(&self.app_id)
+                    .and_then(|entry| entry.icon.clone())
+                    .unwrap_or_else(|| self.app_id.trim_end_matches(".desktop").to_string());
+
+                k_icon(&self.state, icon)
+                    .size(iced::Length::Fixed(WINDOW_PREVIEW_THUMBNAIL_HEIGHT / 2.0))
+                    .scale(self.output.scale_factor as u32)
+                    .symbolic(false)
+                    .into()
+            },
+        };
+
+        let title = if window.title.is_empty() { window.app_id.clone() } else { window.title.clone() };
+
+        let header_ui = row![
+            container(k_text(&self.state, title))
+                .width(iced::Length::Fill)
+                .clip(true),
+            k_button(&self.state, k_icon(&self.state, "window-close-symbolic"))
+                .mode(KobelShellButtonMode::Iconic)
+                .radii(WINDOW_PREVIEW_TITLE_HEIGHT / 2.0)
+                .on_press(KobelWindowPreviewsMessage::Close(window.id).into()),
+        ]
+            .spacing(WINDOW_PREVIEW_SPACING)
+            .height(WINDOW_PREVIEW_TITLE_HEIGHT)
+            .align_y(iced::Alignment::Center);
+
+        let preview_ui = column![
+            header_ui,
+            container(thumbnail_ui)
+                .width(iced::Length::Fill)
+                .height(WINDOW_PREVIEW_THUMBNAIL_HEIGHT)
+                .align_x(iced::Alignment::Center)
+                .align_y(iced::Alignment::Center)
+                .style(move |_| container::Style {
+                    background: Some(Background::Color(palette.hover)),
+                    border: iced::Border {
+                        radius: 8.0.into(),
+                        ..Default::default()
+                    },
+                    ..container::Style::default()
+                })
+                .clip(true),
+        ]
+            .spacing(WINDOW_PREVIEW_PADDING)
+            .width(WINDOW_PREVIEW_WIDTH);
+
+        k_button(&self.state, preview_ui)
+            .mode(KobelShellButtonMode::MenuItem)
+            .radii(14.0 - WINDOW_PREVIEW_PADDING / 2.0)
+            .on_press(KobelWindowPreviewsMessage::Focus(window.id).into())
+            .into()
+    }
+}
+
+impl KobelPanel for KobelWindowPreviews {
+    fn id(&self) -> window::Id {
+        self.id
+    }
+
+    fn kind(&self) -> KobelPanelKind {
+        KobelPanelKind::WindowPreviews
+    }
+
+    fn output(&self) -> Option<&KobelOutput> {
+        Some(&self.output)
+    }
+
+    fn surface(&self) -> SctkLayerSurfaceSettings {
+        let size = self.size();
+
+        SctkLayerSurfaceSettings {
+            id: self.id,
+            namespace: "kobelwm".to_string(),
+            output: IcedOutput::Output(self.output.wl_output.clone()),
+            layer: Layer::Overlay,
+            anchor: Anchor::BOTTOM | Anchor::LEFT,
+            size: Some((Some(size.width as u32), Some(size.height as u32))),
+            exclusive_zone: -1,
+            margin: self.margin(),
+            keyboard_interactivity: KeyboardInteractivity::None,
+            pointer_interactivity: true,
+            ..Default::default()
+        }
+    }
+
+    fn wants(&self, message: &KobelRootMessage) -> bool {
+        match message {
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::WindowPreviews(_)) => true,
+            KobelRootMessage::ShowWindowPreviews { output, app_id, .. } => {
+                output.wl_output == self.output.wl_output && *app_id == self.app_id
+            },
+            KobelRootMessage::HideWindowPreviews
+            | KobelRootMessage::WayfireEvent(_)
+            | KobelRootMessage::WayfireViewsListed(_) => true,
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, message: KobelRootMessage) -> Task<KobelRootMessage> {
+        let message = match message {
+            KobelRootMessage::ShowWindowPreviews { .. } => {
+                self.anchor_hovered = true;
+                return self.schedule_close();
+            },
+            KobelRootMessage::HideWindowPreviews => {
+                self.anchor_hovered = false;
+                return self.schedule_close();
+            },
+            // Windows opening, closing or being renamed change the previews
+            KobelRootMessage::WayfireEvent(_) | KobelRootMessage::WayfireViewsListed(_) => {
+                let windows = self.windows();
+                self.thumbnails.retain(|id, _| windows.iter().any(|window| window.id == *id));
+
+                if windows.is_empty() {
+                    return Task::done(KobelRootMessage::CloseWindowPreviews);
+                }
+                if windows.len() == self.shown_windows {
+                    return Task::none();
+                }
+
+                self.shown_windows = windows.len();
+                return self.place_surface();
+            },
+            KobelRootMessage::Panel(crate::panel::KobelPanelMessage::WindowPreviews(message)) => message,
+            _ => return Task::none(),
+        };
+
+        match message {
+            KobelWindowPreviewsMessage::Hover(hovered) => {
+                self.hovered = hovered;
+                self.schedule_close()
+            },
+            KobelWindowPreviewsMessage::CloseTimeout => {
+                // Timers from before the pointer came back are stale
+                match self.close_at {
+                    Some(close_at) if close_at <= Instant::now() => Task::done(KobelRootMessage::CloseWindowPreviews),
+                    _ => Task::none(),
+                }
+            },
+            KobelWindowPreviewsMessage::Refresh => self.capture(),
+            KobelWindowPreviewsMessage::Captured(thumbnails) => {
+                self.capturing = false;
+                self.thumbnails.extend(thumbnails);
+                Task::none()
+            },
+            KobelWindowPreviewsMessage::Focus(id) => {
+                let wayfire = self.state.wayfire.clone();
+
+                Task::batch(vec![
+                    Task::future(async move {
+                        if let Err(e) = wayfire.call(&KobelFocusView { id }).await {
+                            log::warn!("Failed to focus view {}: {:#}", id, e);
+                        }
+                    })
+                    .discard(),
+                    Task::done(KobelRootMessage::CloseWindowPreviews),
+                ])
+            },
+            KobelWindowPreviewsMessage::Close(id) => {
+                let wayfire = self.state.wayfire.clone();
+
+                Task::future(async move {
+                    if let Err(e) = wayfire.call(&KobelCloseView { id }).await {
+                        log::warn!("Failed to close view {}: {:#}", id, e);
+                    }
+                })
+                .discard()
+            },
+        }
+    }
+
+    fn subscription(&self) -> Subscription<KobelRootMessage> {
+        iced::time::every(WINDOW_PREVIEW_REFRESH_INTERVAL).map(|_| KobelWindowPreviewsMessage::Refresh.into())
+    }
+
+    fn view(&self) -> Element<KobelRootMessage> {
+        let palette = self.state.palette();
+
+        let windows = self.windows();
+        let previews_ui: Element<KobelRootMessage> = if windows.is_empty() {
+            Space::new(0.0, 0.0).into()
+        } else {
+            row(windows.iter().map(|window| self.window_view(window)))
+                .spacing(WINDOW_PREVIEW_SPACING)
+                .into()
+        };
+
+        mouse_area(
+            container(previews_ui)
+                .padding(WINDOW_PREVIEW_PADDING)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .style(move |_| container::Style {
+                    background: Some(Background::Color(palette.background).scale_alpha(2.0)),
+                    text_color: Some(palette.text),
+                    border: iced::Border {
+                        width: 1.0,
+                        color: palette.border,
+                        radius: 14.0.into(),
+                        ..Default::default()
+                    },
+                    ..container::Style::default()
+                })
+        )
+            .on_enter(KobelWindowPreviewsMessage::Hover(true).into())
+            .on_exit(KobelWindowPreviewsMessage::Hover(false).into())
+            .into()
+    }
+}
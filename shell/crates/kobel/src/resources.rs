@@ -0,0 +1,194 @@
+use std::{borrow::Cow, collections::BTreeSet, path::{Path, PathBuf}, sync::Mutex};
+
+pub static RESOURCES_DIR_NAME: &str = "kobel";
+
+// Icons the shell's own chrome needs, so a bare binary still draws a usable bar.
+// Anything in the data directories takes precedence.
+static EMBEDDED_RESOURCES: &[(&str, &[u8])] = &[
+    ("logo.svg", include_bytes!("../resources/logo.svg")),
+    ("search.svg", include_bytes!("../resources/search.svg")),
+    ("inspector.svg", include_bytes!("../resources/inspector.svg")),
+    ("devices.svg", include_bytes!("../resources/devices.svg")),
+    ("network_wired.svg", include_bytes!("../resources/network_wired.svg")),
+    ("power.svg", include_bytes!("../resources/power.svg")),
+    ("speaker_100.svg", include_bytes!("../resources/speaker_100.svg")),
+];
+
+// Names already reported missing, so a view that asks every frame logs once
+static REPORTED_MISSING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// An icon, image or other asset, either installed on disk or built into the binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KobelResource {
+    File(PathBuf),
+    Embedded {
+        name: &'static str,
+        bytes: &'static [u8],
+    },
+}
+
+impl KobelResource {
+    pub fn extension(&self) -> Option<&str> {
+        match self {
+            KobelResource::File(path) => path.extension()?.to_str(),
+            KobelResource::Embedded { name, .. } => Path::new(name).extension()?.to_str(),
+        }
+    }
+
+    /// Reads the whole resource. Blocks on disk access, so load large files off
+    /// the UI thread.
+    pub fn read(&self) -> std::io::Result<Cow<'static, [u8]>> {
+        match self {
+            KobelResource::File(path) => std::fs::read(path).map(Cow::Owned),
+            KobelResource::Embedded { bytes, .. } => Ok(Cow::Borrowed(bytes)),
+        }
+    }
+
+    pub fn svg_handle(&self) -> iced::widget::svg::Handle {
+        match self {
+            KobelResource::File(path) => iced::widget::svg::Handle::from_path(path),
+            KobelResource::Embedded { bytes, .. } => iced::widget::svg::Handle::from_memory(*bytes),
+        }
+    }
+
+    pub fn image_handle(&self) -> iced::widget::image::Handle {
+        match self {
+            KobelResource::File(path) => iced::widget::image::Handle::from_path(path),
+            KobelResource::Embedded { bytes, .. } => iced::widget::image::Handle::from_bytes(*bytes),
+        }
+    }
+}
+
+impl std::fmt::Display for KobelResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KobelResource::File(path) => write!(f, "{}", path.display()),
+            KobelResource::Embedded { name, .. } => write!(f, "{} (built in)", name),
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME`, usually `~/.local/share`.
+pub fn data_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+}
+
+/// `$XDG_DATA_DIRS`, most important first.
+pub fn data_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    std::env::split_paths(&data_dirs)
+        .filter(|p| p.is_absolute())
+        .collect()
+}
+
+/// Where the user can drop files to replace the shell's own, such as a
+/// different `logo.svg`.
+pub fn user_resources_dir() -> Option<PathBuf> {
+    data_home().map(|data_home| data_home.join(RESOURCES_DIR_NAME))
+}
+
+/// Directories searched for resources, most important first: the user's
+/// override directory, then `kobel` in each of `$XDG_DATA_DIRS`. Debug builds
+/// finally look in the source tree, so `cargo run` works without installing.
+pub fn resource_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = user_resources_dir().into_iter().collect();
+
+    dirs.extend(data_dirs().into_iter().map(|dir| dir.join(RESOURCES_DIR_NAME)));
+
+    if cfg!(debug_assertions) {
+        dirs.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources"));
+    }
+
+    dirs
+}
+
+/// Finds the resource called `name`, which may also be an absolute path.
+/// Missing resources are logged once, together with every place searched.
+pub fn lookup(name: &str) -> Option<KobelResource> {
+    let dirs = resource_dirs();
+    let resource = find_in(&dirs, name);
+
+    if resource.is_none() {
+        report_missing(name, || {
+            let searched: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+            format!("Resource '{}' not found. Searched {} and the built-in resources", name, searched.join(", "))
+        });
+    }
+
+    resource
+}
+
+/// Logs `message` the first time `name` turns out to be missing.
+pub fn report_missing(name: &str, message: impl FnOnce() -> String) {
+    if REPORTED_MISSING.lock().unwrap().insert(name.to_string()) {
+        log::warn!("{}", message());
+    }
+}
+
+fn find_in(dirs: &[PathBuf], name: &str) -> Option<KobelResource> {
+    let path = Path::new(name);
+    if path.has_root() {
+        return path.is_file().then(|| KobelResource::File(path.to_path_buf()));
+    }
+
+    // Keep relative names inside the resource directories
+    if path.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+        return None;
+    }
+
+    dirs.iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.is_file())
+        .map(KobelResource::File)
+        .or_else(|| {
+            EMBEDDED_RESOURCES.iter()
+                .find(|(embedded, _)| *embedded == name)
+                .map(|(name, bytes)| KobelResource::Embedded { name, bytes })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::{temp_dir, write};
+
+    #[test]
+    fn earlier_directories_take_precedence() {
+        let user = temp_dir("resources", "user");
+        let system = temp_dir("resources", "system");
+        write(user.join("logo.svg"), "<svg/>");
+        write(system.join("logo.svg"), "<svg/>");
+        write(system.join("wallpaper.jpg"), "");
+
+        let dirs = [user.clone(), system.clone()];
+        assert_eq!(find_in(&dirs, "logo.svg"), Some(KobelResource::File(user.join("logo.svg"))));
+        assert_eq!(find_in(&dirs, "wallpaper.jpg"), Some(KobelResource::File(system.join("wallpaper.jpg"))));
+    }
+
+    #[test]
+    fn falls_back_to_embedded_resources() {
+        let empty = temp_dir("resources", "empty");
+
+        let resource = find_in(&[empty], "search.svg").unwrap();
+        assert!(matches!(resource, KobelResource::Embedded { name: "search.svg", .. }));
+        assert_eq!(resource.extension(), Some("svg"));
+        assert!(!resource.read().unwrap().is_empty());
+
+        assert_eq!(find_in(&[], "missing.svg"), None);
+    }
+
+    #[test]
+    fn accepts_absolute_paths_only_as_given() {
+        let dir = temp_dir("resources", "absolute");
+        let icon = write(dir.join("tray.png"), "");
+
+        assert_eq!(find_in(&[], icon.to_str().unwrap()), Some(KobelResource::File(icon)));
+        assert_eq!(find_in(&[dir], "../absolute/tray.png"), None);
+    }
+}
@@ -1,16 +1,17 @@
-use std::{path::PathBuf, sync::RwLock};
+use std::{path::Path, sync::{Arc, RwLock, RwLockReadGuard}, time::Instant};
 
 use chrono::{DateTime, Local};
-use iced::{font::Family, keyboard, Background, Color, Font, Task};
+use iced::{core::window, futures::SinkExt, font::Family, keyboard, platform_specific::shell::commands::activation, Color, Font, Task};
+use tokio::sync::watch;
 
-use crate::{fps::FpsCounter, panel::{bar::{BAR_DEFAULT_HEIGHT, BAR_DEFAULT_MARGIN, BAR_DEFAULT_PADDING, BAR_DEFAULT_RADII}, dock::{DOCK_DEFAULT_HEIGHT, DOCK_DEFAULT_MARGIN, DOCK_DEFAULT_PADDING, DOCK_DEFAULT_RADII}, search::{SEARCH_DEFAULT_HEIGHT, SEARCH_DEFAULT_MARGIN, SEARCH_DEFAULT_PADDING, SEARCH_DEFAULT_RADII}}, KobelRootMessage};
+use crate::{animation::KobelAnimationClock, apps::{launch::{self, KobelLaunchEvent, KobelLaunches}, launcher_entry::KobelLauncherEntries, mime::KobelMimeDatabase, KobelAppDatabase}, appearance::{KobelAppearance, KobelColorSchemeMode}, config::{self, KobelConfig}, fps::FpsCounter, icons::{theme::ICON_EXTENSIONS, KobelIconCache, KobelIconHandle, KobelIconThemes}, output::KobelOutput, resources::{self, KobelResource}, theme::{KobelPalette, KobelTheme, KobelThemeVariant, KobelThemes}, trash, util::notification, wayfire::{protocol::{KobelListViews, KobelWayfireView}, KobelWayfire, KobelWayfireEvent, KobelWayfireViews}, wayland::foreign_toplevel::KobelToplevels, KobelRootMessage};
 
 #[derive(Debug)]
 pub struct KobelShellState {
     pub now: RwLock<DateTime<Local>>,
     pub fps: RwLock<FpsCounter>,
 
-    pub screen_size: RwLock<iced::Size>,
+    pub outputs: RwLock<Vec<KobelOutput>>,
     pub pointer_position: RwLock<iced::Point>,
 
     pub modifiers_pressed: RwLock<keyboard::Modifiers>,
@@ -19,42 +20,50 @@ pub struct KobelShellState {
     pub debug_panel_visible: RwLock<bool>,
     pub debug_border_style: RwLock<bool>,
 
-    pub font: Font,
-    pub font_bold: Font,
+    config: RwLock<KobelConfig>,
+    font_family: RwLock<&'static str>,
 
-    pub shell_background: iced::Background,
-    pub shell_text_color: Color,
-    pub shell_accent_color: Color,
-    pub shell_wallpaper_name: String,
-
-    pub bar_height: i32,
-    pub bar_margin: i32,
-    pub bar_radii: f32,
-    pub bar_padding: f32,
-    pub bar_radii_bottom_only: bool,
-
-    pub font_base_size: f32,
-    pub icon_base_size: f32,
-
-    pub dock_height: i32,
-    pub dock_margin: i32,
-    pub dock_padding: f32,
-    pub dock_radii: f32,
+    themes: RwLock<KobelThemes>,
+    theme_variant: RwLock<KobelThemeVariant>,
+    theme: RwLock<KobelTheme>,
+    iced_theme: RwLock<iced::Theme>,
+    system_accent_color: RwLock<Option<Color>>,
+    appearance: watch::Sender<KobelAppearance>,
+    icon_themes: RwLock<KobelIconThemes>,
+    icon_cache: KobelIconCache,
+    apps: RwLock<KobelAppDatabase>,
+    mime_types: RwLock<KobelMimeDatabase>,
+    launcher_entries: RwLock<KobelLauncherEntries>,
+    launches: RwLock<KobelLaunches>,
+    trash_full: RwLock<bool>,
 
     pub search_panel_visible: RwLock<bool>,
-    pub search_height: i32,
-    pub search_margin: f32,
-    pub search_padding: f32,
-    pub search_radii: f32,
+
+    /// Shared with tasks that make requests to the compositor.
+    pub wayfire: Arc<KobelWayfire>,
+    wayfire_views: RwLock<KobelWayfireViews>,
+    /// Open windows, shared with the thread that watches them.
+    pub toplevels: Arc<KobelToplevels>,
 }
 
 impl KobelShellState {
     pub fn new() -> Self {
+        let config = KobelConfig::load().unwrap_or_else(|e| {
+            log::error!("{:#}", e);
+            log::warn!("Falling back to the default config");
+            KobelConfig::default()
+        });
+
+        let themes = KobelThemes::load();
+        let icon_themes = KobelIconThemes::load(&config.theme.icon_theme);
+        let theme_variant = config.theme.variant;
+        let theme = Self::resolve_theme(&config, &themes, theme_variant);
+
         Self {
             fps: RwLock::new(FpsCounter::new()),
             now: RwLock::new(Local::now()),
 
-            screen_size: RwLock::new(iced::Size::default()),
+            outputs: RwLock::new(Vec::new()),
             pointer_position: RwLock::new(iced::Point::default()),
 
             modifiers_pressed: RwLock::new(keyboard::Modifiers::default()),
@@ -63,61 +72,141 @@ impl KobelShellState {
             debug_panel_visible: RwLock::new(false),
             debug_border_style: RwLock::new(false),
 
-            font: Font {
-                family: Family::Name("Cantarell"),
-                weight: iced::font::Weight::Normal,
-                stretch: iced::font::Stretch::Normal,
-                style: iced::font::Style::Normal,
-            },
-            
-            font_bold: Font {
-                family: Family::Name("Cantarell"),
-                weight: iced::font::Weight::Bold,
-                stretch: iced::font::Stretch::Normal,
-                style: iced::font::Style::Normal,
-            },
-
-            // shell_background: Background::Color(Color::from_rgba(0.05, 0.05, 0.05, 0.95)),
-            // shell_text_color: Color::WHITE,
-            shell_background: Background::Color(Color::from_rgba(0.95, 0.95, 0.95, 0.925)),
-            shell_text_color: Color::BLACK,
-            shell_accent_color: Color::from_rgb(0.2078, 0.5176, 0.8941),
-            shell_wallpaper_name: "wallpaper_light.jpg".to_string(),
-
-            bar_height: BAR_DEFAULT_HEIGHT,
-            bar_margin: BAR_DEFAULT_MARGIN,
-            bar_radii: BAR_DEFAULT_RADII,
-            bar_padding: BAR_DEFAULT_PADDING,
-            bar_radii_bottom_only: false,
-
-            font_base_size: 14.6666,
-            icon_base_size: 16.0,
-
-            dock_height: DOCK_DEFAULT_HEIGHT,
-            dock_margin: DOCK_DEFAULT_MARGIN,
-            dock_padding: DOCK_DEFAULT_PADDING,
-            dock_radii: DOCK_DEFAULT_RADII,
+            font_family: RwLock::new(leak_font_family(&config.font.family)),
+            config: RwLock::new(config),
+
+            themes: RwLock::new(themes),
+            theme_variant: RwLock::new(theme_variant),
+            iced_theme: RwLock::new(theme.to_iced_theme()),
+            system_accent_color: RwLock::new(None),
+            appearance: watch::Sender::new(KobelAppearance {
+                color_scheme: theme_variant.into(),
+                accent_color: theme.palette.accent,
+            }),
+            theme: RwLock::new(theme),
+            icon_themes: RwLock::new(icon_themes),
+            icon_cache: KobelIconCache::default(),
+            apps: RwLock::new(KobelAppDatabase::load()),
+            mime_types: RwLock::new(KobelMimeDatabase::load()),
+            launcher_entries: RwLock::new(KobelLauncherEntries::default()),
+            launches: RwLock::new(KobelLaunches::default()),
+            trash_full: RwLock::new(false),
 
             search_panel_visible: RwLock::new(false),
-            search_height: SEARCH_DEFAULT_HEIGHT,
-            search_margin: SEARCH_DEFAULT_MARGIN,
-            search_padding: SEARCH_DEFAULT_PADDING,
-            search_radii: SEARCH_DEFAULT_RADII,
+
+            wayfire: Arc::new(KobelWayfire::new()),
+            wayfire_views: RwLock::new(KobelWayfireViews::default()),
+            toplevels: Arc::new(KobelToplevels::new()),
         }
     }
 
     pub fn update(&self, message: KobelRootMessage) -> Task<KobelRootMessage> {
-        self.fps.write().unwrap().tick();
-
-        let now = Local::now();
-        *self.now.write().unwrap() = now;
+        let mut theme_changed = false;
+        let mut tasks = vec![];
 
         match message {
+            KobelRootMessage::Tick(now) => {
+                *self.now.write().unwrap() = now;
+            },
+            KobelRootMessage::AnimationFrame(_) => {
+                self.fps.write().unwrap().tick();
+            },
             KobelRootMessage::CursorMoved { position } => {
                 *self.pointer_position.write().unwrap() = position;
             },
-            KobelRootMessage::ScreenSizeChanged { size } => {
-                *self.screen_size.write().unwrap() = size;
+            KobelRootMessage::OutputAdded(output) => {
+                log::info!("Output added: {}", output.display_name());
+                self.outputs.write().unwrap().push(output);
+            },
+            KobelRootMessage::OutputUpdated(output) => {
+                let mut outputs = self.outputs.write().unwrap();
+                match outputs.iter_mut().find(|o| o.wl_output == output.wl_output) {
+                    Some(existing) => *existing = output,
+                    None => outputs.push(output),
+                }
+            },
+            KobelRootMessage::OutputRemoved(wl_output) => {
+                self.outputs.write().unwrap().retain(|o| o.wl_output != wl_output);
+            },
+            // Saving pinned apps reloads what was already applied
+            KobelRootMessage::ConfigReloaded(config) if *config == *self.config() => {},
+            KobelRootMessage::ConfigReloaded(config) => {
+                if config.theme.icon_theme != self.config().theme.icon_theme {
+                    tasks.push(self.load_icon_themes(config.theme.icon_theme.clone()));
+                }
+                theme_changed = self.set_config((*config).clone());
+            },
+            KobelRootMessage::SetThemeVariant(variant) => {
+                theme_changed = self.set_theme_variant(variant);
+            },
+            KobelRootMessage::AppsChanged => {
+                if self.apps.write().unwrap().refresh() {
+                    log::info!("Applications changed");
+                }
+            },
+            KobelRootMessage::MimeTypesChanged => {
+                *self.mime_types.write().unwrap() = KobelMimeDatabase::load();
+                log::info!("File types changed");
+            },
+            KobelRootMessage::LauncherEntry(event) => {
+                self.launcher_entries.write().unwrap().apply(event, &self.apps());
+            },
+            KobelRootMessage::Launch(event) => {
+                return self.finish_launch(event);
+            },
+            KobelRootMessage::TrashChanged => {
+                tasks.push(Self::check_trash());
+            },
+            KobelRootMessage::TrashChecked(full) => {
+                *self.trash_full.write().unwrap() = full;
+            },
+            KobelRootMessage::Toplevel(event) => {
+                self.toplevels.apply(event);
+            },
+            KobelRootMessage::WayfireConnected => {
+                return self.list_wayfire_views();
+            },
+            KobelRootMessage::WayfireDisconnected => {
+                *self.wayfire_views.write().unwrap() = KobelWayfireViews::default();
+            },
+            KobelRootMessage::WayfireViewsListed(views) => {
+                *self.wayfire_views.write().unwrap() = KobelWayfireViews::new(views);
+            },
+            KobelRootMessage::WayfireEvent(event) => {
+                if let KobelWayfireEvent::WsetWorkspaceChanged { .. } | KobelWayfireEvent::OutputWsetChanged { .. } = *event {
+                    return self.list_wayfire_views();
+                }
+
+                if let KobelWayfireEvent::ViewMapped { view } = &*event {
+                    self.launches.write().unwrap().view_mapped(view, &self.apps());
+                }
+
+                self.wayfire_views.write().unwrap().apply(&event);
+            },
+            KobelRootMessage::SetAppPinned(id, pinned) => {
+                return self.set_app_pinned(&id, pinned);
+            },
+            KobelRootMessage::SetPinnedApps(pinned_apps) => {
+                return self.set_pinned_apps(pinned_apps);
+            },
+            KobelRootMessage::IconsChanged => {
+                tasks.push(self.load_icon_themes(self.config().theme.icon_theme.clone()));
+            },
+            KobelRootMessage::IconThemesLoaded(icon_themes) => {
+                self.set_icon_themes(&icon_themes);
+            },
+            KobelRootMessage::ThemesChanged => {
+                *self.themes.write().unwrap() = KobelThemes::load();
+                theme_changed = self.apply_theme();
+            },
+            KobelRootMessage::ColorSchemeChanged(color_scheme) => {
+                // No preference means the shell's own configured variant applies
+                let variant = color_scheme.variant().unwrap_or(self.config().theme.variant);
+                theme_changed = self.set_theme_variant(variant);
+            },
+            KobelRootMessage::AccentColorChanged(accent_color) => {
+                *self.system_accent_color.write().unwrap() = accent_color;
+                theme_changed = self.apply_theme();
             },
             KobelRootMessage::KeysPressed { modifiers, keys } => {
                 let mut keys_pressed = self.keys_pressed.write().unwrap();
@@ -138,12 +227,384 @@ impl KobelShellState {
             _ => {}
         }
 
-        Task::none()
+        if theme_changed {
+            tasks.push(Task::done(KobelRootMessage::ThemeChanged));
+        }
+
+        Task::batch(tasks)
+    }
+
+    /// Size of the first output, used by surfaces that are not bound to a
+    /// particular output and are placed by the compositor instead.
+    pub fn primary_output_size(&self) -> iced::Size {
+        self.outputs.read().unwrap()
+            .iter()
+            .find(|o| o.has_info())
+            .map(|o| o.size)
+            .unwrap_or_default()
+    }
+
+    pub fn config(&self) -> RwLockReadGuard<'_, KobelConfig> {
+        self.config.read().unwrap()
+    }
+
+    pub fn set_config(&self, config: KobelConfig) -> bool {
+        if config.font.family != self.config().font.family {
+            *self.font_family.write().unwrap() = leak_font_family(&config.font.family);
+        }
+
+        // A runtime switch is kept until the user changes the variant in the file
+        if config.theme.variant != self.config().theme.variant {
+            *self.theme_variant.write().unwrap() = config.theme.variant;
+        }
+
+        *self.config.write().unwrap() = config;
+        self.apply_theme()
+    }
+
+    /// Clock for evaluating animations right now, honoring the reduce motion setting.
+    pub fn animation_clock(&self) -> KobelAnimationClock {
+        KobelAnimationClock::new(Instant::now(), self.config().animation.reduce_motion)
     }
 
-    pub fn get_resource_path(&self, name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("resources")
-            .join(name)
+    pub fn theme(&self) -> RwLockReadGuard<'_, KobelTheme> {
+        self.theme.read().unwrap()
     }
-}
\ No newline at end of file
+
+    pub fn palette(&self) -> KobelPalette {
+        self.theme().palette
+    }
+
+    pub fn iced_theme(&self) -> iced::Theme {
+        self.iced_theme.read().unwrap().clone()
+    }
+
+    pub fn theme_variant(&self) -> KobelThemeVariant {
+        *self.theme_variant.read().unwrap()
+    }
+
+    /// Returns a receiver that is notified whenever the shell's appearance changes.
+    pub fn appearance(&self) -> watch::Receiver<KobelAppearance> {
+        self.appearance.subscribe()
+    }
+
+    pub fn set_theme_variant(&self, variant: KobelThemeVariant) -> bool {
+        *self.theme_variant.write().unwrap() = variant;
+        self.apply_theme()
+    }
+
+    // Returns whether the visible theme changed
+    fn apply_theme(&self) -> bool {
+        let mut theme = Self::resolve_theme(&self.config(), &self.themes.read().unwrap(), self.theme_variant());
+
+        if self.config().theme.color_scheme == KobelColorSchemeMode::Follow
+            && let Some(accent_color) = *self.system_accent_color.read().unwrap()
+        {
+            theme.palette.accent = accent_color;
+        }
+
+        if *self.theme() == theme {
+            return false;
+        }
+
+        log::info!("Using theme '{}'", theme.id);
+
+        let appearance = KobelAppearance {
+            color_scheme: theme.variant.into(),
+            accent_color: theme.palette.accent,
+        };
+        self.appearance.send_if_modified(|current| {
+            let modified = *current != appearance;
+            *current = appearance;
+            modified
+        });
+
+        *self.iced_theme.write().unwrap() = theme.to_iced_theme();
+        *self.theme.write().unwrap() = theme;
+
+        true
+    }
+
+    fn resolve_theme(config: &KobelConfig, themes: &KobelThemes, variant: KobelThemeVariant) -> KobelTheme {
+        let id = match variant {
+            KobelThemeVariant::Light => &config.theme.light,
+            KobelThemeVariant::Dark => &config.theme.dark,
+        };
+
+        themes.resolve(id, variant)
+    }
+
+    pub fn apps(&self) -> RwLockReadGuard<'_, KobelAppDatabase> {
+        self.apps.read().unwrap()
+    }
+
+    /// File types, for telling which apps open dropped files.
+    pub fn mime_types(&self) -> RwLockReadGuard<'_, KobelMimeDatabase> {
+        self.mime_types.read().unwrap()
+    }
+
+    /// Counts, progress and urgency published by apps, by desktop file ID.
+    pub fn launcher_entries(&self) -> RwLockReadGuard<'_, KobelLauncherEntries> {
+        self.launcher_entries.read().unwrap()
+    }
+
+    /// Apps launched from the shell that have not shown a window yet.
+    pub fn launches(&self) -> RwLockReadGuard<'_, KobelLaunches> {
+        self.launches.read().unwrap()
+    }
+
+    /// Whether anything is in the trash, on any mount.
+    pub fn trash_full(&self) -> bool {
+        *self.trash_full.read().unwrap()
+    }
+
+    /// Adds `id` to the end of the dock or removes it.
+    fn set_app_pinned(&self, id: &str, pinned: bool) -> Task<KobelRootMessage> {
+        let mut pinned_apps = self.config().dock.pinned.clone();
+
+        if pinned == pinned_apps.iter().any(|p| p == id) {
+            return Task::none();
+        }
+
+        if pinned {
+            pinned_apps.push(id.to_string());
+        } else {
+            pinned_apps.retain(|p| p != id);
+        }
+
+        self.set_pinned_apps(pinned_apps)
+    }
+
+    /// Replaces the apps pinned to the dock, applying the change right away
+    /// and saving it to the config file. Panels pick it up from the
+    /// `SetAppPinned` or `SetPinnedApps` message that led here.
+    fn set_pinned_apps(&self, pinned_apps: Vec<String>) -> Task<KobelRootMessage> {
+        let mut config = self.config().clone();
+
+        if config.dock.pinned == pinned_apps {
+            return Task::none();
+        }
+
+        config.dock.pinned = pinned_apps.clone();
+
+        // Applied here rather than on the reload, so a second change before it sees this one
+        self.set_config(config);
+
+        Task::future(tokio::task::spawn_blocking(move || {
+            if let Err(e) = config::save_pinned_apps(&pinned_apps) {
+                log::error!("{:#}", e);
+            }
+        }))
+        .discard()
+    }
+
+    /// The compositor's views, as of the last event.
+    pub fn wayfire_views(&self) -> RwLockReadGuard<'_, KobelWayfireViews> {
+        self.wayfire_views.read().unwrap()
+    }
+
+    fn list_wayfire_views(&self) -> Task<KobelRootMessage> {
+        let wayfire = self.wayfire.clone();
+
+        Task::future(async move {
+            match wayfire.call(&KobelListViews {}).await {
+                Ok(views) => Some(KobelRootMessage::WayfireViewsListed(views)),
+                Err(e) => {
+                    log::warn!("Failed to list Wayfire views: {:#}", e);
+                    None
+                },
+            }
+        })
+        .and_then(Task::done)
+    }
+
+    /// The compositor's toplevel views of the app with this ID in the dock.
+    pub fn app_views(&self, app_id: &str) -> Vec<KobelWayfireView> {
+        self.wayfire_views().of_app(app_id, &self.apps())
+    }
+
+    /// Launches the application with the desktop file ID `id`, or its action
+    /// `action`, after a click on `window`. The activation token lets the
+    /// compositor focus the new window. The launch is tracked until a window
+    /// of the app maps, and the user is told if it fails before that.
+    pub fn launch_app(&self, id: &str, action: Option<String>, uris: Vec<String>, window: window::Id) -> Task<KobelRootMessage> {
+        let Some(entry) = self.apps().get(id).cloned() else {
+            log::warn!("Not launching '{}' as it is not installed", id);
+            return Task::none();
+        };
+
+        let serial = self.launches.write().unwrap().start(id, Instant::now());
+
+        let launch_task = activation::request_token(Some(launch::ACTIVATION_APP_ID.to_string()), Some(window))
+            .then(move |token| {
+                let (entry, action, uris) = (entry.clone(), action.clone(), uris.clone());
+
+                Task::stream(iced::stream::channel(2, move |mut output| async move {
+                    if token.is_none() {
+                        log::debug!("No activation token for '{}', its window may not get focus", entry.id);
+                    }
+
+                    let children = match launch::launch(&entry, action.as_deref(), &uris, token.as_deref()) {
+                        Ok(children) => children,
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            let _ = output.send(KobelRootMessage::Launch(KobelLaunchEvent::Failed { serial, error: format!("{:#}", e) })).await;
+                            return;
+                        },
+                    };
+
+                    // The first process is the one holding the activation token
+                    if let Some(pid) = children.first().and_then(|child| child.id()) {
+                        let _ = output.send(KobelRootMessage::Launch(KobelLaunchEvent::Spawned { serial, pid })).await;
+                    }
+
+                    // Launchers that hand over to a running instance exit successfully
+                    // before any window maps, so only failures count
+                    let waits = children.into_iter().map(|mut child| async move {
+                        tokio::time::timeout(launch::LAUNCH_TIMEOUT, child.wait()).await
+                    });
+                    let failure = iced::futures::future::join_all(waits).await.into_iter()
+                        .find_map(|result| match result {
+                            Ok(Ok(status)) if !status.success() => Some(status),
+                            _ => None,
+                        });
+
+                    if let Some(status) = failure {
+                        log::warn!("'{}' exited with {} before showing a window", entry.id, status);
+                        let error = match status.code() {
+                            Some(code) => format!("It exited with code {}.", code),
+                            None => "It was stopped by a signal.".to_string(),
+                        };
+                        let _ = output.send(KobelRootMessage::Launch(KobelLaunchEvent::Failed { serial, error })).await;
+                    }
+                }))
+            });
+
+        let timeout_task = Task::future(tokio::time::sleep(launch::LAUNCH_TIMEOUT))
+            .map(move |_| KobelRootMessage::Launch(KobelLaunchEvent::TimedOut(serial)));
+
+        Task::batch(vec![launch_task, timeout_task])
+    }
+
+    fn finish_launch(&self, event: KobelLaunchEvent) -> Task<KobelRootMessage> {
+        // Failures after the app's window mapped are the app's own business
+        let Some(launch) = self.launches.write().unwrap().apply(&event) else {
+            return Task::none();
+        };
+
+        let error = match event {
+            KobelLaunchEvent::Failed { error, .. } => error,
+            KobelLaunchEvent::TimedOut(_) => {
+                log::info!("'{}' showed no window within {:?}", launch.id, launch::LAUNCH_TIMEOUT);
+                return Task::none();
+            },
+            KobelLaunchEvent::Spawned { .. } => return Task::none(),
+        };
+
+        let entry = self.apps().get(&launch.id).cloned();
+        let name = entry.as_ref().map_or_else(|| launch.id.clone(), |entry| entry.name.clone());
+        let icon = entry.and_then(|entry| entry.icon);
+
+        Task::future(async move {
+            notification::notify(&format!("Failed to start {}", name), &error, icon.as_deref()).await;
+        })
+        .discard()
+    }
+
+    pub fn icon_themes(&self) -> RwLockReadGuard<'_, KobelIconThemes> {
+        self.icon_themes.read().unwrap()
+    }
+
+    /// The icon called `name` for `size` logical pixels at `scale`, from the
+    /// cache after the first time. Names with an image extension, such as
+    /// `logo.svg`, are resources, anything else comes from the icon theme.
+    pub fn icon(&self, name: &str, size: u32, scale: u32, symbolic: bool) -> Option<KobelIconHandle> {
+        self.icon_cache.get_or_load(name, size, scale, symbolic, || {
+            let path = Path::new(name);
+            let is_file = path.has_root()
+                || path.extension().is_some_and(|ext| ICON_EXTENSIONS.iter().any(|e| *e == ext));
+
+            if is_file {
+                return resources::lookup(name);
+            }
+
+            let icon_themes = self.icon_themes();
+            let path = icon_themes.lookup(name, size, scale, symbolic);
+
+            if path.is_none() {
+                resources::report_missing(name, || {
+                    format!("Icon '{}' not found in icon theme '{}' or its fallbacks", name, icon_themes.name())
+                });
+            }
+
+            path.map(|path| KobelResource::File(path.to_path_buf()))
+        })
+    }
+
+    // Every mount is looked at, and slow or sleeping disks would hold up the UI
+    fn check_trash() -> Task<KobelRootMessage> {
+        Task::future(async {
+            match tokio::task::spawn_blocking(trash::is_empty).await {
+                Ok(empty) => Some(KobelRootMessage::TrashChecked(!empty)),
+                Err(e) => {
+                    log::error!("Failed to look into the trash: {}", e);
+                    None
+                },
+            }
+        })
+        .and_then(Task::done)
+    }
+
+    // Installing a theme touches many directories at once, and each burst of
+    // changes would otherwise walk every theme again on the UI thread
+    fn load_icon_themes(&self, icon_theme: String) -> Task<KobelRootMessage> {
+        Task::future(async move {
+            match tokio::task::spawn_blocking(move || KobelIconThemes::load(&icon_theme)).await {
+                Ok(icon_themes) => Some(KobelRootMessage::IconThemesLoaded(Arc::new(icon_themes))),
+                Err(e) => {
+                    log::error!("Failed to load icon themes: {}", e);
+                    None
+                },
+            }
+        })
+        .and_then(Task::done)
+    }
+
+    /// Swaps in `icon_themes`, unless the icon theme was changed again or a
+    /// newer load finished first while it was loading.
+    fn set_icon_themes(&self, icon_themes: &KobelIconThemes) {
+        if icon_themes.name() != self.config().theme.icon_theme {
+            return;
+        }
+
+        let mut current = self.icon_themes.write().unwrap();
+        if current.name() == icon_themes.name() && current.generation() > icon_themes.generation() {
+            return;
+        }
+
+        *current = icon_themes.clone();
+        self.icon_cache.clear();
+    }
+
+    pub fn font(&self) -> Font {
+        Font {
+            family: Family::Name(*self.font_family.read().unwrap()),
+            weight: iced::font::Weight::Normal,
+            stretch: iced::font::Stretch::Normal,
+            style: iced::font::Style::Normal,
+        }
+    }
+
+    pub fn font_bold(&self) -> Font {
+        Font {
+            weight: iced::font::Weight::Bold,
+            ..self.font()
+        }
+    }
+}
+
+// iced only accepts font family names with a static lifetime. The family only
+// changes when the user edits their config, so leaking it is cheap.
+fn leak_font_family(family: &str) -> &'static str {
+    Box::leak(family.to_string().into_boxed_str())
+}
@@ -0,0 +1,271 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use anyhow::Context;
+use iced::{futures::StreamExt, Color, Subscription};
+use serde::{Deserialize, Deserializer};
+
+use crate::{config, util::watch, KobelRootMessage};
+
+pub static THEMES_DIR_NAME: &str = "themes";
+
+pub static LIGHT_THEME_ID: &str = "kobel-light";
+pub static DARK_THEME_ID: &str = "kobel-dark";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KobelThemeVariant {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl KobelThemeVariant {
+    pub fn toggled(self) -> Self {
+        match self {
+            KobelThemeVariant::Light => KobelThemeVariant::Dark,
+            KobelThemeVariant::Dark => KobelThemeVariant::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KobelPalette {
+    /// Panel background, usually slightly translucent.
+    pub background: Color,
+    pub text: Color,
+    pub accent: Color,
+    /// Text and icons drawn on top of `accent`.
+    pub accent_text: Color,
+    /// Tint laid over interactive elements while hovered.
+    pub hover: Color,
+    /// Tint laid over interactive elements while pressed.
+    pub pressed: Color,
+    pub border: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelTheme {
+    pub id: String,
+    pub name: String,
+    pub variant: KobelThemeVariant,
+    pub palette: KobelPalette,
+    pub wallpaper: String,
+}
+
+impl KobelTheme {
+    pub fn light() -> Self {
+        Self {
+            id: LIGHT_THEME_ID.to_string(),
+            name: "Kobel Light".to_string(),
+            variant: KobelThemeVariant::Light,
+            palette: KobelPalette {
+                background: Color::from_rgba(0.95, 0.95, 0.95, 0.925),
+                text: Color::BLACK,
+                accent: Color::from_rgb(0.2078, 0.5176, 0.8941),
+                accent_text: Color::WHITE,
+                hover: Color::from_rgba(0.5, 0.5, 0.5, 0.1),
+                pressed: Color::from_rgba(0.5, 0.5, 0.5, 0.2),
+                border: Color::from_rgba(0.0, 0.0, 0.0, 0.15),
+            },
+            wallpaper: "wallpaper_light.jpg".to_string(),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            id: DARK_THEME_ID.to_string(),
+            name: "Kobel Dark".to_string(),
+            variant: KobelThemeVariant::Dark,
+            palette: KobelPalette {
+                background: Color::from_rgba(0.05, 0.05, 0.05, 0.95),
+                text: Color::WHITE,
+                accent: Color::from_rgb(0.2078, 0.5176, 0.8941),
+                accent_text: Color::WHITE,
+                hover: Color::from_rgba(1.0, 1.0, 1.0, 0.1),
+                pressed: Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                border: Color::from_rgba(1.0, 1.0, 1.0, 0.15),
+            },
+            wallpaper: "wallpaper_dark.jpg".to_string(),
+        }
+    }
+
+    pub fn builtin(variant: KobelThemeVariant) -> Self {
+        match variant {
+            KobelThemeVariant::Light => Self::light(),
+            KobelThemeVariant::Dark => Self::dark(),
+        }
+    }
+
+    /// Builds the iced theme used for stock widgets (text inputs, checkboxes, ...)
+    /// so they match the shell's own widgets.
+    pub fn to_iced_theme(&self) -> iced::Theme {
+        let base = match self.variant {
+            KobelThemeVariant::Light => iced::theme::Palette::LIGHT,
+            KobelThemeVariant::Dark => iced::theme::Palette::DARK,
+        };
+
+        iced::Theme::custom(self.name.clone(), iced::theme::Palette {
+            background: Color { a: 1.0, ..self.palette.background },
+            text: self.palette.text,
+            primary: self.palette.accent,
+            ..base
+        })
+    }
+}
+
+// A user theme only needs to override the values it cares about, everything
+// else is taken from the built-in theme of the same variant.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KobelThemeFile {
+    name: Option<String>,
+    variant: KobelThemeVariant,
+    wallpaper: Option<String>,
+    #[serde(default)]
+    palette: KobelPaletteFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KobelPaletteFile {
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    background: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    text: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    accent: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    accent_text: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    hover: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pressed: Option<Color>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    border: Option<Color>,
+}
+
+fn deserialize_optional_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+
+    Color::parse(&value).map(Some).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "invalid color '{}', expected a hex color such as \"#3584e4\" or \"#f2f2f2ec\"",
+            value
+        ))
+    })
+}
+
+impl KobelTheme {
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let id = path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .with_context(|| format!("Invalid theme file name '{}'", path.display()))?;
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme file '{}'", path.display()))?;
+
+        Self::parse(id, &contents)
+            .with_context(|| format!("Invalid theme file '{}'", path.display()))
+    }
+
+    pub fn parse(id: String, contents: &str) -> anyhow::Result<Self> {
+        let file: KobelThemeFile = toml::from_str(contents)?;
+        let base = Self::builtin(file.variant);
+
+        Ok(Self {
+            name: file.name.unwrap_or_else(|| id.clone()),
+            id,
+            variant: file.variant,
+            palette: KobelPalette {
+                background: file.palette.background.unwrap_or(base.palette.background),
+                text: file.palette.text.unwrap_or(base.palette.text),
+                accent: file.palette.accent.unwrap_or(base.palette.accent),
+                accent_text: file.palette.accent_text.unwrap_or(base.palette.accent_text),
+                hover: file.palette.hover.unwrap_or(base.palette.hover),
+                pressed: file.palette.pressed.unwrap_or(base.palette.pressed),
+                border: file.palette.border.unwrap_or(base.palette.border),
+            },
+            wallpaper: file.wallpaper.unwrap_or(base.wallpaper),
+        })
+    }
+}
+
+/// All themes known to the shell, keyed by id. The id of a user theme is the
+/// file name of its `.toml` file without the extension.
+#[derive(Debug, Clone)]
+pub struct KobelThemes {
+    themes: BTreeMap<String, KobelTheme>,
+}
+
+impl KobelThemes {
+    pub fn load() -> Self {
+        let mut themes = BTreeMap::new();
+
+        for theme in [KobelTheme::light(), KobelTheme::dark()] {
+            themes.insert(theme.id.clone(), theme);
+        }
+
+        let dir = themes_dir();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self { themes },
+            Err(e) => {
+                log::error!("Failed to read themes directory '{}': {}", dir.display(), e);
+                return Self { themes };
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+
+            match KobelTheme::load_from(&path) {
+                Ok(theme) => {
+                    if themes.contains_key(&theme.id) {
+                        log::warn!("Theme '{}' from '{}' replaces an existing theme", theme.id, path.display());
+                    }
+                    themes.insert(theme.id.clone(), theme);
+                },
+                Err(e) => log::error!("{:#}", e),
+            }
+        }
+
+        Self { themes }
+    }
+
+    /// Looks up the theme with the given id, falling back to the built-in theme
+    /// of `variant` when it does not exist or has the wrong variant.
+    pub fn resolve(&self, id: &str, variant: KobelThemeVariant) -> KobelTheme {
+        match self.themes.get(id) {
+            Some(theme) if theme.variant == variant => theme.clone(),
+            Some(_) => {
+                log::warn!("Theme '{}' is not a {:?} theme, using the built-in one", id, variant);
+                KobelTheme::builtin(variant)
+            },
+            None => {
+                log::warn!("Theme '{}' not found, using the built-in {:?} theme", id, variant);
+                KobelTheme::builtin(variant)
+            }
+        }
+    }
+}
+
+pub fn themes_dir() -> PathBuf {
+    config::config_dir().join(THEMES_DIR_NAME)
+}
+
+/// Reloads user themes whenever a file in the themes directory changes.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct ThemesWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<ThemesWatcher>(),
+        watch::watch_path(themes_dir())
+            .map(|_| KobelRootMessage::ThemesChanged),
+    )
+}
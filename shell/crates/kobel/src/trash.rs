@@ -0,0 +1,653 @@
+//! The trash, as described by the FreeDesktop.org Trash Specification. Each
+//! trash directory keeps trashed files in `files` and, for each of them, a
+//! `.trashinfo` file in `info` saying where it came from and when.
+
+use std::{collections::BTreeSet, fs, io::{self, Write}, os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt}, path::{Path, PathBuf}, process::Stdio, sync::Mutex};
+
+use anyhow::{bail, Context};
+use chrono::{Local, NaiveDateTime};
+use iced::{futures::StreamExt, Subscription};
+
+use crate::{apps::{find_program, launch}, resources, util::{key_file, watch}, KobelRootMessage};
+
+pub static TRASH_DIR_NAME: &str = "Trash";
+pub static TRASH_INFO_EXTENSION: &str = "trashinfo";
+pub static TRASH_URI: &str = "trash:///";
+static TRASH_INFO_GROUP: &str = "Trash Info";
+static TRASH_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+/// A cache of the size of trashed directories, which goes stale when emptying.
+static TRASH_DIRECTORY_SIZES: &str = "directorysizes";
+
+// Trash directories on other mounts already reported unsafe, as every
+// listing of the trash looks at them again
+static REPORTED_UNSAFE: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// A trash directory, holding `files` and `info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelTrashDir {
+    pub path: PathBuf,
+    /// The top directory of the mount that original paths are relative to,
+    /// for trash directories on other mounts. `None` for the home trash,
+    /// which records absolute paths.
+    pub topdir: Option<PathBuf>,
+}
+
+/// Where a trashed file came from, as recorded in its `.trashinfo` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelTrashInfo {
+    pub path: PathBuf,
+    pub deletion_date: Option<NaiveDateTime>,
+}
+
+/// A file in the trash, called `name` in `files`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KobelTrashEntry {
+    pub name: String,
+    pub info: KobelTrashInfo,
+}
+
+impl KobelTrashInfo {
+    /// Reads a `.trashinfo` file. Relative paths are only allowed with a
+    /// `topdir` to resolve them against.
+    pub fn parse(contents: &str, topdir: Option<&Path>) -> Option<Self> {
+        let groups = key_file::parse_groups(contents);
+        let group = groups.get(TRASH_INFO_GROUP)?;
+
+        let path = PathBuf::from(launch::percent_decode(group.get("Path")?)?);
+        let path = match topdir {
+            _ if path.is_absolute() => path,
+            Some(topdir) => topdir.join(path),
+            None => return None,
+        };
+
+        Some(Self {
+            path,
+            deletion_date: group.get("DeletionDate")
+                .and_then(|date| NaiveDateTime::parse_from_str(date, TRASH_DATE_FORMAT).ok()),
+        })
+    }
+
+    /// The contents of the `.trashinfo` file. Paths below `topdir` are
+    /// written relative to it.
+    pub fn to_key_file(&self, topdir: Option<&Path>) -> String {
+        let path = topdir
+            .and_then(|topdir| self.path.strip_prefix(topdir).ok())
+            .unwrap_or(&self.path);
+
+        let mut contents = format!("[{}]\nPath={}\n", TRASH_INFO_GROUP, percent_encode(&path.to_string_lossy()));
+        if let Some(date) = self.deletion_date {
+            contents.push_str(&format!("DeletionDate={}\n", date.format(TRASH_DATE_FORMAT)));
+        }
+
+        contents
+    }
+}
+
+impl KobelTrashDir {
+    /// `$XDG_DATA_HOME/Trash`, for files on the same mount as it.
+    pub fn home() -> Option<Self> {
+        Some(Self {
+            path: resources::data_home()?.join(TRASH_DIR_NAME),
+            topdir: None,
+        })
+    }
+
+    /// The trash directory of the mount at `topdir`, if it has one yet.
+    /// `$topdir/.Trash/$uid` is preferred when an administrator has set up
+    /// `.Trash`, and `$topdir/.Trash-$uid` is used otherwise. Anyone may write
+    /// to a mount, so a directory is only used if it is the user's own and
+    /// private to them, or if it does not exist yet and `create` is set.
+    pub fn for_topdir(topdir: &Path, create: bool) -> Option<Self> {
+        let uid = current_uid()?;
+        let shared = topdir.join(".Trash");
+
+        // A shared trash that could have been set up by anyone is not trusted
+        let shared_metadata = fs::symlink_metadata(&shared);
+        let shared_is_safe = shared_metadata.as_ref()
+            .is_ok_and(|metadata| metadata.is_dir() && metadata.permissions().mode() & 0o1000 != 0);
+        if shared_metadata.is_ok() && !shared_is_safe && REPORTED_UNSAFE.lock().unwrap().insert(shared.clone()) {
+            log::warn!("Not using '{}' as it is not a sticky directory", shared.display());
+        }
+
+        let candidates = [
+            Some(shared.join(uid.to_string())).filter(|_| shared_is_safe),
+            Some(topdir.join(format!(".Trash-{}", uid))),
+        ];
+
+        for path in candidates.into_iter().flatten() {
+            let dir = Self {
+                path,
+                topdir: Some(topdir.to_path_buf()),
+            };
+
+            match fs::symlink_metadata(&dir.path) {
+                Ok(_) if is_private_dir(&dir.path, uid) => return Some(dir),
+                Ok(_) => {
+                    if REPORTED_UNSAFE.lock().unwrap().insert(dir.path.clone()) {
+                        log::warn!("Not using '{}' as it is not a directory private to the user", dir.path.display());
+                    }
+                },
+                Err(_) if create => match dir.create() {
+                    Ok(()) => return Some(dir),
+                    Err(e) => log::debug!("Failed to create the trash at '{}': {}", dir.path.display(), e),
+                },
+                Err(_) => {},
+            }
+        }
+
+        None
+    }
+
+    pub fn files_dir(&self) -> PathBuf {
+        self.path.join("files")
+    }
+
+    pub fn info_dir(&self) -> PathBuf {
+        self.path.join("info")
+    }
+
+    fn info_file(&self, name: &str) -> PathBuf {
+        self.info_dir().join(format!("{}.{}", name, TRASH_INFO_EXTENSION))
+    }
+
+    // Only the user may look into their trash. On other mounts nothing on
+    // the way is created or followed, as anyone could have put a link there
+    fn create(&self) -> io::Result<()> {
+        if self.topdir.is_none()
+            && let Some(parent) = self.path.parent()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        for dir in [self.path.clone(), self.files_dir(), self.info_dir()] {
+            match fs::DirBuilder::new().mode(0o700).create(&dir) {
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+                _ => {},
+            }
+
+            if !fs::symlink_metadata(&dir)?.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' is not a directory", dir.display())));
+            }
+        }
+
+        if self.topdir.is_some() && !current_uid().is_some_and(|uid| is_private_dir(&self.path, uid)) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("'{}' is not private to the user", self.path.display())));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !fs::read_dir(self.files_dir()).is_ok_and(|mut files| files.next().is_some())
+    }
+
+    /// Every file in the trash with a valid `.trashinfo` file, by name.
+    pub fn entries(&self) -> Vec<KobelTrashEntry> {
+        let Ok(infos) = fs::read_dir(self.info_dir()) else {
+            return vec![];
+        };
+
+        let mut entries: Vec<KobelTrashEntry> = infos.flatten()
+            .filter_map(|info| {
+                let file_name = info.file_name().into_string().ok()?;
+                let name = file_name.strip_suffix(&format!(".{}", TRASH_INFO_EXTENSION))?;
+                let contents = fs::read_to_string(info.path()).ok()?;
+
+                Some(KobelTrashEntry {
+                    name: name.to_string(),
+                    info: KobelTrashInfo::parse(&contents, self.topdir.as_deref())?,
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        entries
+    }
+
+    /// Moves `path` into the trash, returning the name it was given there.
+    /// `path` has to be on the same mount as the trash.
+    pub fn trash(&self, path: &Path) -> anyhow::Result<String> {
+        let file_name = path.file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("'{}' has no name that can be recorded", path.display()))?;
+
+        self.create().with_context(|| format!("Failed to create the trash at '{}'", self.path.display()))?;
+
+        // Creating the info file claims the name, even against other programs
+        // trashing a file of the same name at the same time
+        let (name, mut info_file) = (1..)
+            .map(|n| numbered_name(file_name, n))
+            .filter(|name| fs::symlink_metadata(self.files_dir().join(name)).is_err())
+            .find_map(|name| {
+                match fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(self.info_file(&name)) {
+                    Ok(file) => Some(Ok((name, file))),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .context("No names left in the trash")?
+            .with_context(|| format!("Failed to write to '{}'", self.info_dir().display()))?;
+
+        let info = KobelTrashInfo {
+            path: path.to_path_buf(),
+            deletion_date: Some(Local::now().naive_local()),
+        };
+
+        let result = info_file.write_all(info.to_key_file(self.topdir.as_deref()).as_bytes())
+            .context("Failed to record where the file came from")
+            .and_then(|_| move_file(path, &self.files_dir().join(&name)).context("Failed to move the file"));
+
+        if let Err(e) = result {
+            let _ = fs::remove_file(self.info_file(&name));
+            return Err(e.context(format!("Failed to trash '{}'", path.display())));
+        }
+
+        Ok(name)
+    }
+
+    /// Deletes everything in the trash for good, including files that lost
+    /// their `.trashinfo` file.
+    pub fn empty(&self) -> anyhow::Result<()> {
+        let mut failed = 0;
+
+        // The info goes last, so a file that could not be deleted still shows
+        // where it came from
+        for entry in self.entries() {
+            log::debug!("Deleting '{}', trashed from '{}'", entry.name, entry.info.path.display());
+
+            match remove(&self.files_dir().join(&entry.name)) {
+                Ok(()) => {
+                    let _ = fs::remove_file(self.info_file(&entry.name));
+                },
+                Err(e) => {
+                    log::warn!("Failed to delete '{}' from the trash: {}", entry.name, e);
+                    failed += 1;
+                },
+            }
+        }
+
+        for dir in [self.files_dir(), self.info_dir()] {
+            for leftover in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                if let Err(e) = remove(&leftover.path()) {
+                    log::warn!("Failed to delete '{}': {}", leftover.path().display(), e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let _ = fs::remove_file(self.path.join(TRASH_DIRECTORY_SIZES));
+
+        if failed > 0 {
+            bail!("{} items could not be deleted from '{}'", failed, self.path.display());
+        }
+
+        Ok(())
+    }
+}
+
+/// The home trash, then the trash directories of every mount that has one.
+pub fn trash_dirs() -> Vec<KobelTrashDir> {
+    let mut dirs: Vec<KobelTrashDir> = KobelTrashDir::home().into_iter().collect();
+
+    let mounts = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    for topdir in mounts.lines().filter_map(|line| line.split(' ').nth(1)).map(unescape_mount_point) {
+        if let Some(dir) = KobelTrashDir::for_topdir(Path::new(&topdir), false) && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+
+    dirs
+}
+
+/// Whether every trash directory is empty.
+pub fn is_empty() -> bool {
+    trash_dirs().iter().all(KobelTrashDir::is_empty)
+}
+
+/// Empties every trash directory.
+pub fn empty() -> anyhow::Result<()> {
+    let mut first_error = None;
+
+    for dir in trash_dirs() {
+        if let Err(e) = dir.empty() {
+            log::warn!("{:#}", e);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// The trash directory `path` belongs in: the home trash if it is on the
+/// same mount, otherwise the one at the top of its own mount.
+pub fn trash_dir_for(path: &Path) -> anyhow::Result<KobelTrashDir> {
+    let device = fs::symlink_metadata(path)
+        .with_context(|| format!("'{}' does not exist", path.display()))?
+        .dev();
+
+    if let Some(home) = KobelTrashDir::home()
+        && let Some(existing) = watch::nearest_existing_dir(&home.path)
+        && fs::metadata(existing).is_ok_and(|metadata| metadata.dev() == device)
+    {
+        return Ok(home);
+    }
+
+    // The top directory is the last parent still on the same device
+    let parent = path.parent().context("The root directory cannot be trashed")?;
+    let topdir = parent.ancestors()
+        .take_while(|dir| fs::metadata(dir).is_ok_and(|metadata| metadata.dev() == device))
+        .last()
+        .with_context(|| format!("Failed to find the mount of '{}'", path.display()))?;
+
+    // As gio does, files go to the home trash when their mount has no trash
+    // the user can safely use
+    match KobelTrashDir::for_topdir(topdir, true) {
+        Some(dir) => Ok(dir),
+        None => {
+            log::info!("No trash directory on the mount of '{}', using the home trash", path.display());
+            KobelTrashDir::home().context("No home directory to find the trash in")
+        },
+    }
+}
+
+/// Moves `path` into the trash of its mount.
+pub fn trash(path: &Path) -> anyhow::Result<()> {
+    // Links are trashed themselves, but their parents are resolved so the
+    // original path can be found relative to the mount
+    let parent = path.parent().context("The root directory cannot be trashed")?;
+    let path = fs::canonicalize(parent)
+        .with_context(|| format!("'{}' does not exist", parent.display()))?
+        .join(path.file_name().context("The root directory cannot be trashed")?);
+
+    let dir = trash_dir_for(&path)?;
+    if path.starts_with(&dir.path) {
+        bail!("'{}' is already in the trash", path.display());
+    }
+
+    let name = dir.trash(&path)?;
+    log::info!("Trashed '{}' as '{}'", path.display(), dir.files_dir().join(name).display());
+
+    Ok(())
+}
+
+/// Moves the local files among `uris` into the trash. Each is tried even if
+/// others fail, and the first failure is returned.
+pub fn trash_uris(uris: &[String]) -> anyhow::Result<()> {
+    let mut first_error = None;
+
+    for uri in uris {
+        let Some(path) = launch::uri_to_path(uri) else {
+            log::info!("Not trashing '{}' as it is not a local file", uri);
+            continue;
+        };
+
+        if let Err(e) = trash(Path::new(&path)) {
+            log::warn!("{:#}", e);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Shows the trash in the file manager, or the home trash's files in
+/// whatever opens directories if nothing handles `trash://`.
+pub fn open() -> anyhow::Result<()> {
+    let command = match find_program("gio") {
+        Some(_) => vec!["gio".to_string(), "open".to_string(), TRASH_URI.to_string()],
+        None => {
+            let home = KobelTrashDir::home().context("No home directory to find the trash in")?;
+            home.create().with_context(|| format!("Failed to create the trash at '{}'", home.path.display()))?;
+            vec!["xdg-open".to_string(), home.files_dir().to_string_lossy().into_owned()]
+        },
+    };
+
+    tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .env_remove("XDG_ACTIVATION_TOKEN")
+        .env_remove("DESKTOP_STARTUP_ID")
+        .spawn()
+        .with_context(|| format!("Failed to start '{}'", command[0]))?;
+
+    Ok(())
+}
+
+/// Sends [`KobelRootMessage::TrashChanged`] when files are trashed, restored
+/// or deleted in the home trash. Trash directories on other mounts are only
+/// looked at again when the shell changes them itself.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct TrashWatcher;
+
+    let Some(home) = KobelTrashDir::home() else {
+        return Subscription::none();
+    };
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<TrashWatcher>(),
+        watch::watch_path(home.files_dir())
+            .map(|_| KobelRootMessage::TrashChanged),
+    )
+}
+
+// `/proc/self` belongs to whoever the shell runs as
+fn current_uid() -> Option<u32> {
+    fs::metadata("/proc/self").ok().map(|metadata| metadata.uid())
+}
+
+// A directory itself rather than a link to one, owned by `uid` and closed to
+// everyone else
+fn is_private_dir(path: &Path, uid: u32) -> bool {
+    fs::symlink_metadata(path)
+        .is_ok_and(|metadata| metadata.is_dir() && metadata.uid() == uid && metadata.permissions().mode() & 0o077 == 0)
+}
+
+// Files trashed to the home trash from another mount are copied over
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {},
+        result => return result,
+    }
+
+    if let Err(e) = copy(from, to) {
+        let _ = remove(to);
+        return Err(e);
+    }
+
+    remove(from)
+}
+
+// Links are copied as links, and permissions are kept
+fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+
+    if metadata.is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path)?.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    };
+
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+// The second `notes.tar.gz` in the trash is `notes.2.tar.gz`. Leading dots
+// are part of the name, so `.bashrc` becomes `.bashrc.2`
+fn numbered_name(name: &str, n: u32) -> String {
+    if n == 1 {
+        return name.to_string();
+    }
+
+    match name.char_indices().skip(1).find(|(_, c)| *c == '.') {
+        Some((dot, _)) => format!("{}.{}{}", &name[..dot], n, &name[dot..]),
+        None => format!("{}.{}", name, n),
+    }
+}
+
+// Everything but unreserved characters and `/`, as in URIs
+fn percent_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Spaces and other whitespace in mount points are written as octal escapes,
+// such as `\040`
+fn unescape_mount_point(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        let octal = tail.get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+
+        match octal {
+            Some(unescaped) if byte == b'\\' => {
+                bytes.push(unescaped);
+                rest = &tail[3..];
+            },
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::{temp_dir, write};
+
+    #[test]
+    fn reads_and_writes_trash_info() {
+        let date = NaiveDateTime::parse_from_str("2004-08-31T22:32:08", TRASH_DATE_FORMAT).unwrap();
+        let info = KobelTrashInfo {
+            path: PathBuf::from("/media/usb/Photos/Summer 2004/ä.jpg"),
+            deletion_date: Some(date),
+        };
+
+        // Paths below the top directory of the mount are relative to it
+        let contents = info.to_key_file(Some(Path::new("/media/usb")));
+        assert_eq!(contents, "[Trash Info]\nPath=Photos/Summer%202004/%C3%A4.jpg\nDeletionDate=2004-08-31T22:32:08\n");
+        assert_eq!(KobelTrashInfo::parse(&contents, Some(Path::new("/media/usb"))), Some(info.clone()));
+
+        // The home trash only takes absolute paths
+        assert_eq!(KobelTrashInfo::parse(&contents, None), None);
+        assert_eq!(KobelTrashInfo::parse(&info.to_key_file(None), None), Some(info));
+        assert_eq!(KobelTrashInfo::parse("[Trash Info]\nDeletionDate=2004-08-31T22:32:08\n", None), None);
+    }
+
+    #[test]
+    fn trashes_and_empties_files() {
+        let root = temp_dir("trash", "trash");
+        let trash = KobelTrashDir { path: root.join("Trash"), topdir: None };
+        assert!(trash.is_empty());
+
+        for dir in ["a", "b"] {
+            write(root.join(dir).join("notes.tar.gz"), dir);
+        }
+        fs::create_dir_all(root.join("a/folder/inner")).unwrap();
+
+        assert_eq!(trash.trash(&root.join("a/notes.tar.gz")).unwrap(), "notes.tar.gz");
+        assert_eq!(trash.trash(&root.join("b/notes.tar.gz")).unwrap(), "notes.2.tar.gz");
+        assert_eq!(trash.trash(&root.join("a/folder")).unwrap(), "folder");
+        assert!(trash.trash(&root.join("a/missing")).is_err());
+        assert!(!trash.info_file("missing").exists());
+
+        let entries = trash.entries();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["folder", "notes.2.tar.gz", "notes.tar.gz"]);
+        assert_eq!(entries[1].info.path, root.join("b/notes.tar.gz"));
+        assert!(entries[1].info.deletion_date.is_some());
+        assert_eq!(fs::read_to_string(trash.files_dir().join("notes.2.tar.gz")).unwrap(), "b");
+        assert!(!trash.is_empty());
+
+        // Files without info are deleted too
+        write(trash.files_dir().join("orphan"), "");
+        trash.empty().unwrap();
+
+        assert!(trash.is_empty());
+        assert_eq!(fs::read_dir(trash.info_dir()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn only_uses_private_trash_dirs_on_other_mounts() {
+        let uid = current_uid().unwrap();
+        let topdir = temp_dir("trash", "topdir");
+        let own = topdir.join(format!(".Trash-{}", uid));
+        let set_mode = |path: &Path, mode: u32| fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, false), None);
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, true).unwrap().path, own);
+        assert!(is_private_dir(&own, uid));
+        assert!(own.join("files").is_dir());
+
+        // Readable by others
+        set_mode(&own, 0o755);
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, false), None);
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, true), None);
+
+        // A link to somewhere else
+        fs::remove_dir_all(&own).unwrap();
+        let elsewhere = temp_dir("trash", "elsewhere");
+        set_mode(&elsewhere, 0o700);
+        std::os::unix::fs::symlink(&elsewhere, &own).unwrap();
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, true), None);
+        fs::remove_file(&own).unwrap();
+
+        // A shared trash is only used once it is sticky
+        let shared = topdir.join(".Trash");
+        fs::create_dir(&shared).unwrap();
+        set_mode(&shared, 0o777);
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, true).unwrap().path, own);
+        set_mode(&shared, 0o1777);
+        assert_eq!(KobelTrashDir::for_topdir(&topdir, true).unwrap().path, shared.join(uid.to_string()));
+    }
+
+    #[test]
+    fn copies_files_between_mounts() {
+        let root = temp_dir("trash", "copy");
+        write(root.join("from/notes.txt"), "notes");
+        std::os::unix::fs::symlink("notes.txt", root.join("from/link")).unwrap();
+
+        copy(&root.join("from"), &root.join("to")).unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("to/notes.txt")).unwrap(), "notes");
+        assert_eq!(fs::read_link(root.join("to/link")).unwrap(), Path::new("notes.txt"));
+    }
+
+    #[test]
+    fn names_copies_before_their_extension() {
+        assert_eq!(numbered_name("notes.tar.gz", 1), "notes.tar.gz");
+        assert_eq!(numbered_name("notes.tar.gz", 3), "notes.3.tar.gz");
+        assert_eq!(numbered_name(".bashrc", 2), ".bashrc.2");
+        assert_eq!(numbered_name("Makefile", 2), "Makefile.2");
+        assert_eq!(unescape_mount_point("/media/My\\040Disk"), "/media/My Disk");
+    }
+}
@@ -0,0 +1,108 @@
+//! The `[group]` and `key=value` format shared by `.desktop` files and icon
+//! themes' `index.theme`, as described by the Desktop Entry Specification.
+
+use std::collections::HashMap;
+
+/// Keys of each group by name. Keys keep their locale suffix, as in `Name[de]`.
+pub type KobelKeyFileGroups = HashMap<String, HashMap<String, String>>;
+
+pub fn parse_groups(contents: &str) -> KobelKeyFileGroups {
+    let mut groups: KobelKeyFileGroups = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(group) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(group.to_string());
+            groups.entry(group.to_string()).or_default();
+            continue;
+        }
+
+        // Later duplicates of a key are ignored, like GLib does
+        if let (Some(group), Some((key, value))) = (&current, line.split_once('=')) {
+            groups.entry(group.clone()).or_default()
+                .entry(key.trim().to_string())
+                .or_insert_with(|| value.trim().to_string());
+        }
+    }
+
+    groups
+}
+
+/// Resolves the `\s`, `\n`, `\t`, `\r` and `\\` escapes of a string value.
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Splits a `;` separated list, where `\;` is a literal semicolon.
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut current = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                },
+                None => current.push('\\'),
+            },
+            ';' => items.push(unescape(&std::mem::take(&mut current))),
+            _ => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        items.push(unescape(&current));
+    }
+
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_groups_and_keys() {
+        let groups = parse_groups("# Comment\n[Desktop Entry]\nName = Files\nName[de]=Dateien\nName=Duplicate\n\n[Desktop Action new-window]\nExec=nautilus --new-window\n");
+
+        assert_eq!(groups["Desktop Entry"]["Name"], "Files");
+        assert_eq!(groups["Desktop Entry"]["Name[de]"], "Dateien");
+        assert_eq!(groups["Desktop Action new-window"]["Exec"], "nautilus --new-window");
+    }
+
+    #[test]
+    fn unescapes_values_and_lists() {
+        assert_eq!(unescape(r"Two\slines\nand a \\ backslash"), "Two lines\nand a \\ backslash");
+        assert_eq!(split_list("GNOME;GTK;Utility;"), ["GNOME", "GTK", "Utility"]);
+        assert_eq!(split_list(r"semi\;colon;with\sspace"), ["semi;colon", "with space"]);
+        assert!(split_list("").is_empty());
+    }
+}
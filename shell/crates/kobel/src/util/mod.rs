@@ -1 +1,6 @@
-pub mod debug;
\ No newline at end of file
+pub mod debug;
+pub mod key_file;
+pub mod notification;
+#[cfg(test)]
+pub mod test;
+pub mod watch;
\ No newline at end of file
@@ -0,0 +1,45 @@
+//! Desktop notifications from the shell itself, through whichever
+//! notification server runs on the session bus.
+
+use std::collections::HashMap;
+
+use zbus::zvariant::Value;
+
+pub static NOTIFICATION_APP_NAME: &str = "Kobel";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Shows a notification with `summary` and `body`, and the icon called
+/// `icon` if there is one. Failures are only logged, as there is nowhere
+/// else left to report them.
+pub async fn notify(summary: &str, body: &str, icon: Option<&str>) {
+    let result = async {
+        let connection = zbus::Connection::session().await?;
+        let proxy = NotificationsProxy::new(&connection).await?;
+
+        // -1 leaves the timeout to the server
+        proxy.notify(NOTIFICATION_APP_NAME, 0, icon.unwrap_or_default(), summary, body, &[], HashMap::new(), -1).await
+    };
+
+    if let Err(e) = result.await {
+        log::warn!("Failed to show the notification '{}': {}", summary, e);
+    }
+}
@@ -0,0 +1,67 @@
+//! Fixtures shared by tests that work with files on disk or on the bus.
+
+use std::{io::{BufRead, BufReader}, path::PathBuf, process::{Child, Command, Stdio}};
+
+use crate::apps::find_program;
+
+/// An empty directory for the test fixture `name` of `module`. The process
+/// ID keeps test runs from sharing files.
+pub fn temp_dir(module: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kobel-{}-{}-{}", module, std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `contents` to `path`, creating its parents, and returns `path`.
+pub fn write(path: PathBuf, contents: &str) -> PathBuf {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// A message bus of the test's own, so signals from the desktop do not
+/// get in the way.
+pub struct KobelPrivateBus {
+    daemon: Child,
+    address: String,
+}
+
+impl KobelPrivateBus {
+    /// Starts the bus, or `None` if `dbus-daemon` is not installed. Any
+    /// other failure fails the test.
+    pub fn start() -> Option<Self> {
+        find_program("dbus-daemon")?;
+
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--nopidfile", "--print-address=1", "--address=unix:tmpdir=/tmp"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start dbus-daemon");
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address)
+            .expect("dbus-daemon printed no address");
+
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub async fn connect(&self) -> zbus::Connection {
+        zbus::connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .expect("failed to connect to the private bus")
+    }
+}
+
+impl Drop for KobelPrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
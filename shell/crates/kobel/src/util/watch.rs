@@ -0,0 +1,122 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use iced::futures::{SinkExt, Stream};
+use notify::{event::AccessKind, EventKind, RecursiveMode, Watcher};
+
+// Editors tend to write a file in several steps (truncate, write, rename), so
+// events arriving within this window are collapsed into a single notification.
+static WATCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Returns the closest ancestor of `path` (including itself) that exists on disk.
+pub fn nearest_existing_dir(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|p| p.is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Watches `paths` with inotify and yields the set of changed paths after each
+/// burst of filesystem activity. Paths that do not exist are skipped.
+pub fn watch_paths(paths: Vec<PathBuf>, recursive: bool) -> impl Stream<Item = Vec<PathBuf>> {
+    iced::stream::channel(16, move |mut output| async move {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<notify::Event>();
+
+        let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            match result {
+                // Reading a watched file must not count as a change to it
+                Ok(notify::Event { kind: EventKind::Access(AccessKind::Read | AccessKind::Open(_)), .. }) => {},
+                Ok(event) => {
+                    let _ = tx.send(event);
+                },
+                Err(e) => log::warn!("Filesystem watcher error: {}", e),
+            }
+        });
+
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log::error!("Failed to create filesystem watcher: {}", e);
+                return std::future::pending().await;
+            }
+        };
+
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+
+        for path in &paths {
+            if !path.exists() {
+                log::debug!("Not watching '{}' as it does not exist", path.display());
+                continue;
+            }
+
+            if let Err(e) = watcher.watch(path, mode) {
+                log::warn!("Failed to watch '{}': {}", path.display(), e);
+            }
+        }
+
+        while let Some(event) = rx.recv().await {
+            let mut changed = event.paths;
+
+            tokio::time::sleep(WATCH_DEBOUNCE).await;
+            while let Ok(event) = rx.try_recv() {
+                changed.extend(event.paths);
+            }
+
+            changed.sort();
+            changed.dedup();
+
+            if output.send(changed).await.is_err() {
+                break;
+            }
+        }
+
+        // Keep the watcher alive for as long as the stream is polled
+        drop(watcher);
+    })
+}
+
+/// Yields whenever `path` changes. `path` may be a file or a directory and does
+/// not have to exist yet: its closest existing parent is watched until it does.
+pub fn watch_path(path: PathBuf) -> impl Stream<Item = ()> {
+    iced::stream::channel(4, move |mut output| async move {
+        use iced::futures::StreamExt;
+
+        loop {
+            let Some(watch_dir) = watch_target(&path) else {
+                log::warn!("No existing directory to watch for '{}'", path.display());
+                return;
+            };
+
+            let mut changes = Box::pin(watch_paths(vec![watch_dir.clone()], false));
+            let mut rearm = false;
+
+            while let Some(changed) = changes.next().await {
+                let relevant = changed.iter().any(|p| p.starts_with(&path) || path.starts_with(p));
+                if !relevant {
+                    continue;
+                }
+
+                if output.send(()).await.is_err() {
+                    return;
+                }
+
+                // The path (or one of its parents) was created or removed, so the
+                // directory that has to be watched is now a different one
+                if watch_target(&path).as_ref() != Some(&watch_dir) {
+                    rearm = true;
+                    break;
+                }
+            }
+
+            if !rearm {
+                return;
+            }
+        }
+    })
+}
+
+fn watch_target(path: &Path) -> Option<PathBuf> {
+    if path.is_dir() {
+        Some(path.to_path_buf())
+    } else {
+        nearest_existing_dir(path.parent()?)
+    }
+}
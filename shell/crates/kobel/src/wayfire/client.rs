@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use serde::de::DeserializeOwned;
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, net::UnixStream};
+
+use crate::wayfire::protocol::{KobelWatchEvents, KobelWayfireEvent, KobelWayfireRequest};
+
+// Same limit as the compositor, which drops clients that send more
+pub static MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// A connection to the compositor's IPC socket. Requests are answered in
+/// order, so each call waits for its own reply.
+#[derive(Debug)]
+pub struct KobelWayfireClient {
+    stream: UnixStream,
+}
+
+impl KobelWayfireClient {
+    /// Connects to the socket in `$WAYFIRE_SOCKET`.
+    pub async fn connect() -> anyhow::Result<Self> {
+        let path = crate::wayfire::socket_path().context("WAYFIRE_SOCKET is not set")?;
+
+        Self::connect_to(&path).await
+    }
+
+    pub async fn connect_to(path: &Path) -> anyhow::Result<Self> {
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("Failed to connect to {}", path.display()))?;
+
+        Ok(Self { stream })
+    }
+
+    pub async fn call<R: KobelWayfireRequest>(&mut self, request: &R) -> anyhow::Result<R::Response> {
+        let message = serde_json::json!({
+            "method": R::METHOD,
+            "data": request,
+        });
+
+        write_message(&mut self.stream, &serde_json::to_vec(&message)?).await?;
+        let reply = read_json::<serde_json::Value, _>(&mut self.stream).await?;
+
+        parse_reply::<R::Response>(reply).with_context(|| format!("{} failed", R::METHOD))
+    }
+
+    /// Subscribes to compositor events. The connection then carries nothing but
+    /// events, so keep a separate client around for requests.
+    pub async fn watch(mut self, events: Option<Vec<String>>) -> anyhow::Result<KobelWayfireEvents> {
+        self.call(&KobelWatchEvents { events }).await?;
+
+        Ok(KobelWayfireEvents { stream: self.stream })
+    }
+}
+
+/// A connection that has been turned into an event stream by [`KobelWayfireClient::watch`].
+#[derive(Debug)]
+pub struct KobelWayfireEvents {
+    stream: UnixStream,
+}
+
+impl KobelWayfireEvents {
+    /// Waits for the next event. Fails once the compositor goes away.
+    pub async fn next(&mut self) -> anyhow::Result<KobelWayfireEvent> {
+        loop {
+            let message = read_message(&mut self.stream).await?;
+
+            // One odd event is no reason to drop the connection
+            match serde_json::from_slice(&message) {
+                Ok(event) => return Ok(event),
+                Err(e) => log::warn!("Ignoring malformed Wayfire event: {}", e),
+            }
+        }
+    }
+}
+
+// Replies that carry an `error` member are failures, whatever the method
+fn parse_reply<T: DeserializeOwned>(reply: serde_json::Value) -> anyhow::Result<T> {
+    if let Some(error) = reply.get("error") {
+        bail!("{}", error.as_str().unwrap_or(&error.to_string()));
+    }
+
+    serde_json::from_value(reply).context("Unexpected reply")
+}
+
+/// Messages are framed by their length as a native-endian `u32`, followed by that much JSON.
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &[u8]) -> anyhow::Result<()> {
+    if message.len() > MAX_MESSAGE_LEN {
+        bail!("Message of {} bytes is too long", message.len());
+    }
+
+    writer.write_all(&(message.len() as u32).to_ne_bytes()).await?;
+    writer.write_all(message).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len).await.context("Connection closed")?;
+
+    let len = u32::from_ne_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(anyhow!("Message of {} bytes is too long", len));
+    }
+
+    let mut message = vec![0; len];
+    reader.read_exact(&mut message).await.context("Connection closed")?;
+
+    Ok(message)
+}
+
+async fn read_json<T: DeserializeOwned, R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<T> {
+    let message = read_message(reader).await?;
+
+    Ok(serde_json::from_slice(&message)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::wayfire::protocol::{KobelFocusedView, KobelWayfireOk, KobelWayfireView};
+
+    #[tokio::test]
+    async fn frames_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        write_message(&mut client, b"{\"method\":\"list-methods\"}").await.unwrap();
+        write_message(&mut client, b"{}").await.unwrap();
+
+        assert_eq!(read_message(&mut server).await.unwrap(), b"{\"method\":\"list-methods\"}");
+        assert_eq!(read_message(&mut server).await.unwrap(), b"{}");
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_frames() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        client.write_all(&(MAX_MESSAGE_LEN as u32 + 1).to_ne_bytes()).await.unwrap();
+
+        assert!(read_message(&mut server).await.is_err());
+    }
+
+    #[test]
+    fn error_replies_fail() {
+        let reply = json!({ "error": "toplevel view id not found!" });
+
+        let error = parse_reply::<KobelWayfireOk>(reply).unwrap_err();
+        assert_eq!(error.to_string(), "toplevel view id not found!");
+    }
+
+    #[test]
+    fn parses_views_with_missing_fields() {
+        let reply = json!({
+            "result": "ok",
+            "info": { "id": 7, "app-id": "org.gnome.Nautilus", "type": "toplevel", "output-id": 1 },
+        });
+
+        let focused = parse_reply::<KobelFocusedView>(reply).unwrap();
+        assert_eq!(focused.info, Some(KobelWayfireView {
+            id: 7,
+            app_id: "org.gnome.Nautilus".to_string(),
+            kind: "toplevel".to_string(),
+            output_id: 1,
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn parses_events() {
+        let focused = json!({ "event": "view-focused", "view": null });
+        assert_eq!(serde_json::from_value::<KobelWayfireEvent>(focused).unwrap(), KobelWayfireEvent::ViewFocused { view: None });
+
+        let switched = json!({
+            "event": "wset-workspace-changed",
+            "previous-workspace": { "x": 0, "y": 0 },
+            "new-workspace": { "x": 1, "y": 0 },
+            "output": 1,
+            "wset": 1,
+            "output-data": null,
+            "wset-data": null,
+        });
+        assert!(matches!(
+            serde_json::from_value::<KobelWayfireEvent>(switched).unwrap(),
+            KobelWayfireEvent::WsetWorkspaceChanged { new_workspace, .. } if new_workspace.x == 1
+        ));
+
+        let moved = json!({
+            "event": "view-geometry-changed",
+            "old-geometry": { "x": 0, "y": 0, "width": 640, "height": 480 },
+            "view": { "id": 3, "geometry": { "x": 20, "y": 0, "width": 640, "height": 480 } },
+        });
+        assert!(matches!(
+            serde_json::from_value::<KobelWayfireEvent>(moved).unwrap(),
+            KobelWayfireEvent::ViewGeometryChanged { view, old_geometry } if view.geometry.x == 20 && old_geometry.x == 0
+        ));
+
+        let unknown = json!({ "event": "plugin-activation-state-changed", "plugin": "expo", "state": true });
+        assert_eq!(serde_json::from_value::<KobelWayfireEvent>(unknown).unwrap(), KobelWayfireEvent::Other);
+    }
+}
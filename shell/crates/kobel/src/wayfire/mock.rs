@@ -0,0 +1,387 @@
+//! A stand-in for the compositor's IPC socket, so that code consuming
+//! compositor state can be tested without a running Wayfire.
+
+use std::{path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::{net::{UnixListener, UnixStream}, sync::broadcast, task::{JoinHandle, JoinSet}};
+
+use crate::wayfire::{client::{read_message, write_message}, protocol::{KobelWayfireGeometry, KobelWayfireOutput, KobelWayfireView, KobelWayfireWorkspaceSet}, KobelWayfireEvent, KobelWayfireEvents};
+
+#[derive(Debug, Default)]
+struct KobelMockState {
+    views: Vec<KobelWayfireView>,
+    outputs: Vec<KobelWayfireOutput>,
+    wsets: Vec<KobelWayfireWorkspaceSet>,
+    focused: Option<u64>,
+    /// Canned replies for methods the mock does not implement itself.
+    replies: Vec<(String, Value)>,
+    /// Every request received, in order.
+    requests: Vec<(String, Value)>,
+}
+
+/// Serves scripted replies on a socket of its own, and sends events to every
+/// client that watches for them. The socket goes away when this is dropped.
+pub struct KobelMockWayfire {
+    path: PathBuf,
+    state: Arc<Mutex<KobelMockState>>,
+    events: broadcast::Sender<Value>,
+    server: JoinHandle<()>,
+}
+
+impl KobelMockWayfire {
+    pub fn builder() -> KobelMockWayfireBuilder {
+        KobelMockWayfireBuilder::default()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Requests received so far as `(method, data)`, including event watches.
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn emit_view_mapped(&self, view: KobelWayfireView) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.views.retain(|v| v.id != view.id);
+            state.views.push(view.clone());
+        }
+
+        self.emit(json!({ "event": "view-mapped", "view": view }));
+    }
+
+    /// Focuses the view with `id`, or nothing when `None`. The view has to be
+    /// known, either from the builder or an earlier `emit_view_mapped`.
+    pub fn emit_view_focused(&self, id: Option<u64>) {
+        let event = focus(&mut self.state.lock().unwrap(), id).expect("focused view is unknown");
+
+        self.emit(event);
+    }
+
+    /// Moves or resizes the view with `id`, which has to be known.
+    pub fn emit_view_geometry_changed(&self, id: u64, geometry: KobelWayfireGeometry) {
+        let (view, old_geometry) = {
+            let mut state = self.state.lock().unwrap();
+            let view = state.views.iter_mut().find(|v| v.id == id).expect("moved view is unknown");
+            let old_geometry = std::mem::replace(&mut view.geometry, geometry);
+
+            (view.clone(), old_geometry)
+        };
+
+        self.emit(json!({ "event": "view-geometry-changed", "view": view, "old-geometry": old_geometry }));
+    }
+
+    /// Unmaps the view with `id`, which has to be known.
+    pub fn emit_view_unmapped(&self, id: u64) {
+        let event = unmap(&mut self.state.lock().unwrap(), id).expect("unmapped view is unknown");
+
+        self.emit(event);
+    }
+
+    pub fn emit_output_added(&self, output: KobelWayfireOutput) {
+        self.state.lock().unwrap().outputs.push(output.clone());
+
+        self.emit(json!({ "event": "output-added", "output": output }));
+    }
+
+    /// Drops every connection and starts listening again at the same path,
+    /// keeping the scripted state, as if the compositor had restarted.
+    pub fn restart(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_file(&self.path);
+
+        self.server = listen(&self.path, self.state.clone(), self.events.clone());
+    }
+
+    /// Sends an arbitrary event, for the ones without a helper.
+    pub fn emit(&self, event: Value) {
+        // Nobody watching is fine, the event is simply lost like it would be
+        let _ = self.events.send(event);
+    }
+}
+
+impl Drop for KobelMockWayfire {
+    fn drop(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct KobelMockWayfireBuilder {
+    state: KobelMockState,
+}
+
+impl KobelMockWayfireBuilder {
+    pub fn view(mut self, view: KobelWayfireView) -> Self {
+        self.state.views.push(view);
+        self
+    }
+
+    pub fn output(mut self, output: KobelWayfireOutput) -> Self {
+        self.state.outputs.push(output);
+        self
+    }
+
+    pub fn wset(mut self, wset: KobelWayfireWorkspaceSet) -> Self {
+        self.state.wsets.push(wset);
+        self
+    }
+
+    pub fn focused(mut self, id: u64) -> Self {
+        self.state.focused = Some(id);
+        self
+    }
+
+    /// Answers every call of `method` with `reply`.
+    pub fn reply(mut self, method: &str, reply: Value) -> Self {
+        self.state.replies.push((method.to_string(), reply));
+        self
+    }
+
+    /// Starts listening. Has to be called from within a tokio runtime.
+    pub fn start(self) -> KobelMockWayfire {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "kobel-mock-wayfire-{}-{}.socket",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let state = Arc::new(Mutex::new(self.state));
+        let (events, _) = broadcast::channel(64);
+        let server = listen(&path, state.clone(), events.clone());
+
+        KobelMockWayfire {
+            path,
+            state,
+            events,
+            server,
+        }
+    }
+}
+
+/// The next event from the mock, failing the test if none arrives in time.
+pub async fn next_event(events: &mut KobelWayfireEvents) -> KobelWayfireEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("no event arrived")
+        .expect("the mock went away")
+}
+
+// Clients are served within the listening task, so aborting it drops every
+// connection, like the compositor exiting would
+fn listen(path: &Path, state: Arc<Mutex<KobelMockState>>, events: broadcast::Sender<Value>) -> JoinHandle<()> {
+    let listener = UnixListener::bind(path).expect("failed to bind the mock Wayfire socket");
+
+    tokio::spawn(async move {
+        let mut clients = JoinSet::new();
+
+        while let Ok((stream, _)) = listener.accept().await {
+            clients.spawn(serve(stream, state.clone(), events.clone()));
+        }
+    })
+}
+
+async fn serve(mut stream: UnixStream, state: Arc<Mutex<KobelMockState>>, events: broadcast::Sender<Value>) {
+    while let Ok(message) = read_message(&mut stream).await {
+        let request: Value = serde_json::from_slice(&message).expect("client sent invalid JSON");
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let data = request["data"].clone();
+
+        state.lock().unwrap().requests.push((method.clone(), data.clone()));
+
+        if method == "window-rules/events/watch" {
+            // Subscribe before replying, so no event emitted after the reply is missed
+            let mut receiver = events.subscribe();
+
+            if write_message(&mut stream, json!({ "result": "ok" }).to_string().as_bytes()).await.is_err() {
+                return;
+            }
+
+            while let Ok(event) = receiver.recv().await {
+                if write_message(&mut stream, event.to_string().as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+
+            return;
+        }
+
+        let (reply, event) = reply_to(&method, &data, &mut state.lock().unwrap());
+        if let Some(event) = event {
+            let _ = events.send(event);
+        }
+
+        if write_message(&mut stream, reply.to_string().as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+// The event for the view with `id` gaining focus, or `None` if it is unknown
+fn focus(state: &mut KobelMockState, id: Option<u64>) -> Option<Value> {
+    let view = match id {
+        Some(id) => Some(state.views.iter().find(|v| v.id == id)?.clone()),
+        None => None,
+    };
+
+    state.focused = id;
+    for view in state.views.iter_mut() {
+        view.activated = Some(view.id) == id;
+    }
+
+    Some(json!({ "event": "view-focused", "view": view.map(|view| KobelWayfireView { activated: true, ..view }) }))
+}
+
+// The event for the view with `id` going away, or `None` if it is unknown
+fn unmap(state: &mut KobelMockState, id: u64) -> Option<Value> {
+    let index = state.views.iter().position(|v| v.id == id)?;
+    let view = state.views.remove(index);
+
+    if state.focused == Some(id) {
+        state.focused = None;
+    }
+
+    Some(json!({ "event": "view-unmapped", "view": KobelWayfireView { mapped: false, ..view } }))
+}
+
+// Requests that change views are answered like Wayfire does, along with the
+// event they cause. Closing is taken as the view going away right after.
+fn reply_to(method: &str, data: &Value, state: &mut KobelMockState) -> (Value, Option<Value>) {
+    if let Some((_, reply)) = state.replies.iter().find(|(m, _)| m == method) {
+        return (reply.clone(), None);
+    }
+
+    let event = match method {
+        "window-rules/list-views" => return (json!(state.views), None),
+        "window-rules/list-outputs" => return (json!(state.outputs), None),
+        "window-rules/list-wsets" => return (json!(state.wsets), None),
+        "window-rules/get-focused-view" => {
+            let view = state.focused.and_then(|id| state.views.iter().find(|v| v.id == id));
+            return (json!({ "result": "ok", "info": view }), None);
+        },
+        "window-rules/focus-view" => data["id"].as_u64().and_then(|id| focus(state, Some(id))),
+        "window-rules/close-view" => data["id"].as_u64().and_then(|id| unmap(state, id)),
+        _ => return (json!({ "error": "No such method found!", "method": method }), None),
+    };
+
+    match event {
+        Some(event) => (json!({ "result": "ok" }), Some(event)),
+        None => (json!({ "error": "No such view found!" }), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayfire::{protocol::{KobelGetFocusedView, KobelListOutputs, KobelListViews, KobelListWorkspaceSets, KobelSetMinimized}, KobelWayfire, KobelWayfireClient};
+
+    fn view(id: u64, app_id: &str) -> KobelWayfireView {
+        KobelWayfireView {
+            id,
+            app_id: app_id.to_string(),
+            title: app_id.to_string(),
+            kind: "toplevel".to_string(),
+            mapped: true,
+            ..Default::default()
+        }
+    }
+
+    fn output(id: u64, name: &str) -> KobelWayfireOutput {
+        KobelWayfireOutput {
+            id,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn timeout<T>(future: impl Future<Output = T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), future).await.expect("timed out")
+    }
+
+    #[tokio::test]
+    async fn serves_scripted_state() {
+        let mock = KobelMockWayfire::builder()
+            .view(view(1, "firefox"))
+            .view(view(2, "org.gnome.Nautilus"))
+            .output(output(1, "DP-1"))
+            .wset(KobelWayfireWorkspaceSet { index: 1, output_id: 1, ..Default::default() })
+            .focused(2)
+            .start();
+
+        let mut client = KobelWayfireClient::connect_to(mock.path()).await.unwrap();
+
+        let views = client.call(&KobelListViews {}).await.unwrap();
+        assert_eq!(views.iter().map(|v| v.app_id.as_str()).collect::<Vec<_>>(), ["firefox", "org.gnome.Nautilus"]);
+
+        assert_eq!(client.call(&KobelListOutputs {}).await.unwrap(), [output(1, "DP-1")]);
+        assert_eq!(client.call(&KobelListWorkspaceSets {}).await.unwrap()[0].output_id, 1);
+        assert_eq!(client.call(&KobelGetFocusedView {}).await.unwrap().info.map(|v| v.id), Some(2));
+    }
+
+    #[tokio::test]
+    async fn records_requests_and_uses_canned_replies() {
+        let mock = KobelMockWayfire::builder()
+            .reply("wm-actions/set-minimized", json!({ "result": "ok" }))
+            .start();
+
+        let mut client = KobelWayfireClient::connect_to(mock.path()).await.unwrap();
+
+        client.call(&KobelSetMinimized { view_id: 4, minimized: true }).await.unwrap();
+        assert!(client.call(&KobelListViews {}).await.unwrap().is_empty());
+
+        assert_eq!(mock.requests()[0], ("wm-actions/set-minimized".to_string(), json!({ "view_id": 4, "state": true })));
+    }
+
+    #[tokio::test]
+    async fn sends_events_to_watchers() {
+        let mock = KobelMockWayfire::builder().start();
+        let mut events = KobelWayfireClient::connect_to(mock.path()).await.unwrap().watch(None).await.unwrap();
+
+        mock.emit_output_added(output(2, "HDMI-A-1"));
+        mock.emit_view_mapped(view(3, "foot"));
+        mock.emit_view_focused(Some(3));
+
+        assert_eq!(timeout(events.next()).await.unwrap(), KobelWayfireEvent::OutputAdded { output: output(2, "HDMI-A-1") });
+        assert_eq!(timeout(events.next()).await.unwrap(), KobelWayfireEvent::ViewMapped { view: view(3, "foot") });
+
+        let KobelWayfireEvent::ViewFocused { view: Some(focused) } = timeout(events.next()).await.unwrap() else {
+            panic!("expected a focused view");
+        };
+        assert!(focused.activated);
+
+        // Events also change what later requests see
+        let mut client = KobelWayfireClient::connect_to(mock.path()).await.unwrap();
+        assert_eq!(client.call(&KobelListViews {}).await.unwrap().len(), 1);
+        assert_eq!(client.call(&KobelListOutputs {}).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn watching_ends_when_the_compositor_goes_away() {
+        let mock = KobelMockWayfire::builder().start();
+        let mut events = KobelWayfireClient::connect_to(mock.path()).await.unwrap().watch(None).await.unwrap();
+
+        drop(mock);
+
+        assert!(timeout(events.next()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn shared_connection_survives_a_restart() {
+        let mut mock = KobelMockWayfire::builder().view(view(1, "firefox")).start();
+        let wayfire = KobelWayfire::with_socket(mock.path().to_path_buf());
+
+        assert_eq!(wayfire.call(&KobelListViews {}).await.unwrap().len(), 1);
+
+        mock.restart();
+        mock.emit_view_mapped(view(2, "foot"));
+
+        assert_eq!(timeout(wayfire.call(&KobelListViews {})).await.unwrap().len(), 2);
+    }
+}
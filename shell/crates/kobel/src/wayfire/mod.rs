@@ -0,0 +1,136 @@
+use std::{path::PathBuf, time::Duration};
+
+use iced::{futures::SinkExt, Subscription};
+use tokio::sync::Mutex;
+
+use crate::KobelRootMessage;
+
+pub mod client;
+#[cfg(test)]
+pub mod mock;
+pub mod protocol;
+pub mod views;
+
+pub use client::{KobelWayfireClient, KobelWayfireEvents};
+pub use protocol::{KobelWayfireEvent, KobelWayfireRequest};
+pub use views::KobelWayfireViews;
+
+static RECONNECT_DELAY_MIN: Duration = Duration::from_millis(250);
+static RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5);
+
+/// Path of the compositor's IPC socket, which Wayfire exports to everything it starts.
+pub fn socket_path() -> Option<PathBuf> {
+    std::env::var_os("WAYFIRE_SOCKET")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A shared connection for making requests, which is opened on first use and
+/// reopened after the compositor restarts.
+#[derive(Debug, Default)]
+pub struct KobelWayfire {
+    /// Overrides `$WAYFIRE_SOCKET`.
+    socket: Option<PathBuf>,
+    client: Mutex<Option<KobelWayfireClient>>,
+}
+
+impl KobelWayfire {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_socket(socket: PathBuf) -> Self {
+        Self {
+            socket: Some(socket),
+            ..Self::default()
+        }
+    }
+
+    async fn connect(&self) -> anyhow::Result<KobelWayfireClient> {
+        match &self.socket {
+            Some(socket) => KobelWayfireClient::connect_to(socket).await,
+            None => KobelWayfireClient::connect().await,
+        }
+    }
+
+    pub async fn call<R: KobelWayfireRequest>(&self, request: &R) -> anyhow::Result<R::Response> {
+        let mut client = self.client.lock().await;
+
+        // A connection left over from before a restart only fails once it is
+        // used, so give a fresh one a second chance
+        for retry in [false, true] {
+            if client.is_none() {
+                *client = Some(self.connect().await?);
+            }
+
+            match client.as_mut().unwrap().call(request).await {
+                Err(e) if !retry && e.root_cause().is::<std::io::Error>() => {
+                    log::debug!("Reconnecting to Wayfire: {:#}", e);
+                    *client = None;
+                },
+                result => return result,
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+async fn watch() -> anyhow::Result<KobelWayfireEvents> {
+    KobelWayfireClient::connect().await?.watch(None).await
+}
+
+/// Forwards compositor events, reconnecting whenever the compositor goes away.
+/// `WayfireConnected` is sent after every (re)connect, so that consumers can
+/// query the state they missed.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct WayfireWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<WayfireWatcher>(),
+        iced::stream::channel(16, |mut output| async move {
+            if socket_path().is_none() {
+                log::warn!("WAYFIRE_SOCKET is not set, not running under Wayfire?");
+                return;
+            }
+
+            let mut delay = RECONNECT_DELAY_MIN;
+
+            loop {
+                match watch().await {
+                    Ok(mut events) => {
+                        log::info!("Connected to Wayfire");
+                        delay = RECONNECT_DELAY_MIN;
+
+                        if output.send(KobelRootMessage::WayfireConnected).await.is_err() {
+                            return;
+                        }
+
+                        let error = loop {
+                            match events.next().await {
+                                Ok(event) => {
+                                    if output.send(KobelRootMessage::WayfireEvent(Box::new(event))).await.is_err() {
+                                        return;
+                                    }
+                                },
+                                Err(e) => break e,
+                            }
+                        };
+
+                        log::warn!("Lost the connection to Wayfire: {:#}", error);
+
+                        if output.send(KobelRootMessage::WayfireDisconnected).await.is_err() {
+                            return;
+                        }
+                    },
+                    Err(e) => {
+                        log::debug!("Failed to watch Wayfire events: {:#}", e);
+                    },
+                }
+
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+            }
+        }),
+    )
+}
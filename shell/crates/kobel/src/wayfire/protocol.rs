@@ -0,0 +1,315 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A method call understood by the compositor, together with the shape of its reply.
+pub trait KobelWayfireRequest: Serialize {
+    const METHOD: &'static str;
+
+    type Response: DeserializeOwned;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct KobelWayfirePoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct KobelWayfireGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl KobelWayfireGeometry {
+    /// Whether the two share any area. Touching edges do not count.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// The workspace a workspace set currently shows, within its grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct KobelWayfireWorkspace {
+    pub x: i32,
+    pub y: i32,
+    pub grid_width: i32,
+    pub grid_height: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct KobelWayfireView {
+    pub id: u64,
+    pub pid: i32,
+    pub title: String,
+    pub app_id: String,
+    pub geometry: KobelWayfireGeometry,
+    pub bbox: KobelWayfireGeometry,
+    /// -1 when the view is not on an output.
+    pub output_id: i64,
+    pub output_name: String,
+    /// -1 when the view is not in a workspace set.
+    pub wset_index: i64,
+    pub last_focus_timestamp: i64,
+    /// -1 for views without a parent.
+    pub parent: i64,
+    pub role: String,
+    pub layer: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub mapped: bool,
+    pub focusable: bool,
+    pub minimized: bool,
+    pub activated: bool,
+    pub sticky: bool,
+    pub fullscreen: bool,
+    pub tiled_edges: u32,
+}
+
+impl KobelWayfireView {
+    /// Whether the view is a regular application window, as opposed to a
+    /// panel, background or popup.
+    pub fn is_toplevel(&self) -> bool {
+        self.kind == "toplevel"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct KobelWayfireOutput {
+    pub id: u64,
+    pub name: String,
+    pub geometry: KobelWayfireGeometry,
+    pub workarea: KobelWayfireGeometry,
+    pub wset_index: u64,
+    pub workspace: KobelWayfireWorkspace,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct KobelWayfireWorkspaceSet {
+    pub index: u64,
+    pub name: String,
+    /// -1 when the set is not shown on any output.
+    pub output_id: i64,
+    pub output_name: String,
+    pub workspace: KobelWayfireWorkspace,
+}
+
+/// Reply of methods that only report success.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KobelWayfireOk {
+    pub result: String,
+}
+
+/// Something that happened in the compositor, sent to clients that asked to
+/// watch for it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum KobelWayfireEvent {
+    ViewMapped {
+        view: KobelWayfireView,
+    },
+    ViewUnmapped {
+        view: KobelWayfireView,
+    },
+    /// `view` is `None` when nothing has keyboard focus.
+    ViewFocused {
+        view: Option<KobelWayfireView>,
+    },
+    ViewTitleChanged {
+        view: KobelWayfireView,
+    },
+    ViewAppIdChanged {
+        view: KobelWayfireView,
+    },
+    ViewMinimized {
+        view: KobelWayfireView,
+    },
+    /// The view moved or was resized. Geometry is relative to its output.
+    #[serde(rename_all = "kebab-case")]
+    ViewGeometryChanged {
+        view: KobelWayfireView,
+        old_geometry: KobelWayfireGeometry,
+    },
+    ViewFullscreen {
+        view: KobelWayfireView,
+    },
+    ViewSticky {
+        view: KobelWayfireView,
+    },
+    ViewSetOutput {
+        view: KobelWayfireView,
+        output: Option<KobelWayfireOutput>,
+    },
+    ViewWsetChanged {
+        view: KobelWayfireView,
+    },
+    ViewWorkspaceChanged {
+        view: KobelWayfireView,
+        from: KobelWayfirePoint,
+        to: KobelWayfirePoint,
+    },
+    #[serde(rename_all = "kebab-case")]
+    WsetWorkspaceChanged {
+        previous_workspace: KobelWayfirePoint,
+        new_workspace: KobelWayfirePoint,
+        output_data: Option<KobelWayfireOutput>,
+        wset_data: Option<KobelWayfireWorkspaceSet>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    OutputWsetChanged {
+        output_data: Option<KobelWayfireOutput>,
+        new_wset_data: Option<KobelWayfireWorkspaceSet>,
+    },
+    OutputAdded {
+        output: KobelWayfireOutput,
+    },
+    OutputRemoved {
+        output: KobelWayfireOutput,
+    },
+    OutputGainFocus {
+        output: Option<KobelWayfireOutput>,
+    },
+    /// Events the shell does not handle, such as plugins being activated.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KobelListViews {}
+
+impl KobelWayfireRequest for KobelListViews {
+    const METHOD: &'static str = "window-rules/list-views";
+
+    type Response = Vec<KobelWayfireView>;
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KobelListOutputs {}
+
+impl KobelWayfireRequest for KobelListOutputs {
+    const METHOD: &'static str = "window-rules/list-outputs";
+
+    type Response = Vec<KobelWayfireOutput>;
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KobelListWorkspaceSets {}
+
+impl KobelWayfireRequest for KobelListWorkspaceSets {
+    const METHOD: &'static str = "window-rules/list-wsets";
+
+    type Response = Vec<KobelWayfireWorkspaceSet>;
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KobelGetFocusedView {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct KobelFocusedView {
+    pub info: Option<KobelWayfireView>,
+}
+
+impl KobelWayfireRequest for KobelGetFocusedView {
+    const METHOD: &'static str = "window-rules/get-focused-view";
+
+    type Response = KobelFocusedView;
+}
+
+/// Switches an output to the workspace at `x`, `y`, optionally taking a view along.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct KobelSetWorkspace {
+    pub x: u32,
+    pub y: u32,
+    pub output_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_id: Option<u64>,
+}
+
+impl KobelWayfireRequest for KobelSetWorkspace {
+    const METHOD: &'static str = "vswitch/set-workspace";
+
+    type Response = KobelWayfireOk;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KobelSetMinimized {
+    pub view_id: u64,
+    #[serde(rename = "state")]
+    pub minimized: bool,
+}
+
+impl KobelWayfireRequest for KobelSetMinimized {
+    const METHOD: &'static str = "wm-actions/set-minimized";
+
+    type Response = KobelWayfireOk;
+}
+
+/// Gives a toplevel view keyboard focus, bringing it back if it is minimized.
+#[derive(Debug, Clone, Serialize)]
+pub struct KobelFocusView {
+    pub id: u64,
+}
+
+impl KobelWayfireRequest for KobelFocusView {
+    const METHOD: &'static str = "window-rules/focus-view";
+
+    type Response = KobelWayfireOk;
+}
+
+/// Asks a view to close, which it may refuse or delay.
+#[derive(Debug, Clone, Serialize)]
+pub struct KobelCloseView {
+    pub id: u64,
+}
+
+impl KobelWayfireRequest for KobelCloseView {
+    const METHOD: &'static str = "window-rules/close-view";
+
+    type Response = KobelWayfireOk;
+}
+
+/// Reads an option from the compositor's config, such as `core/vwidth`.
+#[derive(Debug, Clone, Serialize)]
+pub struct KobelGetConfigOption {
+    pub option: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct KobelConfigOption {
+    /// A string for plain options, or a list of tuples for compound ones.
+    pub value: serde_json::Value,
+    pub default: Option<String>,
+}
+
+impl KobelConfigOption {
+    pub fn as_str(&self) -> Option<&str> {
+        self.value.as_str()
+    }
+}
+
+impl KobelWayfireRequest for KobelGetConfigOption {
+    const METHOD: &'static str = "wayfire/get-config-option";
+
+    type Response = KobelConfigOption;
+}
+
+/// Turns the connection into an event stream. Without a list of event names,
+/// every event is sent.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KobelWatchEvents {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+}
+
+impl KobelWayfireRequest for KobelWatchEvents {
+    const METHOD: &'static str = "window-rules/events/watch";
+
+    type Response = KobelWayfireOk;
+}
@@ -0,0 +1,225 @@
+use crate::{apps::KobelAppDatabase, wayfire::protocol::{KobelWayfireEvent, KobelWayfireGeometry, KobelWayfireView}};
+
+/// The compositor's views as last listed, kept up to date from events so
+/// panels can look at window geometry without asking the compositor.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KobelWayfireViews {
+    views: Vec<KobelWayfireView>,
+}
+
+impl KobelWayfireViews {
+    pub fn new(views: Vec<KobelWayfireView>) -> Self {
+        Self {
+            views: views.into_iter().filter(|view| view.mapped).collect(),
+        }
+    }
+
+    /// Every mapped view, toplevel or not.
+    pub fn list(&self) -> &[KobelWayfireView] {
+        &self.views
+    }
+
+    /// Applies an event that carries a view, returning whether anything
+    /// changed. Events about workspaces change where views are without
+    /// saying so, and need the views to be listed again.
+    pub fn apply(&mut self, event: &KobelWayfireEvent) -> bool {
+        let view = match event {
+            KobelWayfireEvent::ViewUnmapped { view } => {
+                let len = self.views.len();
+                self.views.retain(|v| v.id != view.id);
+                return self.views.len() != len;
+            },
+            KobelWayfireEvent::ViewFocused { view: None } => return false,
+            KobelWayfireEvent::ViewMapped { view }
+            | KobelWayfireEvent::ViewFocused { view: Some(view) }
+            | KobelWayfireEvent::ViewTitleChanged { view }
+            | KobelWayfireEvent::ViewAppIdChanged { view }
+            | KobelWayfireEvent::ViewMinimized { view }
+            | KobelWayfireEvent::ViewGeometryChanged { view, .. }
+            | KobelWayfireEvent::ViewFullscreen { view }
+            | KobelWayfireEvent::ViewSticky { view }
+            | KobelWayfireEvent::ViewSetOutput { view, .. }
+            | KobelWayfireEvent::ViewWsetChanged { view }
+            | KobelWayfireEvent::ViewWorkspaceChanged { view, .. } => view,
+            _ => return false,
+        };
+
+        let existing = self.views.iter().position(|v| v.id == view.id);
+
+        match existing {
+            _ if !view.mapped => return existing.map(|index| self.views.remove(index)).is_some(),
+            Some(index) if self.views[index] == *view => return false,
+            Some(index) => self.views[index] = view.clone(),
+            None => self.views.push(view.clone()),
+        }
+
+        true
+    }
+
+    /// Toplevel views of the app with this ID in the dock, either its desktop
+    /// file ID or app ID, in the order they were opened in.
+    pub fn of_app(&self, app_id: &str, apps: &KobelAppDatabase) -> Vec<KobelWayfireView> {
+        let mut views: Vec<KobelWayfireView> = self.views.iter()
+            .filter(|view| view.is_toplevel() && !view.app_id.is_empty())
+            .filter(|view| apps.id_for_app_id(&view.app_id) == app_id)
+            .cloned()
+            .collect();
+        views.sort_by_key(|view| view.id);
+
+        views
+    }
+
+    /// Whether a window that is shown on the output named `output_name`
+    /// covers part of `area`, given relative to that output.
+    pub fn any_overlapping(&self, output_name: &str, area: &KobelWayfireGeometry) -> bool {
+        self.views.iter()
+            .filter(|view| view.is_toplevel() && !view.minimized && view.output_name == output_name)
+            .any(|view| view.geometry.intersects(area))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayfire::{mock::{next_event, KobelMockWayfire}, protocol::{KobelCloseView, KobelFocusView, KobelListViews}, KobelWayfire, KobelWayfireClient, KobelWayfireEvents};
+
+    fn view(id: u64, x: i32, y: i32) -> KobelWayfireView {
+        KobelWayfireView {
+            id,
+            kind: "toplevel".to_string(),
+            output_name: "DP-1".to_string(),
+            geometry: KobelWayfireGeometry { x, y, width: 800, height: 600 },
+            mapped: true,
+            ..Default::default()
+        }
+    }
+
+    fn app_view(id: u64, app_id: &str) -> KobelWayfireView {
+        KobelWayfireView {
+            app_id: app_id.to_string(),
+            ..view(id, 0, 0)
+        }
+    }
+
+    fn geometry(x: i32, y: i32) -> KobelWayfireGeometry {
+        KobelWayfireGeometry { x, y, width: 800, height: 600 }
+    }
+
+    /// Views as listed from `mock`, along with its events to apply.
+    async fn watch(mock: &KobelMockWayfire) -> (KobelWayfireViews, KobelWayfireEvents) {
+        let mut client = KobelWayfireClient::connect_to(mock.path()).await.unwrap();
+        let views = KobelWayfireViews::new(client.call(&KobelListViews {}).await.unwrap());
+
+        (views, client.watch(None).await.unwrap())
+    }
+
+    // A dock along the bottom of a 1920x1080 output
+    static DOCK: KobelWayfireGeometry = KobelWayfireGeometry { x: 8, y: 988, width: 1904, height: 84 };
+
+    #[test]
+    fn follows_view_events() {
+        let mut views = KobelWayfireViews::new(vec![view(1, 0, 0), KobelWayfireView { mapped: false, ..view(2, 0, 0) }]);
+        assert_eq!(views.list().len(), 1);
+
+        assert!(views.apply(&KobelWayfireEvent::ViewMapped { view: view(3, 100, 100) }));
+        assert!(views.apply(&KobelWayfireEvent::ViewGeometryChanged {
+            view: view(3, 100, 400),
+            old_geometry: view(3, 100, 100).geometry,
+        }));
+        assert!(!views.apply(&KobelWayfireEvent::ViewTitleChanged { view: view(3, 100, 400) }));
+        assert_eq!(views.list()[1].geometry.y, 400);
+
+        assert!(views.apply(&KobelWayfireEvent::ViewUnmapped { view: view(1, 0, 0) }));
+        assert!(!views.apply(&KobelWayfireEvent::ViewUnmapped { view: view(1, 0, 0) }));
+        assert_eq!(views.list().iter().map(|v| v.id).collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn finds_windows_over_an_area() {
+        let mut views = KobelWayfireViews::new(vec![view(1, 0, 0)]);
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+
+        // Touching the dock's top edge is not covering it
+        views.apply(&KobelWayfireEvent::ViewGeometryChanged { view: view(1, 0, 388), old_geometry: Default::default() });
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+
+        views.apply(&KobelWayfireEvent::ViewGeometryChanged { view: view(1, 0, 389), old_geometry: Default::default() });
+        assert!(views.any_overlapping("DP-1", &DOCK));
+        assert!(!views.any_overlapping("HDMI-A-1", &DOCK));
+
+        // Minimized windows and panels do not count
+        views.apply(&KobelWayfireEvent::ViewMinimized { view: KobelWayfireView { minimized: true, ..view(1, 0, 389) } });
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+
+        views.apply(&KobelWayfireEvent::ViewMapped { view: KobelWayfireView { kind: "panel".to_string(), ..view(2, 0, 1000) } });
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+    }
+
+    #[tokio::test]
+    async fn follows_the_compositor() {
+        let mock = KobelMockWayfire::builder().view(view(1, 0, 0)).start();
+        let (mut views, mut events) = watch(&mock).await;
+
+        mock.emit_view_mapped(view(2, 100, 100));
+        assert!(views.apply(&next_event(&mut events).await));
+
+        mock.emit_view_geometry_changed(2, geometry(100, 400));
+        assert!(views.apply(&next_event(&mut events).await));
+
+        mock.emit_view_unmapped(1);
+        assert!(views.apply(&next_event(&mut events).await));
+
+        assert_eq!(views.list().iter().map(|v| (v.id, v.geometry.y)).collect::<Vec<_>>(), [(2, 400)]);
+    }
+
+    #[tokio::test]
+    async fn intellihide_follows_windows_over_the_dock() {
+        let mock = KobelMockWayfire::builder().view(view(1, 0, 0)).start();
+        let (mut views, mut events) = watch(&mock).await;
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+
+        mock.emit_view_geometry_changed(1, geometry(0, 389));
+        views.apply(&next_event(&mut events).await);
+        assert!(views.any_overlapping("DP-1", &DOCK));
+
+        mock.emit_view_mapped(view(2, 600, 600));
+        views.apply(&next_event(&mut events).await);
+        mock.emit_view_unmapped(1);
+        views.apply(&next_event(&mut events).await);
+        assert!(views.any_overlapping("DP-1", &DOCK));
+
+        mock.emit_view_unmapped(2);
+        views.apply(&next_event(&mut events).await);
+        assert!(!views.any_overlapping("DP-1", &DOCK));
+    }
+
+    #[tokio::test]
+    async fn previews_focus_and_close_windows() {
+        let apps = KobelAppDatabase::load_from(vec![], None, vec![]);
+        let mock = KobelMockWayfire::builder()
+            .view(app_view(1, "firefox"))
+            .view(app_view(2, "foot"))
+            .view(app_view(3, "firefox"))
+            .start();
+        let (mut views, mut events) = watch(&mock).await;
+        let wayfire = KobelWayfire::with_socket(mock.path().to_path_buf());
+
+        let ids = |views: &KobelWayfireViews| views.of_app("firefox", &apps).iter().map(|v| v.id).collect::<Vec<_>>();
+        assert_eq!(ids(&views), [1, 3]);
+
+        wayfire.call(&KobelFocusView { id: 3 }).await.unwrap();
+        views.apply(&next_event(&mut events).await);
+        assert!(views.of_app("firefox", &apps)[1].activated);
+
+        wayfire.call(&KobelCloseView { id: 1 }).await.unwrap();
+        views.apply(&next_event(&mut events).await);
+        assert_eq!(ids(&views), [3]);
+
+        // Gone windows cannot be closed again
+        assert!(wayfire.call(&KobelCloseView { id: 1 }).await.is_err());
+
+        let requests: Vec<_> = mock.requests().into_iter().filter(|(method, _)| method.starts_with("window-rules/")).collect();
+        assert!(requests.contains(&("window-rules/focus-view".to_string(), serde_json::json!({ "id": 3 }))));
+        assert!(requests.contains(&("window-rules/close-view".to_string(), serde_json::json!({ "id": 1 }))));
+    }
+}
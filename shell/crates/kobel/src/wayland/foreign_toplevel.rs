@@ -0,0 +1,416 @@
+//! The windows open on the compositor, as wlr-foreign-toplevel-management
+//! lists them, so the dock can show which apps are running and switch between
+//! their windows.
+
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, RwLock, RwLockReadGuard}};
+
+use anyhow::Context;
+use iced::{futures::SinkExt, Subscription};
+use tokio::sync::mpsc;
+use wayland_client::{event_created_child, globals::{registry_queue_init, GlobalListContents}, protocol::{wl_registry::WlRegistry, wl_seat::WlSeat}, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1}, zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1}};
+
+use crate::KobelRootMessage;
+
+static NEXT_TOPLEVEL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a toplevel for as long as the shell runs. Protocol object IDs
+/// are reused once a window closes, so they are not used for this.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KobelToplevelId(u64);
+
+impl KobelToplevelId {
+    fn next() -> Self {
+        Self(NEXT_TOPLEVEL_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KobelToplevel {
+    pub id: KobelToplevelId,
+    /// The Wayland app ID, or the X11 class for Xwayland windows. Empty until
+    /// the client sets one.
+    pub app_id: String,
+    pub title: String,
+    pub activated: bool,
+    pub minimized: bool,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// Grows every time a toplevel gains focus, so an app's windows can be
+    /// ordered by when they were last used. Zero if it never had focus.
+    pub last_activated: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum KobelToplevelEvent {
+    /// A toplevel opened or changed. Sent once the compositor has sent all of
+    /// its state, never halfway through.
+    Updated(KobelToplevel),
+    Closed(KobelToplevelId),
+}
+
+/// What a click on an app in the dock does with its windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KobelToplevelAction {
+    /// The app has no windows.
+    Launch,
+    Activate(KobelToplevelId),
+    Minimize(KobelToplevelId),
+}
+
+impl KobelToplevelAction {
+    /// Decides what clicking an app with `windows` does. A focused window is
+    /// minimized if it is the app's only one, otherwise the next window is
+    /// focused, so repeated clicks cycle through all of them. If none has
+    /// focus, the most recently used one gets it.
+    pub fn for_windows(windows: &[KobelToplevel]) -> Self {
+        let mut windows: Vec<_> = windows.iter().collect();
+        // Cycle in the order the windows opened in, which never changes
+        windows.sort_by_key(|window| window.id);
+
+        if let Some(focused) = windows.iter().position(|window| window.activated) {
+            return match windows.len() {
+                1 => Self::Minimize(windows[0].id),
+                len => Self::Activate(windows[(focused + 1) % len].id),
+            };
+        }
+
+        windows.iter()
+            .rev()
+            .max_by_key(|window| window.last_activated)
+            .map_or(Self::Launch, |window| Self::Activate(window.id))
+    }
+}
+
+#[derive(Debug)]
+struct KobelToplevelConnection {
+    connection: Connection,
+    /// Needed to activate windows. Missing on a seatless compositor.
+    seat: Option<WlSeat>,
+    handles: HashMap<KobelToplevelId, ZwlrForeignToplevelHandleV1>,
+}
+
+/// The toplevels open right now, and a way to ask the compositor to focus,
+/// minimize or close them. Kept up to date by [`subscription`].
+#[derive(Debug, Default)]
+pub struct KobelToplevels {
+    toplevels: RwLock<Vec<KobelToplevel>>,
+    activations: AtomicU64,
+    /// Set while the subscription is connected. Wayland proxies can make
+    /// requests from any thread, so this is shared with the shell.
+    connection: Mutex<Option<KobelToplevelConnection>>,
+}
+
+impl KobelToplevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every toplevel, in the order they opened in.
+    pub fn list(&self) -> RwLockReadGuard<'_, Vec<KobelToplevel>> {
+        self.toplevels.read().unwrap()
+    }
+
+    pub fn apply(&self, event: KobelToplevelEvent) {
+        let mut toplevels = self.toplevels.write().unwrap();
+
+        match event {
+            KobelToplevelEvent::Updated(mut toplevel) => {
+                let existing = toplevels.iter().position(|t| t.id == toplevel.id);
+                let was_activated = existing.is_some_and(|index| toplevels[index].activated);
+
+                toplevel.last_activated = match existing {
+                    _ if toplevel.activated && !was_activated => self.activations.fetch_add(1, Ordering::Relaxed) + 1,
+                    Some(index) => toplevels[index].last_activated,
+                    None => 0,
+                };
+
+                match existing {
+                    Some(index) => toplevels[index] = toplevel,
+                    None => toplevels.push(toplevel),
+                }
+            },
+            KobelToplevelEvent::Closed(id) => {
+                toplevels.retain(|t| t.id != id);
+            },
+        }
+    }
+
+    pub fn activate(&self, id: KobelToplevelId) {
+        self.request(id, |handle, seat| match seat {
+            Some(seat) => handle.activate(seat),
+            None => log::warn!("Cannot activate windows without a seat"),
+        });
+    }
+
+    pub fn set_minimized(&self, id: KobelToplevelId, minimized: bool) {
+        self.request(id, |handle, _| {
+            if minimized {
+                handle.set_minimized();
+            } else {
+                handle.unset_minimized();
+            }
+        });
+    }
+
+    /// Asks the window to close, which it may refuse, for example to ask
+    /// about unsaved changes.
+    pub fn close(&self, id: KobelToplevelId) {
+        self.request(id, |handle, _| handle.close());
+    }
+
+    fn request(&self, id: KobelToplevelId, request: impl FnOnce(&ZwlrForeignToplevelHandleV1, Option<&WlSeat>)) {
+        let connection = self.connection.lock().unwrap();
+        let Some(connection) = connection.as_ref() else {
+            log::debug!("Not connected to the compositor's toplevel list");
+            return;
+        };
+        let Some(handle) = connection.handles.get(&id) else {
+            log::debug!("Toplevel {:?} already closed", id);
+            return;
+        };
+
+        request(handle, connection.seat.as_ref());
+
+        // The event queue only flushes when it is woken up, so send it now
+        if let Err(e) = connection.connection.flush() {
+            log::warn!("Failed to send a toplevel request: {}", e);
+        }
+    }
+}
+
+/// Keeps `toplevels` up to date and sends [`KobelRootMessage::Toplevel`] for
+/// every change. Wayland dispatching blocks, so it runs on a thread of its own
+/// with its own connection.
+pub fn subscription(toplevels: Arc<KobelToplevels>) -> Subscription<KobelRootMessage> {
+    struct ToplevelWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<ToplevelWatcher>(),
+        iced::stream::channel(16, |mut output| async move {
+            let (sender, mut events) = mpsc::unbounded_channel();
+
+            let thread = std::thread::Builder::new()
+                .name("kobel-toplevels".to_string())
+                .spawn(move || {
+                    if let Err(e) = run(&toplevels, sender) {
+                        log::warn!("Running apps are not shown: {:#}", e);
+                    }
+
+                    *toplevels.connection.lock().unwrap() = None;
+                });
+
+            if let Err(e) = thread {
+                log::error!("Failed to start watching toplevels: {}", e);
+                return;
+            }
+
+            while let Some(event) = events.recv().await {
+                if output.send(KobelRootMessage::Toplevel(event)).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
+
+struct KobelToplevelClient {
+    toplevels: Arc<KobelToplevels>,
+    /// State received since the last `done` event.
+    pending: HashMap<KobelToplevelId, KobelToplevel>,
+    events: mpsc::UnboundedSender<KobelToplevelEvent>,
+    finished: bool,
+}
+
+fn run(toplevels: &Arc<KobelToplevels>, events: mpsc::UnboundedSender<KobelToplevelEvent>) -> anyhow::Result<()> {
+    let connection = Connection::connect_to_env()
+        .context("Failed to connect to the compositor")?;
+    let (globals, mut queue) = registry_queue_init::<KobelToplevelClient>(&connection)
+        .context("Failed to list the compositor's globals")?;
+    let qh = queue.handle();
+
+    let _manager: ZwlrForeignToplevelManagerV1 = globals.bind(&qh, 1..=3, ())
+        .context("The compositor does not support wlr-foreign-toplevel-management")?;
+    let seat: Option<WlSeat> = globals.bind(&qh, 1..=1, ()).ok();
+
+    *toplevels.connection.lock().unwrap() = Some(KobelToplevelConnection {
+        connection,
+        seat,
+        handles: HashMap::new(),
+    });
+
+    let mut client = KobelToplevelClient {
+        toplevels: toplevels.clone(),
+        pending: HashMap::new(),
+        events,
+        finished: false,
+    };
+
+    while !client.finished {
+        queue.blocking_dispatch(&mut client)
+            .context("Lost the connection to the compositor")?;
+    }
+
+    Ok(())
+}
+
+impl KobelToplevelClient {
+    fn send(&mut self, event: KobelToplevelEvent) {
+        // The shell is gone, so stop once the current events are dispatched
+        if self.events.send(event).is_err() {
+            self.finished = true;
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for KobelToplevelClient {
+    fn event(_: &mut Self, _: &WlRegistry, _: <WlRegistry as Proxy>::Event, _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<WlSeat, ()> for KobelToplevelClient {
+    fn event(_: &mut Self, _: &WlSeat, _: <WlSeat as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for KobelToplevelClient {
+    fn event(
+        client: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                let Some(&id) = toplevel.data::<KobelToplevelId>() else {
+                    return;
+                };
+
+                client.pending.insert(id, KobelToplevel { id, ..Default::default() });
+                if let Some(connection) = client.toplevels.connection.lock().unwrap().as_mut() {
+                    connection.handles.insert(id, toplevel);
+                }
+            },
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                log::info!("The compositor stopped sending toplevels");
+                client.finished = true;
+            },
+            _ => {},
+        }
+    }
+
+    event_created_child!(KobelToplevelClient, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, KobelToplevelId::next()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, KobelToplevelId> for KobelToplevelClient {
+    fn event(
+        client: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        id: &KobelToplevelId,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::{Event, State};
+
+        let id = *id;
+
+        if let Event::Closed = event {
+            client.pending.remove(&id);
+            if let Some(connection) = client.toplevels.connection.lock().unwrap().as_mut() {
+                connection.handles.remove(&id);
+            }
+            handle.destroy();
+
+            client.send(KobelToplevelEvent::Closed(id));
+            return;
+        }
+
+        let Some(toplevel) = client.pending.get_mut(&id) else {
+            return;
+        };
+
+        match event {
+            Event::Title { title } => toplevel.title = title,
+            Event::AppId { app_id } => toplevel.app_id = app_id,
+            Event::State { state } => {
+                let states: Vec<_> = state.chunks_exact(4)
+                    .filter_map(|bytes| State::try_from(u32::from_ne_bytes(bytes.try_into().unwrap())).ok())
+                    .collect();
+
+                toplevel.activated = states.contains(&State::Activated);
+                toplevel.minimized = states.contains(&State::Minimized);
+                toplevel.maximized = states.contains(&State::Maximized);
+                toplevel.fullscreen = states.contains(&State::Fullscreen);
+            },
+            Event::Done => {
+                let toplevel = toplevel.clone();
+                client.send(KobelToplevelEvent::Updated(toplevel));
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u64, activated: bool, last_activated: u64) -> KobelToplevel {
+        KobelToplevel {
+            id: KobelToplevelId(id),
+            app_id: "org.gnome.Nautilus".to_string(),
+            activated,
+            last_activated,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clicks_launch_focus_or_minimize() {
+        assert_eq!(KobelToplevelAction::for_windows(&[]), KobelToplevelAction::Launch);
+        assert_eq!(
+            KobelToplevelAction::for_windows(&[window(1, false, 0)]),
+            KobelToplevelAction::Activate(KobelToplevelId(1))
+        );
+        assert_eq!(
+            KobelToplevelAction::for_windows(&[window(1, true, 3)]),
+            KobelToplevelAction::Minimize(KobelToplevelId(1))
+        );
+    }
+
+    #[test]
+    fn clicks_cycle_through_windows() {
+        // Focus moves on in opening order, whatever order the windows come in
+        let windows = [window(5, false, 2), window(2, true, 4), window(9, false, 0)];
+        assert_eq!(KobelToplevelAction::for_windows(&windows), KobelToplevelAction::Activate(KobelToplevelId(5)));
+
+        let windows = [window(2, false, 4), window(5, false, 2), window(9, true, 6)];
+        assert_eq!(KobelToplevelAction::for_windows(&windows), KobelToplevelAction::Activate(KobelToplevelId(2)));
+
+        // Without focus, the window used last comes back
+        let windows = [window(2, false, 4), window(5, false, 7), window(9, false, 0)];
+        assert_eq!(KobelToplevelAction::for_windows(&windows), KobelToplevelAction::Activate(KobelToplevelId(5)));
+    }
+
+    #[test]
+    fn orders_windows_by_focus() {
+        let toplevels = KobelToplevels::new();
+        let update = |id, activated| toplevels.apply(KobelToplevelEvent::Updated(window(id, activated, 0)));
+
+        update(1, true);
+        update(2, false);
+        update(1, false);
+        update(2, true);
+        // Changing the title of a focused window does not count as focusing it again
+        update(2, true);
+
+        let last_activated: Vec<_> = toplevels.list().iter().map(|t| t.last_activated).collect();
+        assert_eq!(last_activated, [1, 2]);
+
+        toplevels.apply(KobelToplevelEvent::Closed(KobelToplevelId(1)));
+        assert_eq!(toplevels.list().len(), 1);
+    }
+}
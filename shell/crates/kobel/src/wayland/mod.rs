@@ -0,0 +1,3 @@
+pub mod foreign_toplevel;
+pub mod screencopy;
+pub mod wayfire_shell;
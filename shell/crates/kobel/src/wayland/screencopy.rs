@@ -0,0 +1,405 @@
+//! Window thumbnails, captured with wlr-screencopy from the part of the output
+//! a window covers. Wayfire cannot capture a single window, so whatever lies
+//! on top of the window shows up in its thumbnail too.
+
+use std::{collections::HashMap, fs::{File, OpenOptions}, os::{fd::AsFd, unix::fs::{FileExt, OpenOptionsExt}}, path::PathBuf, sync::atomic::{AtomicU64, Ordering}};
+
+use anyhow::Context;
+use iced::widget::image;
+use wayland_client::{globals::{registry_queue_init, GlobalListContents}, protocol::{wl_buffer::WlBuffer, wl_output::{self, WlOutput}, wl_registry::{self, WlRegistry}, wl_shm::{self, WlShm}, wl_shm_pool::WlShmPool}, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols_wlr::screencopy::v1::client::{zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1}, zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1};
+
+static NEXT_BUFFER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A rectangle of an output to capture, in logical pixels relative to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KobelCaptureRegion {
+    pub output_name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A captured image as rows of RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KobelThumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl KobelThumbnail {
+    pub fn to_handle(self) -> image::Handle {
+        image::Handle::from_rgba(self.width, self.height, self.pixels)
+    }
+}
+
+/// A connection of its own to the compositor for capturing regions of its
+/// outputs. It is kept across captures, so refreshing thumbnails does not
+/// connect anew every time.
+#[derive(Debug)]
+pub struct KobelScreencopy {
+    queue: EventQueue<KobelCaptureClient>,
+    manager: ZwlrScreencopyManagerV1,
+    client: KobelCaptureClient,
+}
+
+impl KobelScreencopy {
+    pub fn connect() -> anyhow::Result<Self> {
+        let connection = Connection::connect_to_env()
+            .context("Failed to connect to the compositor")?;
+        let (globals, mut queue) = registry_queue_init::<KobelCaptureClient>(&connection)
+            .context("Failed to list the compositor's globals")?;
+        let qh = queue.handle();
+
+        let manager: ZwlrScreencopyManagerV1 = globals.bind(&qh, 1..=3, ())
+            .context("The compositor does not support wlr-screencopy")?;
+        let shm: WlShm = globals.bind(&qh, 1..=1, ())
+            .context("The compositor does not support wl_shm")?;
+
+        let mut client = KobelCaptureClient {
+            shm,
+            outputs: HashMap::new(),
+            frames: HashMap::new(),
+        };
+
+        let outputs = globals.contents().with_list(|list| {
+            list.iter()
+                .filter(|global| global.interface == WlOutput::interface().name)
+                .map(|global| (global.name, global.version))
+                .collect::<Vec<_>>()
+        });
+        for (name, version) in outputs {
+            client.bind_output(globals.registry(), name, version, &qh);
+        }
+
+        // Output names arrive in reply to binding them
+        queue.roundtrip(&mut client)
+            .context("Lost the connection to the compositor")?;
+
+        Ok(Self { queue, manager, client })
+    }
+
+    /// Captures every region, scaled down to fit in `max_width` by
+    /// `max_height`. Regions on outputs that are gone, or that the compositor
+    /// refuses to copy, come back as `None`. Blocks until every copy is done.
+    pub fn capture(&mut self, regions: &[KobelCaptureRegion], max_width: u32, max_height: u32) -> anyhow::Result<Vec<Option<KobelThumbnail>>> {
+        let qh = self.queue.handle();
+
+        // Outputs that came or went since the last capture
+        self.queue.dispatch_pending(&mut self.client)
+            .context("Lost the connection to the compositor")?;
+
+        self.client.frames.clear();
+        for (index, region) in regions.iter().enumerate() {
+            let output = self.client.outputs.values().find(|(_, name)| name.as_deref() == Some(region.output_name.as_str()));
+
+            if let Some((output, _)) = output
+                && region.width > 0
+                && region.height > 0
+            {
+                self.manager.capture_output_region(0, output, region.x, region.y, region.width, region.height, &qh, index);
+                self.client.frames.insert(index, KobelFrame::default());
+            }
+        }
+
+        while self.client.frames.values().any(|frame| !frame.finished) {
+            self.queue.blocking_dispatch(&mut self.client)
+                .context("Lost the connection to the compositor")?;
+        }
+
+        Ok((0..regions.len())
+            .map(|index| {
+                let image = self.client.frames.remove(&index)?.image?;
+                Some(scale_to_fit(image, max_width, max_height))
+            })
+            .collect())
+    }
+}
+
+impl Drop for KobelScreencopy {
+    fn drop(&mut self) {
+        for (output, _) in self.client.outputs.values() {
+            if output.version() >= 3 {
+                output.release();
+            }
+        }
+        self.manager.destroy();
+        let _ = self.queue.flush();
+    }
+}
+
+#[derive(Debug, Default)]
+struct KobelFrame {
+    buffer: Option<KobelShmBuffer>,
+    y_invert: bool,
+    image: Option<KobelThumbnail>,
+    finished: bool,
+}
+
+#[derive(Debug)]
+struct KobelShmBuffer {
+    file: File,
+    buffer: WlBuffer,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: wl_shm::Format,
+}
+
+#[derive(Debug)]
+struct KobelCaptureClient {
+    shm: WlShm,
+    /// By global name, with the connector name once it is known.
+    outputs: HashMap<u32, (WlOutput, Option<String>)>,
+    /// By the index of the region they capture.
+    frames: HashMap<usize, KobelFrame>,
+}
+
+// Backed by an unlinked file in the runtime directory, so nothing is left
+// behind if the shell dies
+fn shm_file(size: u64) -> anyhow::Result<File> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("kobel-screencopy-{}-{}", std::process::id(), NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed)));
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to create '{}'", path.display()))?;
+    std::fs::remove_file(&path)?;
+    file.set_len(size)?;
+
+    Ok(file)
+}
+
+impl KobelCaptureClient {
+    fn bind_output(&mut self, registry: &WlRegistry, name: u32, version: u32, qh: &QueueHandle<Self>) {
+        let output: WlOutput = registry.bind(name, version.min(4), qh, name);
+        self.outputs.insert(name, (output, None));
+    }
+
+    fn create_buffer(&self, width: u32, height: u32, stride: u32, format: wl_shm::Format, qh: &QueueHandle<Self>) -> anyhow::Result<KobelShmBuffer> {
+        let size = stride as u64 * height as u64;
+        let file = shm_file(size)?;
+
+        let pool = self.shm.create_pool(file.as_fd(), size.try_into()?, qh, ());
+        let buffer = pool.create_buffer(0, width as i32, height as i32, stride as i32, format, qh, ());
+        pool.destroy();
+
+        Ok(KobelShmBuffer { file, buffer, width, height, stride, format })
+    }
+
+    fn finish(&mut self, index: usize, frame: &ZwlrScreencopyFrameV1) {
+        frame.destroy();
+
+        if let Some(state) = self.frames.get_mut(&index) {
+            state.finished = true;
+            if let Some(buffer) = &state.buffer {
+                buffer.buffer.destroy();
+            }
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for KobelCaptureClient {
+    fn event(client: &mut Self, registry: &WlRegistry, event: wl_registry::Event, _: &GlobalListContents, _: &Connection, qh: &QueueHandle<Self>) {
+        match event {
+            wl_registry::Event::Global { name, interface, version } if interface == WlOutput::interface().name => {
+                client.bind_output(registry, name, version, qh);
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some((output, _)) = client.outputs.remove(&name)
+                    && output.version() >= 3
+                {
+                    output.release();
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Dispatch<WlOutput, u32> for KobelCaptureClient {
+    fn event(client: &mut Self, _: &WlOutput, event: wl_output::Event, name: &u32, _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_output::Event::Name { name: connector } = event
+            && let Some((_, output_name)) = client.outputs.get_mut(name)
+        {
+            *output_name = Some(connector);
+        }
+    }
+}
+
+impl Dispatch<WlShm, ()> for KobelCaptureClient {
+    fn event(_: &mut Self, _: &WlShm, _: wl_shm::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<WlShmPool, ()> for KobelCaptureClient {
+    fn event(_: &mut Self, _: &WlShmPool, _: <WlShmPool as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<WlBuffer, ()> for KobelCaptureClient {
+    fn event(_: &mut Self, _: &WlBuffer, _: <WlBuffer as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for KobelCaptureClient {
+    fn event(_: &mut Self, _: &ZwlrScreencopyManagerV1, _: <ZwlrScreencopyManagerV1 as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, usize> for KobelCaptureClient {
+    fn event(
+        client: &mut Self,
+        frame: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        index: &usize,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::{Event, Flags};
+
+        let index = *index;
+
+        match event {
+            // Only the 32-bit formats every compositor offers are read
+            Event::Buffer { format: WEnum::Value(format @ (wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888)), width, height, stride } => {
+                if client.frames.get(&index).is_none_or(|state| state.buffer.is_some()) {
+                    return;
+                }
+
+                match client.create_buffer(width, height, stride, format, qh) {
+                    Ok(buffer) => {
+                        // Before version 3 no more buffer types follow, so the copy starts right away
+                        if frame.version() < 3 {
+                            frame.copy(&buffer.buffer);
+                        }
+                        client.frames.get_mut(&index).unwrap().buffer = Some(buffer);
+                    },
+                    Err(e) => {
+                        log::warn!("Failed to create a screencopy buffer: {:#}", e);
+                        client.finish(index, frame);
+                    },
+                }
+            },
+            // Before version 3 this was the only buffer type offered, so nothing can be copied
+            Event::Buffer { .. } if frame.version() < 3 => {
+                log::debug!("No screencopy buffer format the shell can read");
+                client.finish(index, frame);
+            },
+            Event::BufferDone => {
+                match client.frames.get(&index).and_then(|state| state.buffer.as_ref()) {
+                    Some(buffer) => frame.copy(&buffer.buffer),
+                    None => {
+                        log::debug!("No screencopy buffer format the shell can read");
+                        client.finish(index, frame);
+                    },
+                }
+            },
+            Event::Flags { flags } => {
+                if let Some(state) = client.frames.get_mut(&index) {
+                    state.y_invert = matches!(flags, WEnum::Value(flags) if flags.contains(Flags::YInvert));
+                }
+            },
+            Event::Ready { .. } => {
+                if let Some(state) = client.frames.get_mut(&index)
+                    && let Some(buffer) = &state.buffer
+                {
+                    let mut data = vec![0; buffer.stride as usize * buffer.height as usize];
+
+                    match buffer.file.read_exact_at(&mut data, 0) {
+                        Ok(()) => state.image = Some(KobelThumbnail {
+                            width: buffer.width,
+                            height: buffer.height,
+                            pixels: to_rgba(&data, buffer.width, buffer.height, buffer.stride, state.y_invert, buffer.format == wl_shm::Format::Xrgb8888),
+                        }),
+                        Err(e) => log::warn!("Failed to read a screencopy buffer: {}", e),
+                    }
+                }
+
+                client.finish(index, frame);
+            },
+            Event::Failed => client.finish(index, frame),
+            _ => {},
+        }
+    }
+}
+
+/// Turns rows of little-endian ARGB or XRGB pixels, `stride` bytes apart,
+/// into tightly packed RGBA with the top row first.
+fn to_rgba(data: &[u8], width: u32, height: u32, stride: u32, y_invert: bool, opaque: bool) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+
+    for row in 0..height {
+        let row = if y_invert { height - 1 - row } else { row };
+        let start = (row * stride) as usize;
+
+        for bgra in data[start..start + width as usize * 4].chunks_exact(4) {
+            pixels.extend([bgra[2], bgra[1], bgra[0], if opaque { 255 } else { bgra[3] }]);
+        }
+    }
+
+    pixels
+}
+
+fn scale_to_fit(image: KobelThumbnail, max_width: u32, max_height: u32) -> KobelThumbnail {
+    let (width, height) = fit(image.width, image.height, max_width, max_height);
+    if (width, height) == (image.width, image.height) {
+        return image;
+    }
+
+    let Some(buffer) = ::image::RgbaImage::from_raw(image.width, image.height, image.pixels.clone()) else {
+        return image;
+    };
+    let scaled = ::image::imageops::thumbnail(&buffer, width, height);
+
+    KobelThumbnail {
+        width,
+        height,
+        pixels: scaled.into_raw(),
+    }
+}
+
+// The largest size with the same aspect ratio within the bounds, never larger
+// than the original
+fn fit(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+
+    let scale = (max_width as f32 / width as f32).min(max_height as f32 / height as f32);
+
+    (((width as f32 * scale).round() as u32).max(1), ((height as f32 * scale).round() as u32).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_rows_to_rgba() {
+        // Two pixels per row, blue and red, then padding up to the stride
+        let data = [
+            0xff, 0x00, 0x00, 0x80, 0x00, 0x00, 0xff, 0xff, 0xaa, 0xaa,
+            0x00, 0xff, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0xaa, 0xaa,
+        ];
+
+        assert_eq!(to_rgba(&data, 2, 2, 10, false, false), [
+            0x00, 0x00, 0xff, 0x80, 0xff, 0x00, 0x00, 0xff,
+            0x00, 0xff, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        assert_eq!(to_rgba(&data, 2, 2, 10, true, true), [
+            0x00, 0xff, 0x00, 0xff, 0x00, 0x00, 0x00, 0xff,
+            0x00, 0x00, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff,
+        ]);
+    }
+
+    #[test]
+    fn fits_within_bounds() {
+        assert_eq!(fit(1920, 1080, 240, 135), (240, 135));
+        assert_eq!(fit(1000, 2000, 240, 135), (68, 135));
+        assert_eq!(fit(100, 50, 240, 135), (100, 50));
+        assert_eq!(fit(10000, 1, 240, 135), (240, 1));
+    }
+}
@@ -0,0 +1,213 @@
+//! Edge hotspots from Wayfire's own shell protocol, which tell the dock when
+//! the pointer rests against the bottom of an output so it can slide back in.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use iced::{futures::SinkExt, Subscription};
+use tokio::sync::mpsc;
+use wayland_client::{globals::{registry_queue_init, GlobalListContents}, protocol::{wl_output::{self, WlOutput}, wl_registry::{self, WlRegistry}}, Connection, Dispatch, Proxy, QueueHandle};
+
+use crate::KobelRootMessage;
+
+use self::protocol::{zwf_hotspot_v2::{self, ZwfHotspotV2}, zwf_output_v2::{self, ZwfOutputV2}, zwf_shell_manager_v2::ZwfShellManagerV2};
+
+/// How close to the bottom edge the pointer has to be, in logical pixels.
+static HOTSPOT_THRESHOLD: u32 = 2;
+/// How long the pointer has to stay there, so passing by does not count.
+static HOTSPOT_TIMEOUT_MS: u32 = 250;
+
+#[allow(dead_code, non_camel_case_types, non_upper_case_globals, unused_imports, clippy::all)]
+pub mod protocol {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/wayfire-shell-unstable-v2.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/wayfire-shell-unstable-v2.xml");
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KobelHotspotEvent {
+    /// The pointer has rested on the bottom edge of the output with this
+    /// connector name.
+    Entered(String),
+    /// The pointer moved away from the bottom edge again.
+    Left(String),
+}
+
+/// Sends [`KobelRootMessage::Hotspot`] whenever the pointer reaches or leaves
+/// the bottom edge of an output. Outputs are bound on a connection of their
+/// own, so they are told apart by connector name.
+pub fn subscription() -> Subscription<KobelRootMessage> {
+    struct HotspotWatcher;
+
+    Subscription::run_with_id(
+        std::any::TypeId::of::<HotspotWatcher>(),
+        iced::stream::channel(16, |mut output| async move {
+            let (sender, mut events) = mpsc::unbounded_channel();
+
+            let thread = std::thread::Builder::new()
+                .name("kobel-hotspots".to_string())
+                .spawn(move || {
+                    if let Err(e) = run(sender) {
+                        log::warn!("The dock cannot be revealed from the screen edge: {:#}", e);
+                    }
+                });
+
+            if let Err(e) = thread {
+                log::error!("Failed to start watching edge hotspots: {}", e);
+                return;
+            }
+
+            while let Some(event) = events.recv().await {
+                if output.send(KobelRootMessage::Hotspot(event)).await.is_err() {
+                    return;
+                }
+            }
+        }),
+    )
+}
+
+#[derive(Debug)]
+struct KobelHotspotOutput {
+    output: WlOutput,
+    /// Only known once the compositor has sent it, which needs `wl_output` 4.
+    name: Option<String>,
+}
+
+struct KobelHotspotClient {
+    manager: ZwfShellManagerV2,
+    /// By the output's global name in the registry.
+    outputs: HashMap<u32, KobelHotspotOutput>,
+    events: mpsc::UnboundedSender<KobelHotspotEvent>,
+    finished: bool,
+}
+
+fn run(events: mpsc::UnboundedSender<KobelHotspotEvent>) -> anyhow::Result<()> {
+    let connection = Connection::connect_to_env()
+        .context("Failed to connect to the compositor")?;
+    let (globals, mut queue) = registry_queue_init::<KobelHotspotClient>(&connection)
+        .context("Failed to list the compositor's globals")?;
+    let qh = queue.handle();
+
+    let manager: ZwfShellManagerV2 = globals.bind(&qh, 1..=2, ())
+        .context("The compositor does not support wayfire-shell")?;
+
+    let mut client = KobelHotspotClient {
+        manager,
+        outputs: HashMap::new(),
+        events,
+        finished: false,
+    };
+
+    // Outputs plugged in later are announced through the registry
+    let outputs = globals.contents().with_list(|list| {
+        list.iter()
+            .filter(|global| global.interface == WlOutput::interface().name)
+            .map(|global| (global.name, global.version))
+            .collect::<Vec<_>>()
+    });
+    for (name, version) in outputs {
+        client.add_output(globals.registry(), name, version, &qh);
+    }
+
+    while !client.finished {
+        queue.blocking_dispatch(&mut client)
+            .context("Lost the connection to the compositor")?;
+    }
+
+    Ok(())
+}
+
+impl KobelHotspotClient {
+    fn add_output(&mut self, registry: &WlRegistry, name: u32, version: u32, qh: &QueueHandle<Self>) {
+        let output: WlOutput = registry.bind(name, version.min(4), qh, name);
+        let wf_output = self.manager.get_wf_output(&output, qh, name);
+        wf_output.create_hotspot(
+            zwf_output_v2::HotspotEdge::Bottom.into(),
+            HOTSPOT_THRESHOLD,
+            HOTSPOT_TIMEOUT_MS,
+            qh,
+            name,
+        );
+
+        self.outputs.insert(name, KobelHotspotOutput {
+            output,
+            name: None,
+        });
+    }
+
+    fn remove_output(&mut self, name: u32) {
+        let Some(output) = self.outputs.remove(&name) else {
+            return;
+        };
+
+        // The protocol has no destructors, the compositor drops the output's
+        // hotspot along with it
+        if output.output.version() >= 3 {
+            output.output.release();
+        }
+    }
+
+    fn send(&mut self, event: KobelHotspotEvent) {
+        // The shell is gone, so stop once the current events are dispatched
+        if self.events.send(event).is_err() {
+            self.finished = true;
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for KobelHotspotClient {
+    fn event(
+        client: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global { name, interface, version } if interface == WlOutput::interface().name => {
+                client.add_output(registry, name, version, qh);
+            },
+            wl_registry::Event::GlobalRemove { name } => client.remove_output(name),
+            _ => {},
+        }
+    }
+}
+
+impl Dispatch<WlOutput, u32> for KobelHotspotClient {
+    fn event(client: &mut Self, _: &WlOutput, event: wl_output::Event, name: &u32, _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_output::Event::Name { name: connector } = event
+            && let Some(output) = client.outputs.get_mut(name)
+        {
+            output.name = Some(connector);
+        }
+    }
+}
+
+impl Dispatch<ZwfShellManagerV2, ()> for KobelHotspotClient {
+    fn event(_: &mut Self, _: &ZwfShellManagerV2, _: <ZwfShellManagerV2 as Proxy>::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwfOutputV2, u32> for KobelHotspotClient {
+    fn event(_: &mut Self, _: &ZwfOutputV2, _: zwf_output_v2::Event, _: &u32, _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwfHotspotV2, u32> for KobelHotspotClient {
+    fn event(client: &mut Self, _: &ZwfHotspotV2, event: zwf_hotspot_v2::Event, name: &u32, _: &Connection, _: &QueueHandle<Self>) {
+        let Some(connector) = client.outputs.get(name).and_then(|output| output.name.clone()) else {
+            return;
+        };
+
+        match event {
+            zwf_hotspot_v2::Event::Enter => client.send(KobelHotspotEvent::Entered(connector)),
+            zwf_hotspot_v2::Event::Leave => client.send(KobelHotspotEvent::Left(connector)),
+        }
+    }
+}
@@ -29,7 +29,8 @@ pub struct KobelShellButton<'a> {
     mode: KobelShellButtonMode,
     button_type: KobelShellButtonType,
     radii: f32,
-    on_press: KobelRootMessage
+    on_press: KobelRootMessage,
+    on_drag: Option<Box<dyn Fn(button::DragEvent) -> KobelRootMessage + 'a>>,
 }
 
 impl<'a> From<KobelShellButton<'a>> for Element<'a, KobelRootMessage, iced::Theme, iced::Renderer> {
@@ -47,6 +48,7 @@ impl<'a> KobelShellButton<'a> {
             button_type: KobelShellButtonType::default(),
             radii: 8.0,
             on_press: KobelRootMessage::Noop,
+            on_drag: None,
         }
     }
 
@@ -65,6 +67,12 @@ impl<'a> KobelShellButton<'a> {
         self
     }
 
+    /// Makes the button draggable, see [`button::Button::on_drag`].
+    pub fn on_drag(mut self, on_drag: impl Fn(button::DragEvent) -> KobelRootMessage + 'a) -> Self {
+        self.on_drag = Some(Box::new(on_drag));
+        self
+    }
+
     pub fn button_type(mut self, button_type: KobelShellButtonType) -> Self {
         self.button_type = button_type;
         self
@@ -111,27 +119,29 @@ impl<'a> KobelShellButton<'a> {
                 }
             });
 
+        let palette = self.state.palette();
+
         let button_background = match self.button_type {
             KobelShellButtonType::Normal => Background::Color(Color::TRANSPARENT),
-            KobelShellButtonType::Primary => Background::Color(self.state.shell_accent_color),
+            KobelShellButtonType::Primary => Background::Color(palette.accent),
         };
 
         let button_hovered_background = match self.button_type {
-            KobelShellButtonType::Normal => Background::Color(Color::from_rgba(0.5, 0.5, 0.5, 0.1)),
-            KobelShellButtonType::Primary => Background::Color(self.state.shell_accent_color.scale_alpha(0.95)),
+            KobelShellButtonType::Normal => Background::Color(palette.hover),
+            KobelShellButtonType::Primary => Background::Color(palette.accent.scale_alpha(0.95)),
         };
 
         let button_pressed_background = match self.button_type {
-            KobelShellButtonType::Normal => Background::Color(Color::from_rgba(0.5, 0.5, 0.5, 0.2)),
-            KobelShellButtonType::Primary => Background::Color(self.state.shell_accent_color.scale_alpha(0.85)),
+            KobelShellButtonType::Normal => Background::Color(palette.pressed),
+            KobelShellButtonType::Primary => Background::Color(palette.accent.scale_alpha(0.85)),
         };
 
         let button_text_color = match self.button_type {
-            KobelShellButtonType::Normal => self.state.shell_text_color,
-            KobelShellButtonType::Primary => Color::WHITE,
+            KobelShellButtonType::Normal => palette.text,
+            KobelShellButtonType::Primary => palette.accent_text,
         };
 
-        button(button_container)
+        let mut button_ui = button(button_container)
             .width(button_width)
             .height(button_height)
             .padding(button_padding)
@@ -150,8 +160,13 @@ impl<'a> KobelShellButton<'a> {
                     ..Default::default()
                 }
             })
-            .on_press(self.on_press)
-            .into()
+            .on_press(self.on_press);
+
+        if let Some(on_drag) = self.on_drag {
+            button_ui = button_ui.on_drag(on_drag);
+        }
+
+        button_ui.into()
     }
 }
 
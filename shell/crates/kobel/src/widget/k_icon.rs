@@ -1,18 +1,22 @@
-use std::{path::PathBuf, sync::Arc};
+use std::sync::Arc;
 
 use iced::core::image::FilterMethod;
 use iced::widget::{container, row, svg};
 use iced::Element;
 
+use crate::icons::KobelIconHandle;
 use crate::util::debug::debug_border_style_or_default;
 use crate::{state::KobelShellState, KobelRootMessage};
 
 #[derive(Debug)]
 pub struct KobelShellIcon<'a> {
     state: &'a Arc<KobelShellState>,
+    /// A name from the icon theme, such as `firefox`, or a file such as
+    /// `logo.svg` or an absolute path.
     name: String,
 
     size: iced::Length,
+    scale: u32,
     is_symbolic: bool,
     color: Option<iced::Color>,
 }
@@ -25,26 +29,11 @@ impl<'a> From<KobelShellIcon<'a>> for Element<'a, KobelRootMessage, iced::Theme,
 
 impl<'a> KobelShellIcon<'a> {
     pub fn new(state: &'a Arc<KobelShellState>, name: impl Into<String>) -> Self {
-        let name_str: String = name.into();
-
-        let name_pathbuf = PathBuf::from(name_str.clone());
-        let icon_path = if name_pathbuf.has_root() {
-            name_pathbuf
-        } else {
-            state.get_resource_path(&name_str)
-        };
-
-        if !icon_path.exists() || !icon_path.is_file() {
-            log::warn!(
-                "Failed to load icon. The path '{}' either does not exist or is not a valid file.",
-                icon_path.display()
-            );
-        }
-
         Self {
             state,
-            name: icon_path.to_string_lossy().to_string(),
-            size: iced::Length::Fixed(state.icon_base_size),
+            name: name.into(),
+            size: iced::Length::Fixed(state.config().font.icon_base_size),
+            scale: 1,
             is_symbolic: true,
             color: None,
         }
@@ -55,6 +44,12 @@ impl<'a> KobelShellIcon<'a> {
         self
     }
 
+    /// Scale factor of the output the icon is shown on, to pick sharper icons on HiDPI screens.
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
     pub fn symbolic(mut self, symbolic: bool) -> Self {
         self.is_symbolic = symbolic;
         self
@@ -66,18 +61,27 @@ impl<'a> KobelShellIcon<'a> {
     }
 
     pub fn view(self) -> Element<'a, KobelRootMessage, iced::Theme, iced::Renderer> {
-        let icon_path = PathBuf::from(self.name.clone());
+        let size = match self.size {
+            iced::Length::Fixed(size) => size.round() as u32,
+            _ => self.state.config().font.icon_base_size.round() as u32,
+        };
 
-        let icon_element: Element<'a, KobelRootMessage, iced::Theme, iced::Renderer> = match icon_path.extension() {
-            Some(ext) if ext == "svg" => {
-                svg(&icon_path)
+        // Missing icons have already been logged
+        let Some(handle) = self.state.icon(&self.name, size, self.scale, self.is_symbolic) else {
+            return row![].into();
+        };
+        let text_color = self.state.palette().text;
+
+        let icon_element: Element<'a, KobelRootMessage, iced::Theme, iced::Renderer> = match handle {
+            KobelIconHandle::Svg(handle) => {
+                svg(handle)
                     .width(self.size)
                     .height(self.size)
                     .content_fit(iced::ContentFit::Contain)
                     .symbolic(self.is_symbolic)
                     .style(move |_, _| svg::Style {
                         color: if self.is_symbolic {
-                            Some(self.color.unwrap_or(self.state.shell_text_color))
+                            Some(self.color.unwrap_or(text_color))
                         } else {
                             None
                         },
@@ -85,18 +89,14 @@ impl<'a> KobelShellIcon<'a> {
                     })
                     .into()
             }
-            Some(ext) if ext == "png" => {
-                iced::widget::image(&icon_path)
+            KobelIconHandle::Image(handle) => {
+                iced::widget::image(handle)
                     .width(self.size)
                     .height(self.size)
                     .content_fit(iced::ContentFit::Contain)
                     .filter_method(FilterMethod::Linear)
                     .into()
             }
-            _ => {
-                log::warn!("Unsupported icon format for '{}'. Only SVG and PNG are supported.", icon_path.display());
-                return row![].into();
-            }
         };
 
         container(icon_element)
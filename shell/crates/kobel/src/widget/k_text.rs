@@ -41,12 +41,12 @@ impl<'a> KobelShellText<'a> {
 
     pub fn view(self) -> Element<'a, KobelRootMessage, iced::Theme, iced::Renderer> {
         let font = if self.is_bold {
-            self.state.font_bold
+            self.state.font_bold()
         } else {
-            self.state.font
+            self.state.font()
         };
 
-        let font_size = self.size * self.state.font_base_size;
+        let font_size = self.size * self.state.config().font.base_size;
 
         container(
             text(self.contents.clone())
@@ -36,8 +36,8 @@ use iced::core::touch;
 use iced::core::widget::tree::{self, Tree};
 use iced::core::widget::Operation;
 use iced::core::{
-    Background, Clipboard, Color, Element, Layout, Length, Padding, Rectangle,
-    Shadow, Shell, Size, Theme, Vector, Widget,
+    Background, Clipboard, Color, Element, Layout, Length, Padding, Point,
+    Rectangle, Shadow, Shell, Size, Theme, Vector, Widget,
 };
 
 use iced::core::widget::operation;
@@ -87,6 +87,7 @@ where
 {
     content: Element<'a, Message, Theme, Renderer>,
     on_press: Option<OnPress<'a, Message>>,
+    on_drag: Option<Box<dyn Fn(DragEvent) -> Message + 'a>>,
     id: Id,
     #[cfg(feature = "a11y")]
     name: Option<Cow<'a, str>>,
@@ -137,6 +138,7 @@ where
             #[cfg(feature = "a11y")]
             label: None,
             on_press: None,
+            on_drag: None,
             width: size.width.fluid(),
             height: size.height.fluid(),
             padding: DEFAULT_PADDING,
@@ -196,6 +198,17 @@ where
         self
     }
 
+    /// Sets the message that will be produced as the [`Button`] is dragged.
+    ///
+    /// A drag starts once the pointer moves [`DRAG_THRESHOLD`] away from
+    /// where it was pressed, and a drag does not end in a press. Positions
+    /// are relative to the window, and may lie outside of it while the
+    /// pointer is held down.
+    pub fn on_drag(mut self, on_drag: impl Fn(DragEvent) -> Message + 'a) -> Self {
+        self.on_drag = Some(Box::new(on_drag));
+        self
+    }
+
     /// Sets whether the contents of the [`Button`] should be clipped on
     /// overflow.
     pub fn clip(mut self, clip: bool) -> Self {
@@ -263,11 +276,29 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How far the pointer has to move while pressed before a drag starts.
+pub const DRAG_THRESHOLD: f32 = 8.0;
+
+/// A step of dragging a [`Button`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragEvent {
+    /// The pointer moved past [`DRAG_THRESHOLD`], and is now here.
+    Started(Point),
+    Moved(Point),
+    /// The button was released here.
+    Ended(Point),
+    /// The pointer was lost before it was released.
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 struct State {
     is_hovered: bool,
     is_pressed: bool,
     is_focused: bool,
+    pressed_at: Option<Point>,
+    is_dragging: bool,
+    dragged_to: Point,
 }
 
 impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer>
@@ -373,13 +404,40 @@ where
         match event {
             Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
             | Event::Touch(touch::Event::FingerPressed { .. }) => {
-                if self.on_press.is_some() {
+                if self.on_press.is_some() || self.on_drag.is_some() {
                     let bounds = layout.bounds();
 
                     if cursor.is_over(bounds) {
                         let state = tree.state.downcast_mut::<State>();
 
                         state.is_pressed = true;
+                        state.pressed_at = cursor.position();
+
+                        return event::Status::Captured;
+                    }
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position })
+            | Event::Touch(touch::Event::FingerMoved { position, .. }) => {
+                if let Some(on_drag) = self.on_drag.as_ref() {
+                    let state = tree.state.downcast_mut::<State>();
+
+                    if !state.is_dragging
+                        && state.is_pressed
+                        && state.pressed_at.is_some_and(|pressed_at| {
+                            pressed_at.distance(position) > DRAG_THRESHOLD
+                        })
+                    {
+                        state.is_dragging = true;
+                        state.dragged_to = position;
+                        shell.publish(on_drag(DragEvent::Started(position)));
+
+                        return event::Status::Captured;
+                    }
+
+                    if state.is_dragging {
+                        state.dragged_to = position;
+                        shell.publish(on_drag(DragEvent::Moved(position)));
 
                         return event::Status::Captured;
                     }
@@ -387,10 +445,26 @@ where
             }
             Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
             | Event::Touch(touch::Event::FingerLifted { .. }) => {
+                let state = tree.state.downcast_mut::<State>();
+
+                if state.is_dragging {
+                    state.is_dragging = false;
+                    state.is_pressed = false;
+                    state.pressed_at = None;
+
+                    if let Some(on_drag) = self.on_drag.as_ref() {
+                        let position =
+                            cursor.position().unwrap_or(state.dragged_to);
+                        shell.publish(on_drag(DragEvent::Ended(position)));
+                    }
+
+                    return event::Status::Captured;
+                }
+
+                state.pressed_at = None;
+
                 if let Some(on_press) = self.on_press.as_ref().map(OnPress::get)
                 {
-                    let state = tree.state.downcast_mut::<State>();
-
                     if state.is_pressed {
                         state.is_pressed = false;
 
@@ -440,8 +514,17 @@ where
             Event::Touch(touch::Event::FingerLost { .. })
             | Event::Mouse(mouse::Event::CursorLeft) => {
                 let state = tree.state.downcast_mut::<State>();
+
+                if state.is_dragging
+                    && let Some(on_drag) = self.on_drag.as_ref()
+                {
+                    shell.publish(on_drag(DragEvent::Cancelled));
+                }
+
                 state.is_hovered = false;
                 state.is_pressed = false;
+                state.pressed_at = None;
+                state.is_dragging = false;
             }
             _ => {}
         }